@@ -0,0 +1,47 @@
+//! Benchmark the lookup of the color at a position in a document with 10 000 colors,
+//! run with `cargo bench -p color-lsp --bench color_index`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use tower_lsp::lsp_types::Position;
+
+use color_lsp::index::ColorIndex;
+use color_lsp::parser::parse_str;
+
+/// The number of colors in the document, like a large theme JSON file.
+const COLORS: usize = 10_000;
+
+fn color_index(c: &mut Criterion) {
+    let text = (0..COLORS)
+        .map(|ix| {
+            format!(
+                "  \"color-{ix}\": \"#{:06x}\",\n",
+                ix * 0x010203 % 0x100_0000
+            )
+        })
+        .collect::<String>();
+    let nodes = parse_str(&format!("{{\n{text}}}"));
+    assert_eq!(nodes.len(), COLORS);
+
+    let index = ColorIndex::new(nodes.clone());
+    // The color in the middle of the document
+    let position = Position::new(COLORS as u32 / 2 + 1, 20);
+    assert!(index.at(position).is_some());
+
+    c.bench_function("ColorIndex::at 10 000 colors", |b| {
+        b.iter(|| index.at(black_box(position)))
+    });
+    c.bench_function("linear scan 10 000 colors", |b| {
+        b.iter(|| {
+            let position = black_box(position);
+            // The document is ASCII, so the byte columns are the same as the UTF-16 columns
+            let (line, col) = (position.line + 1, position.character + 1);
+            nodes.iter().find(|node| {
+                let range = node.range;
+                range.line == line && range.col <= col && col <= range.col + range.len
+            })
+        })
+    });
+}
+
+criterion_group!(benches, color_index);
+criterion_main!(benches);
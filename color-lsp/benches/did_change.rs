@@ -0,0 +1,63 @@
+//! Benchmark the concurrent document updates, run with `cargo bench -p color-lsp`.
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use tower_lsp::lsp_types::{
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, TextDocumentContentChangeEvent,
+    TextDocumentItem, Url, VersionedTextDocumentIdentifier,
+};
+use tower_lsp::LanguageServer;
+
+/// The number of documents updated at the same time.
+const DOCUMENTS: usize = 10;
+
+fn did_change(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let server = Arc::new(color_lsp::lsp::server());
+
+    // JSON documents don't publish diagnostics, so no client is needed
+    let text = (0..200)
+        .map(|ix| format!("  \"color-{ix}\": \"#{:06x}\",\n", ix * 0x010203))
+        .collect::<String>();
+    let text = format!("{{\n{text}}}");
+    let uris = (0..DOCUMENTS)
+        .map(|ix| Url::parse(&format!("file:///bench/{ix}.json")).unwrap())
+        .collect::<Vec<_>>();
+    runtime.block_on(async {
+        for uri in &uris {
+            let document = TextDocumentItem::new(uri.clone(), "json".into(), 0, text.clone());
+            server
+                .did_open(DidOpenTextDocumentParams {
+                    text_document: document,
+                })
+                .await;
+        }
+    });
+
+    let mut version = 0;
+    c.bench_function("did_change 10 documents concurrently", |b| {
+        b.iter(|| {
+            version += 1;
+            runtime.block_on(async {
+                let mut tasks = tokio::task::JoinSet::new();
+                for uri in &uris {
+                    let server = server.clone();
+                    let params = DidChangeTextDocumentParams {
+                        text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+                        content_changes: vec![TextDocumentContentChangeEvent {
+                            range: None,
+                            range_length: None,
+                            text: text.clone(),
+                        }],
+                    };
+                    tasks.spawn(async move { server.did_change(params).await });
+                }
+                while tasks.join_next().await.is_some() {}
+            });
+        })
+    });
+}
+
+criterion_group!(benches, did_change);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // The build time (Unix seconds) to warn about outdated servers.
+    let build_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    println!("cargo:rustc-env=COLOR_LSP_BUILD_TIME={build_time}");
+}
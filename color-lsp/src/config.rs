@@ -0,0 +1,204 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// The config file name, looked up in the workspace root.
+pub(crate) const CONFIG_FILE: &str = ".color-lsp.json";
+/// The fields of the format choices of the user, persisted across the workspaces in the prefs file.
+const PREFS_FIELDS: &[&str] = &["hex_case", "use_css4_syntax"];
+
+/// The path of the user preferences, `~/.config/color-lsp/prefs.json`, or in `$XDG_CONFIG_HOME`
+/// if it's set.
+pub(crate) fn prefs_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("color-lsp").join("prefs.json"))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Detect hex colors like `#rrggbb`.
+    pub enable_hex: bool,
+    /// Language ids that should not detect `0x` prefixed hex colors, like `0xFF0000`.
+    pub hex_0x_disabled_languages: Vec<String>,
+    /// The case of the hex colors when formatting.
+    pub hex_case: HexCase,
+    /// Upgrade `rgba(255, 0, 0, 0.5)` to CSS Color Level 4 syntax `rgb(255 0 0 / 50%)`
+    /// when formatting CSS documents.
+    pub use_css4_syntax: bool,
+    /// Read 8 digits hex colors in the Android `#AARRGGBB` order instead of the CSS `#RRGGBBAA`.
+    pub enable_android_argb: bool,
+    /// Detect UIKit colors like `UIColor(red: 1.0, green: 0.5, blue: 0.0, alpha: 1.0)`.
+    pub enable_uicolor: bool,
+    /// Detect Go colors like `color.RGBA{R: 255, G: 0, B: 0, A: 255}` and `color.NRGBA{…}`.
+    pub enable_go_color: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HexCase {
+    #[default]
+    Lower,
+    Upper,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enable_hex: true,
+            hex_0x_disabled_languages: ["css", "scss", "sass", "less"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            hex_case: HexCase::default(),
+            use_css4_syntax: false,
+            enable_android_argb: false,
+            enable_uicolor: true,
+            enable_go_color: true,
+        }
+    }
+}
+
+impl Config {
+    /// Return a new config with the fields in the JSON file at `path` overridden,
+    /// like the `.color-lsp.json` of the workspace, unchanged if the file is missing.
+    pub(crate) fn merge_file(&self, path: &Path) -> Result<Self, String> {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Ok(self.clone());
+        };
+
+        serde_json::from_str(&text)
+            .and_then(|settings| self.merge(&settings))
+            .map_err(|err| format!("Invalid {}: {err}", path.display()))
+    }
+
+    /// Write the format choices like `hex_case` to the prefs file at `path`, see [`prefs_path`].
+    pub(crate) fn save_prefs(&self, path: &Path) -> Result<(), String> {
+        let value = serde_json::to_value(self).map_err(|err| err.to_string())?;
+        let prefs = PREFS_FIELDS
+            .iter()
+            .filter_map(|field| Some((field.to_string(), value.get(field)?.clone())))
+            .collect::<serde_json::Map<_, _>>();
+
+        let write = || {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, serde_json::to_string_pretty(&prefs)?)
+        };
+        write().map_err(|err| format!("Failed to write {}: {err}", path.display()))
+    }
+
+    /// Return true if the `settings` JSON object changes any of the format choices.
+    pub(crate) fn has_prefs(settings: &serde_json::Value) -> bool {
+        PREFS_FIELDS
+            .iter()
+            .any(|field| settings.get(field).is_some())
+    }
+
+    /// Return a new config with the fields in the `settings` JSON object overridden.
+    pub(crate) fn merge(&self, settings: &serde_json::Value) -> Result<Self, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if let (Some(fields), Some(overrides)) = (value.as_object_mut(), settings.as_object()) {
+            for (key, field) in overrides {
+                fields.insert(key.clone(), field.clone());
+            }
+        }
+        serde_json::from_value(value)
+    }
+
+    pub(crate) fn hex_0x_enabled(&self, language_id: &str) -> bool {
+        !self
+            .hex_0x_disabled_languages
+            .iter()
+            .any(|lang| lang.eq_ignore_ascii_case(language_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, HexCase, CONFIG_FILE};
+
+    #[test]
+    fn test_merge() {
+        let config = Config {
+            hex_case: HexCase::Upper,
+            ..Default::default()
+        };
+
+        let merged = config
+            .merge(&serde_json::json!({ "enable_hex": false }))
+            .unwrap();
+        assert_eq!(
+            merged,
+            Config {
+                enable_hex: false,
+                hex_case: HexCase::Upper,
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(config.merge(&serde_json::json!(null)).unwrap(), config);
+        assert!(config
+            .merge(&serde_json::json!({ "hex_case": "title" }))
+            .is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir().join(format!("color-lsp-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(CONFIG_FILE);
+        assert_eq!(Config::default().merge_file(&path), Ok(Config::default()));
+
+        std::fs::write(&path, r#"{ "hex_case": "upper" }"#).unwrap();
+        assert_eq!(
+            Config::default().merge_file(&path).unwrap().hex_case,
+            HexCase::Upper
+        );
+
+        std::fs::write(&path, "{ enable_hex: false }").unwrap();
+        let err = Config::default().merge_file(&path).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(err.starts_with("Invalid "));
+    }
+
+    #[test]
+    fn test_save_prefs() {
+        let dir = std::env::temp_dir().join(format!("color-lsp-prefs-{}", std::process::id()));
+        let path = dir.join("color-lsp/prefs.json");
+        let config = Config {
+            hex_case: HexCase::Upper,
+            enable_hex: false,
+            ..Default::default()
+        };
+        config.save_prefs(&path).unwrap();
+        let prefs: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            prefs,
+            serde_json::json!({ "hex_case": "upper", "use_css4_syntax": false })
+        );
+
+        // Only the format choices are restored
+        let restored = Config::default().merge_file(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            restored,
+            Config {
+                hex_case: HexCase::Upper,
+                ..Default::default()
+            }
+        );
+
+        assert!(Config::has_prefs(
+            &serde_json::json!({ "hex_case": "lower" })
+        ));
+        assert!(!Config::has_prefs(
+            &serde_json::json!({ "enable_hex": false })
+        ));
+    }
+}
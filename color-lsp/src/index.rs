@@ -0,0 +1,102 @@
+//! The index of the color nodes of a document by position, for the lookups of hover,
+//! code actions and color presentations in documents with thousands of colors.
+use tower_lsp::lsp_types::{self, ColorInformation, Position};
+
+use crate::parser::ColorNode;
+
+/// The color nodes of a document sorted by the LSP start position, to find the node at a
+/// position with binary search.
+///
+/// The nodes are in one line each, so only the nodes of the same line before the position are
+/// checked, which are few even for nested nodes.
+#[derive(Debug, Clone, Default)]
+pub struct ColorIndex {
+    nodes: Vec<ColorNode>,
+}
+
+impl ColorIndex {
+    pub fn new(mut nodes: Vec<ColorNode>) -> Self {
+        nodes.sort_by_key(|node| node.lsp_range().start);
+        Self { nodes }
+    }
+
+    /// Find the node at the `position`, the end of the node range is included like the start.
+    ///
+    /// If the nodes are adjacent, the first one is returned.
+    pub fn at(&self, position: Position) -> Option<&ColorNode> {
+        let end = self
+            .nodes
+            .partition_point(|node| node.lsp_range().start <= position);
+        self.nodes[..end]
+            .iter()
+            .rev()
+            .take_while(|node| node.lsp_range().start.line == position.line)
+            .filter(|node| position <= node.lsp_range().end)
+            .last()
+    }
+
+    /// The nodes sorted by position.
+    pub fn nodes(&self) -> &[ColorNode] {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The `textDocument/documentColor` result of the nodes.
+    pub fn color_information(&self) -> Vec<ColorInformation> {
+        self.nodes
+            .iter()
+            .map(lsp_types::ColorInformation::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+
+    use super::ColorIndex;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_at() {
+        let text = "a { color: #f00; background: #0f0,#00f; }\n\nb { color: rgb(0 0 0); }";
+        let index = ColorIndex::new(parse(text));
+        assert_eq!(index.len(), 4);
+        let matched = |line, character| {
+            index
+                .at(Position::new(line, character))
+                .map(|node| node.matched.as_str())
+        };
+
+        assert_eq!(matched(0, 10), None);
+        assert_eq!(matched(0, 11), Some("#f00"));
+        assert_eq!(matched(0, 15), Some("#f00"));
+        assert_eq!(matched(0, 16), None);
+        // The end is included
+        assert_eq!(matched(0, 33), Some("#0f0"));
+        assert_eq!(matched(0, 34), Some("#00f"));
+        assert_eq!(matched(1, 0), None);
+        assert_eq!(matched(2, 15), Some("rgb(0 0 0)"));
+        assert_eq!(matched(3, 0), None);
+
+        assert_eq!(ColorIndex::default().at(Position::new(0, 0)), None);
+    }
+
+    #[test]
+    fn test_color_information() {
+        let nodes = parse("#f00 #00f");
+        let index = ColorIndex::new(nodes.iter().rev().cloned().collect());
+        assert_eq!(index.nodes(), nodes);
+        assert_eq!(
+            index.color_information()[1].range.start,
+            Position::new(0, 5)
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! The color language server, and the color parser to extend it with custom color patterns.
+pub mod config;
+pub mod index;
+pub mod lsp;
+pub mod migrate;
+pub mod palette;
+pub mod parser;
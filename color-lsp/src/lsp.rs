@@ -1,63 +1,125 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
-use tower_lsp::jsonrpc::Result;
+use csscolorparser::Color;
+use dashmap::DashMap;
+use tower_lsp::jsonrpc::{Error, Result};
 use tower_lsp::lsp_types::{self, *};
-use tower_lsp::{Client, LanguageServer, LspService, Server};
+use tower_lsp::{Client, ClientSocket, LanguageServer, LspService, Server};
+
+use crate::config::{Config, CONFIG_FILE};
+use crate::index::ColorIndex;
+use crate::palette::ColorPalette;
+use crate::parser::{
+    byte_offset, is_css_language, wcag_aa_passes, ColorNode, ColorPatternRegistry, ColorSpace,
+    DEFAULT_NEAR_THRESHOLD,
+};
 
 const LSP_NAME: &str = "ColorLSP";
+/// The age of the build after which the server is outdated, about 6 months.
+const OUTDATED_AFTER_SECS: u64 = 183 * 24 * 60 * 60;
+/// The delay to scan the changed document, only the last change of quick typing is scanned.
+const SCAN_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
+const GENERATE_TAILWIND_CONFIG_COMMAND: &str = "color-lsp.generateTailwindConfig";
+const SCAN_WORKSPACE_COMMAND: &str = "color-lsp.scanWorkspace";
+/// Return the Xcode Asset Catalog `Contents.json` of the color at the position,
+/// the argument is the `TextDocumentPositionParams`.
+const XCASSETS_COLOR_SET_COMMAND: &str = "color-lsp.xcassetsColorSet";
+/// Convert all the CSS colors of the document to hex with `workspace/applyEdit`,
+/// the argument is the document URI.
+const CONVERT_ALL_TO_HEX_COMMAND: &str = "color-lsp.convertAllToHex";
+/// Find the colors in the document within a ΔE threshold of the color at a position,
+/// the argument is `{ uri, position, threshold }`.
+const FIND_SIMILAR_COLORS_COMMAND: &str = "color-lsp.findSimilarColors";
+/// Return the unique colors of the open documents as a JSON string, see `Backend::palette_json`.
+const PALETTE_COMMAND: &str = "color-lsp.palette";
+/// Return the colors of the document grouped by perceptual similarity, see
+/// `ColorPalette::to_json`, the argument is the document URI.
+const DOCUMENT_PALETTE_COMMAND: &str = "color-lsp.documentPalette";
+/// The no-op command of the color count code lenses, which are informational only.
+const COLOR_COUNT_COMMAND: &str = "color-lsp.colorCount";
+/// The file extensions to scan in `color-lsp.scanWorkspace`, with the language id of the files.
+const SCAN_EXTENSIONS: &[(&str, &str)] = &[
+    ("css", "css"),
+    ("scss", "scss"),
+    ("json", "json"),
+    ("rs", "rust"),
+    ("ts", "typescript"),
+    ("tsx", "typescriptreact"),
+    ("html", "html"),
+];
+/// The dirs to skip in `color-lsp.scanWorkspace`, besides the hidden dirs.
+const SCAN_SKIP_DIRS: &[&str] = &["node_modules", "target"];
+/// The color functions to close the parenthesis of in `on_type_formatting`.
+const AUTO_CLOSE_FUNCTIONS: &[&str] = &["rgb", "rgba", "hsl", "hsla", "oklch"];
 
 struct Backend {
     client: Client,
-    work_dir: RwLock<PathBuf>,
-    documents: RwLock<HashMap<Url, Arc<TextDocumentItem>>>,
-    diagnostics: RwLock<HashMap<Url, Vec<Diagnostic>>>,
-    colors: RwLock<HashMap<Url, Vec<ColorInformation>>>,
+    /// The root dirs of the workspace folders, or the root of the workspace.
+    work_dir_list: RwLock<Vec<PathBuf>>,
+    config: RwLock<Config>,
+    /// Custom color patterns, tried before the built-in patterns.
+    patterns: Option<Arc<ColorPatternRegistry>>,
+    // The documents are updated concurrently, the sharded maps only lock the entries of a URI
+    documents: DashMap<Url, Arc<TextDocumentItem>>,
+    diagnostics: DashMap<Url, Vec<Diagnostic>>,
+    colors: DashMap<Url, Arc<ColorIndex>>,
+    /// The document versions of the cached `colors`, like an `ETag` of the scan results.
+    color_versions: DashMap<Url, i32>,
+    /// The colors of the custom properties declared in the documents, by the property name.
+    custom_properties: DashMap<Url, HashMap<String, ColorNode>>,
+    /// The count of the changes of the documents, to debounce the scans in `did_change`.
+    changes: DashMap<Url, u64>,
+    /// The file to persist the format choices of the user, see `config::prefs_path`.
+    prefs_path: Option<PathBuf>,
 }
 
 #[allow(unused)]
 impl Backend {
-    fn work_dir(&self) -> PathBuf {
-        self.work_dir.read().unwrap().clone()
+    fn work_dir_list(&self) -> Vec<PathBuf> {
+        self.work_dir_list.read().unwrap().clone()
+    }
+
+    fn set_work_dir_list(&self, work_dir_list: Vec<PathBuf>) {
+        *self.work_dir_list.write().unwrap() = work_dir_list;
+    }
+
+    fn config(&self) -> Config {
+        self.config.read().unwrap().clone()
     }
 
-    fn set_work_dir(&self, work_dir: PathBuf) {
-        *self.work_dir.write().unwrap() = work_dir;
+    fn set_config(&self, config: Config) {
+        *self.config.write().unwrap() = config;
     }
 
     fn upsert_document(&self, doc: Arc<TextDocumentItem>) {
-        let uri = doc.uri.clone();
-        self.documents
-            .write()
-            .unwrap()
-            .get_mut(&uri)
-            .map(|old| std::mem::replace(old, doc.clone()));
+        self.documents.insert(doc.uri.clone(), doc);
     }
 
     fn get_document(&self, uri: &Url) -> Option<Arc<TextDocumentItem>> {
-        self.documents.read().unwrap().get(uri).cloned()
+        self.documents.get(uri).map(|doc| doc.clone())
     }
 
     fn remove_document(&self, uri: &Url) {
-        self.documents.write().unwrap().remove(uri);
-        self.colors.write().unwrap().remove(uri);
-        self.diagnostics.write().unwrap().remove(uri);
+        self.documents.remove(uri);
+        self.colors.remove(uri);
+        self.color_versions.remove(uri);
+        self.custom_properties.remove(uri);
+        self.changes.remove(uri);
+        self.diagnostics.remove(uri);
     }
 
     async fn send_diagnostics(&self, document: &TextDocumentItem, diagnostics: Vec<Diagnostic>) {
-        if let Ok(mut map) = self.diagnostics.write() {
-            map.entry(document.uri.clone())
-                .and_modify(|old_diagnostics| old_diagnostics.extend_from_slice(&diagnostics))
-                .or_insert_with(|| diagnostics.clone());
-        }
+        self.diagnostics
+            .insert(document.uri.clone(), diagnostics.clone());
         self.client
-            .publish_diagnostics(document.uri.clone(), diagnostics, None)
+            .publish_diagnostics(document.uri.clone(), diagnostics, Some(document.version))
             .await;
     }
 
     async fn clear_diagnostics(&self, uri: &Url) {
-        self.diagnostics.write().unwrap().remove(uri);
+        self.diagnostics.remove(uri);
         self.client
             .publish_diagnostics(uri.clone(), vec![], None)
             .await;
@@ -66,10 +128,8 @@ impl Backend {
     async fn clear_all_diagnostic(&self) {
         let uris = self
             .documents
-            .read()
-            .unwrap()
-            .keys()
-            .cloned()
+            .iter()
+            .map(|entry| entry.key().clone())
             .collect::<Vec<_>>();
 
         for uri in uris.iter() {
@@ -77,41 +137,363 @@ impl Backend {
         }
     }
 
-    /// Scan all colors vars in the document
-    async fn scan_document(&self, document: &TextDocumentItem) {
-        let input = document.text.as_str();
-        let nodes = crate::parser::parse(input);
-        let mut colors = vec![];
-        for node in nodes.iter() {
-            let info = ColorInformation {
-                range: lsp_types::Range {
-                    start: lsp_types::Position {
-                        line: node.loc.0.saturating_sub(1) as u32,
-                        character: node.loc.1.saturating_sub(1) as u32,
-                    },
-                    end: lsp_types::Position {
-                        line: node.loc.0.saturating_sub(1) as u32,
-                        character: (node.loc.1.saturating_sub(1) + node.matched.len()) as u32,
-                    },
-                },
-                color: node.lsp_color(),
+    /// Create the work done progress and begin it, return false if the client failed to create it.
+    async fn progress_start(&self, token: &NumberOrString, title: &str) -> bool {
+        let params = WorkDoneProgressCreateParams {
+            token: token.clone(),
+        };
+        if self
+            .client
+            .send_request::<request::WorkDoneProgressCreate>(params)
+            .await
+            .is_err()
+        {
+            return false;
+        }
+
+        self.progress(
+            token,
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.into(),
+                percentage: Some(0),
+                ..Default::default()
+            }),
+        )
+        .await;
+        true
+    }
+
+    async fn progress_report(&self, token: &NumberOrString, message: String, percentage: u32) {
+        self.progress(
+            token,
+            WorkDoneProgress::Report(WorkDoneProgressReport {
+                message: Some(message),
+                percentage: Some(percentage),
+                ..Default::default()
+            }),
+        )
+        .await;
+    }
+
+    async fn progress_end(&self, token: &NumberOrString, message: String) {
+        self.progress(
+            token,
+            WorkDoneProgress::End(WorkDoneProgressEnd {
+                message: Some(message),
+            }),
+        )
+        .await;
+    }
+
+    async fn progress(&self, token: &NumberOrString, progress: WorkDoneProgress) {
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(progress),
+            })
+            .await;
+    }
+
+    /// Scan the colors of all supported files in the work dirs, return the number of scanned files.
+    ///
+    /// The open documents are skipped, their colors are scanned from the editor buffer.
+    async fn scan_workspace(&self) -> usize {
+        let token = NumberOrString::String(SCAN_WORKSPACE_COMMAND.into());
+        let progress = self.progress_start(&token, "Scanning colors").await;
+
+        let mut paths = vec![];
+        for work_dir in self.work_dir_list() {
+            let (files, errors) = workspace_files(work_dir).await;
+            for err in errors {
+                self.client.log_message(MessageType::WARNING, err).await;
+            }
+            paths.extend(files);
+        }
+        // The folders can be nested
+        paths.sort();
+        paths.dedup();
+        let mut scanned = 0;
+        for (ix, (path, language_id)) in paths.iter().enumerate() {
+            let Ok(uri) = Url::from_file_path(path) else {
+                continue;
             };
-            colors.push(info);
+            if self.get_document(&uri).is_some() {
+                continue;
+            }
+            let text = match tokio::fs::read_to_string(path).await {
+                Ok(text) => text,
+                Err(err) => {
+                    self.client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("Failed to read {}: {err}", path.display()),
+                        )
+                        .await;
+                    continue;
+                }
+            };
+
+            let document = TextDocumentItem::new(uri.clone(), language_id.to_string(), 0, text);
+            let index = ColorIndex::new(self.parse(&document));
+            self.color_versions.insert(uri.clone(), document.version);
+            self.colors.insert(uri, Arc::new(index));
+            scanned += 1;
+
+            if progress {
+                let percentage = ((ix + 1) * 100 / paths.len()) as u32;
+                self.progress_report(&token, format!("{}/{}", ix + 1, paths.len()), percentage)
+                    .await;
+            }
+        }
+
+        if progress {
+            self.progress_end(&token, format!("Scanned {scanned} files"))
+                .await;
+        }
+        scanned
+    }
+
+    /// Parse the colors of the document with the custom and built-in patterns.
+    fn parse(&self, document: &TextDocumentItem) -> Vec<ColorNode> {
+        let android_colors = self.android_colors(document);
+        parse_document(
+            document,
+            self.patterns.clone(),
+            &self.config(),
+            &android_colors,
+        )
+    }
+
+    /// The colors of the Android `<color>` resources for the `@color/name` references of the XML
+    /// document, from the open documents, or else from the `colors.xml` on disk next to the
+    /// document or in `app/src/main/res/values/` of the workspace roots.
+    fn android_colors(&self, document: &TextDocumentItem) -> HashMap<String, Color> {
+        if document.language_id != "xml" || !document.text.contains("@color/") {
+            return HashMap::new();
+        }
+
+        let mut has_colors_xml = false;
+        let mut open_colors = HashMap::new();
+        for entry in self
+            .documents
+            .iter()
+            .filter(|entry| entry.language_id == "xml")
+        {
+            has_colors_xml |= entry.uri.path().ends_with("/values/colors.xml");
+            open_colors.extend(crate::parser::android_color_resources(&entry.text));
+        }
+
+        let mut colors = HashMap::new();
+        if !has_colors_xml {
+            let sibling = document
+                .uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| Some(path.parent()?.join("colors.xml")));
+            let text = sibling
+                .into_iter()
+                .chain(
+                    self.work_dir_list()
+                        .into_iter()
+                        .map(|dir| dir.join("app/src/main/res/values/colors.xml")),
+                )
+                .find_map(|path| std::fs::read_to_string(path).ok());
+            if let Some(text) = text {
+                colors = crate::parser::android_color_resources(&text);
+            }
+        }
+        colors.extend(open_colors);
+        colors
+    }
+
+    /// Same as `parse`, but on the blocking thread pool, to not block the runtime on large documents.
+    ///
+    /// Return no colors if the parsing panics.
+    async fn parse_blocking(&self, document: &TextDocumentItem) -> Vec<ColorNode> {
+        let patterns = self.patterns.clone();
+        let config = self.config();
+        let android_colors = self.android_colors(document);
+        let task_document = document.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            parse_document(&task_document, patterns, &config, &android_colors)
+        })
+        .await;
+
+        result.unwrap_or_else(|err| {
+            tracing::error!(uri = %document.uri, error = %err, "parse failed");
+            vec![]
+        })
+    }
+
+    /// Group the colors of the document within `DEFAULT_NEAR_THRESHOLD` ΔE.
+    pub fn document_palette(&self, uri: &Url) -> Option<ColorPalette> {
+        let document = self.get_document(uri)?;
+        Some(ColorPalette::from_nodes(
+            self.parse(&document),
+            DEFAULT_NEAR_THRESHOLD,
+        ))
+    }
+
+    /// Return the unique colors of the open documents by hex, sorted by hue then lightness, as
+    /// `[{"hex": "#ff0000", "files": ["file:///a.css"], "count": 3, "layers": ["base"]}]`.
+    ///
+    /// The `layers` are the CSS `@layer`s the color is used in.
+    fn palette_json(&self) -> String {
+        let mut documents = self
+            .documents
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect::<Vec<_>>();
+        documents.sort_by(|a, b| a.uri.cmp(&b.uri));
+
+        struct PaletteColor<'a> {
+            hex: String,
+            node: ColorNode,
+            files: BTreeSet<&'a Url>,
+            count: usize,
+            layers: BTreeSet<String>,
+        }
+
+        let mut palette: Vec<PaletteColor> = vec![];
+        for document in &documents {
+            for node in self.parse(document) {
+                let hex = node.color.to_css_hex();
+                let ix = match palette.iter().position(|color| color.hex == hex) {
+                    Some(ix) => ix,
+                    None => {
+                        palette.push(PaletteColor {
+                            hex,
+                            node: node.clone(),
+                            files: BTreeSet::new(),
+                            count: 0,
+                            layers: BTreeSet::new(),
+                        });
+                        palette.len() - 1
+                    }
+                };
+                let color = &mut palette[ix];
+                color.files.insert(&document.uri);
+                color.count += 1;
+                color.layers.extend(node.layer);
+            }
+        }
+
+        let lightness = |node: &ColorNode| node.color.to_hsla()[2];
+        palette.sort_by(|a, b| {
+            (a.node.hue().total_cmp(&b.node.hue()))
+                .then(lightness(&a.node).total_cmp(&lightness(&b.node)))
+        });
+        let palette = palette
+            .into_iter()
+            .map(|color| {
+                serde_json::json!({
+                    "hex": color.hex,
+                    "files": color.files,
+                    "count": color.count,
+                    "layers": color.layers,
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::Value::from(palette).to_string()
+    }
+
+    /// Return the cached colors of the document, scan the document if not cached
+    /// or the cache is of another version of the document.
+    async fn document_colors(&self, uri: &Url) -> Vec<ColorInformation> {
+        let cached = self.colors.get(uri).map(|index| index.color_information());
+        let document = self.get_document(uri);
+        let is_fresh = document.as_ref().is_none_or(|document| {
+            self.color_versions
+                .get(uri)
+                .is_some_and(|version| *version == document.version)
+        });
+        match (cached, document) {
+            (Some(colors), _) if is_fresh => colors,
+            (_, Some(document)) => {
+                self.scan_document(&document).await;
+                self.colors
+                    .get(uri)
+                    .map(|index| index.color_information())
+                    .unwrap_or_default()
+            }
+            (_, None) => vec![],
         }
+    }
+
+    /// Find the color node at the position of the document, in the cached colors if they are of
+    /// the same version of the document.
+    fn node_at(&self, document: &TextDocumentItem, position: Position) -> Option<ColorNode> {
+        let is_fresh = self
+            .color_versions
+            .get(&document.uri)
+            .is_some_and(|version| *version == document.version);
+        let cached = self
+            .colors
+            .get(&document.uri)
+            .filter(|_| is_fresh)
+            .map(|index| index.clone());
+        let index = cached.unwrap_or_else(|| Arc::new(ColorIndex::new(self.parse(document))));
+        index.at(position).cloned()
+    }
+
+    /// Scan all colors vars in the document
+    async fn scan_document(&self, document: &TextDocumentItem) {
+        let start = std::time::Instant::now();
+        let index = Arc::new(ColorIndex::new(self.parse_blocking(document).await));
+        let nodes = index.nodes();
+        self.colors.insert(document.uri.clone(), index.clone());
+        self.color_versions
+            .insert(document.uri.clone(), document.version);
+        let custom_properties = crate::parser::custom_property_colors(&document.text, nodes);
+        self.custom_properties
+            .insert(document.uri.clone(), custom_properties);
 
-        if let Ok(mut map) = self.colors.write() {
-            map.insert(document.uri.clone(), colors);
+        if is_css_language(&document.language_id) {
+            let mut diagnostics = contrast_diagnostics(&document.text, nodes);
+            diagnostics.extend(near_duplicate_diagnostics(nodes));
+            diagnostics.extend(duplicate_notation_diagnostics(nodes));
+            self.send_diagnostics(document, diagnostics).await;
         }
+        tracing::info!(
+            uri = %document.uri,
+            color_count = nodes.len(),
+            duration_ms = start.elapsed().as_secs_f64() * 1000.,
+            "scan_document"
+        );
     }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
-        if let Some(root_uri) = params.root_uri {
-            let root_path = root_uri.to_file_path().unwrap();
-            self.set_work_dir(root_path.clone());
+        tracing::info!(
+            root_uri = ?params.root_uri,
+            workspace_folders = ?params.workspace_folders,
+            "initialize"
+        );
+        let root_uris = match params.workspace_folders {
+            Some(folders) if !folders.is_empty() => {
+                folders.into_iter().map(|folder| folder.uri).collect()
+            }
+            _ => params.root_uri.into_iter().collect::<Vec<_>>(),
+        };
+        let work_dir_list = root_uris
+            .iter()
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect::<Vec<_>>();
+
+        // The config is loaded from the first root, over the user preferences
+        if let Some(root_path) = work_dir_list.first() {
+            match self.config().merge_file(&root_path.join(CONFIG_FILE)) {
+                Ok(config) => self.set_config(config),
+                Err(err) => {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("{err}, use default config"))
+                        .await;
+                }
+            }
         }
+        self.set_work_dir_list(work_dir_list);
 
         Ok(InitializeResult {
             server_info: Some(ServerInfo {
@@ -130,14 +512,47 @@ impl LanguageServer for Backend {
                     TextDocumentSyncOptions {
                         open_close: Some(true),
                         change: Some(TextDocumentSyncKind::FULL),
+                        will_save_wait_until: Some(true),
                         ..Default::default()
                     },
                 )),
                 color_provider: Some(ColorProviderCapability::Simple(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "(".into(),
+                    more_trigger_character: None,
+                }),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(
+                    true,
+                )),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        GENERATE_TAILWIND_CONFIG_COMMAND.into(),
+                        SCAN_WORKSPACE_COMMAND.into(),
+                        XCASSETS_COLOR_SET_COMMAND.into(),
+                        COLOR_COUNT_COMMAND.into(),
+                        CONVERT_ALL_TO_HEX_COMMAND.into(),
+                        FIND_SIMILAR_COLORS_COMMAND.into(),
+                        PALETTE_COMMAND.into(),
+                        DOCUMENT_PALETTE_COMMAND.into(),
+                    ],
+                    ..Default::default()
+                }),
                 code_action_provider: Some(CodeActionProviderCapability::Options(
                     CodeActionOptions {
                         code_action_kinds: Some(vec![
                             CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_REWRITE,
                             CodeActionKind::SOURCE_FIX_ALL,
                         ]),
                         ..Default::default()
@@ -148,24 +563,61 @@ impl LanguageServer for Backend {
         })
     }
 
-    async fn initialized(&self, _: InitializedParams) {}
+    async fn initialized(&self, _: InitializedParams) {
+        let work_dirs = self
+            .work_dir_list()
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        tracing::info!(%work_dirs, "initialized");
+        self.client
+            .log_message(
+                MessageType::INFO,
+                format!(
+                    "{LSP_NAME} v{} started in {work_dirs}",
+                    env!("CARGO_PKG_VERSION"),
+                ),
+            )
+            .await;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        if is_outdated(env!("COLOR_LSP_BUILD_TIME"), now) {
+            self.client
+                .show_message(
+                    MessageType::WARNING,
+                    format!(
+                        "color-lsp v{} is outdated; please update.",
+                        env!("CARGO_PKG_VERSION")
+                    ),
+                )
+                .await;
+        }
+    }
 
     async fn shutdown(&self) -> Result<()> {
+        tracing::info!("shutdown");
         Ok(())
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        tracing::debug!(uri = %params.text_document.uri, "did_open");
         let DidOpenTextDocumentParams { text_document } = params;
         self.upsert_document(Arc::new(text_document.clone()));
         self.scan_document(&text_document).await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        tracing::debug!(uri = %params.text_document.uri, "did_close");
         let DidCloseTextDocumentParams { text_document } = params;
         self.remove_document(&text_document.uri);
+        self.clear_diagnostics(&text_document.uri).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        tracing::debug!(uri = %params.text_document.uri, "did_change");
         let DidChangeTextDocumentParams {
             text_document,
             content_changes,
@@ -176,58 +628,3570 @@ impl LanguageServer for Backend {
         let change = content_changes.into_iter().next().unwrap();
         assert!(change.range.is_none());
 
-        let updated_doc =
-            TextDocumentItem::new(uri.clone(), "".to_string(), version, change.text.clone());
+        let language_id = self
+            .get_document(&uri)
+            .map(|doc| doc.language_id.clone())
+            .unwrap_or_default();
+        let updated_doc = TextDocumentItem::new(uri.clone(), language_id, version, change.text);
 
         self.upsert_document(Arc::new(updated_doc.clone()));
+
+        let change = {
+            let mut count = self.changes.entry(uri.clone()).or_default();
+            *count += 1;
+            *count
+        };
+        tokio::time::sleep(SCAN_DEBOUNCE).await;
+        // Skip the scan if the document has changed again or has been closed
+        if self.changes.get(&uri).map(|count| *count) != Some(change) {
+            return;
+        }
         self.scan_document(&updated_doc).await;
     }
 
     async fn did_save(&self, _: DidSaveTextDocumentParams) {}
 
-    async fn formatting(&self, _: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
-        Ok(None)
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        tracing::debug!(event = ?params.event, "did_change_workspace_folders");
+        let to_path = |folder: WorkspaceFolder| folder.uri.to_file_path().ok();
+        let removed = params
+            .event
+            .removed
+            .into_iter()
+            .filter_map(to_path)
+            .collect::<Vec<_>>();
+
+        let mut work_dir_list = self.work_dir_list.write().unwrap();
+        work_dir_list.retain(|dir| !removed.contains(dir));
+        for dir in params.event.added.into_iter().filter_map(to_path) {
+            if !work_dir_list.contains(&dir) {
+                work_dir_list.push(dir);
+            }
+        }
     }
 
-    async fn code_action(&self, _: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        return Ok(None);
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        tracing::debug!("did_change_configuration");
+        let Some(settings) = params.settings.get("color-lsp") else {
+            return;
+        };
+
+        match self.config().merge(settings) {
+            Ok(config) => {
+                if let (true, Some(path)) = (Config::has_prefs(settings), &self.prefs_path) {
+                    if let Err(err) = config.save_prefs(path) {
+                        self.client.log_message(MessageType::WARNING, err).await;
+                    }
+                }
+                self.set_config(config);
+                self.colors.clear();
+                self.color_versions.clear();
+
+                // Scan the open documents with the new config, to update the diagnostics
+                let documents = self
+                    .documents
+                    .iter()
+                    .map(|entry| entry.value().clone())
+                    .collect::<Vec<_>>();
+                for document in documents {
+                    self.scan_document(&document).await;
+                }
+            }
+            Err(err) => {
+                self.client
+                    .log_message(MessageType::ERROR, format!("Invalid settings: {err}"))
+                    .await;
+            }
+        }
     }
 
-    async fn document_color(&self, params: DocumentColorParams) -> Result<Vec<ColorInformation>> {
-        // self.client
-        //     .log_message(
-        //         MessageType::INFO,
-        //         format!("-- document_color: {}", params.text_document.uri),
-        //     )
-        //     .await;
-
-        let colors = self
-            .colors
-            .read()
-            .unwrap()
-            .get(&params.text_document.uri)
-            .cloned()
-            .unwrap_or_default();
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        tracing::debug!(uri = %params.text_document.uri, "formatting");
+        let Some(document) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        // Hex-like tokens in other languages are often not colors, e.g. `#123` in comments
+        if !is_css_language(&document.language_id) {
+            return Ok(None);
+        }
+
+        let config = self.config();
+        let nodes = self.parse(&document);
+        let mut edits = hex_format_edits(&document.text, &nodes, &config);
+        if config.use_css4_syntax {
+            edits.extend(css4_syntax_edits(&nodes));
+            edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+        }
+        Ok(Some(edits))
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let position = params.text_document_position;
+        tracing::debug!(uri = %position.text_document.uri, ch = %params.ch, "on_type_formatting");
+        if params.ch != "(" {
+            return Ok(None);
+        }
+        let Some(document) = self.get_document(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(close_color_function_edit(&document.text, position.position).map(|edit| vec![edit]))
+    }
+
+    async fn will_save_wait_until(
+        &self,
+        params: WillSaveTextDocumentParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        tracing::debug!(uri = %params.text_document.uri, "will_save_wait_until");
+        // Only normalize on the saves of the user, not on the auto saves
+        if params.reason != TextDocumentSaveReason::MANUAL {
+            return Ok(None);
+        }
+        let Some(document) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        if !is_css_language(&document.language_id) {
+            return Ok(None);
+        }
+
+        let nodes = self.parse(&document);
+        Ok(Some(hex_format_edits(
+            &document.text,
+            &nodes,
+            &self.config(),
+        )))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        tracing::debug!(uri = %params.text_document.uri, "code_action");
+        let Some(document) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let mut actions = vec![];
+        if let Some(node) = self.node_at(&document, params.range.start) {
+            if is_css_language(&document.language_id) {
+                actions.push(extract_css_variable_action(&document, &node).into());
+            }
+            actions.extend(
+                nearest_named_color_actions(&document, &node)
+                    .into_iter()
+                    .chain(cycle_format_action(&document, &node))
+                    .chain(adjust_color_actions(&document, &node))
+                    .map(CodeActionOrCommand::from),
+            );
+        }
+        if is_css_language(&document.language_id) {
+            let nodes = self.parse(&document);
+            actions
+                .extend(extract_palette_action(&document, &nodes).map(CodeActionOrCommand::from));
+        }
+
+        Ok((!actions.is_empty()).then_some(actions))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        tracing::debug!(uri = %params.text_document.uri, "prepare_rename");
+        let Some(document) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        Ok(
+            custom_property_declaration_at(&document.text, params.position).map(|(range, name)| {
+                PrepareRenameResponse::RangeWithPlaceholder {
+                    range,
+                    placeholder: name,
+                }
+            }),
+        )
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let position = params.text_document_position;
+        tracing::debug!(uri = %position.text_document.uri, "rename");
+        let Some(document) = self.get_document(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let Some((_, name)) = custom_property_declaration_at(&document.text, position.position)
+        else {
+            return Ok(None);
+        };
+        let new_name = params.new_name;
+        let is_valid = new_name.strip_prefix("--").is_some_and(|s| {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+        if !is_valid {
+            return Err(Error::invalid_params(format!(
+                "Invalid custom property name: {new_name}"
+            )));
+        }
+
+        let changes = self
+            .documents
+            .iter()
+            .filter_map(|entry| {
+                let edits = custom_property_rename_edits(&entry.value().text, &name, &new_name);
+                (!edits.is_empty()).then(|| (entry.key().clone(), edits))
+            })
+            .collect::<HashMap<_, _>>();
+        Ok(Some(WorkspaceEdit::new(changes)))
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let position = params.text_document_position_params;
+        tracing::debug!(uri = %position.text_document.uri, "linked_editing_range");
+        let Some(document) = self.get_document(&position.text_document.uri) else {
+            return Ok(None);
+        };
+
+        // Only the custom properties are linked, not the components of the color functions
+        let Some((_, name)) = custom_property_name_at(&document.text, position.position) else {
+            return Ok(None);
+        };
+        let ranges = custom_property_rename_edits(&document.text, &name, &name)
+            .into_iter()
+            .map(|edit| edit.range)
+            .collect();
+        Ok(Some(LinkedEditingRanges {
+            ranges,
+            word_pattern: Some("--[\\w-]+".into()),
+        }))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        tracing::debug!(uri = %params.text_document_position_params.text_document.uri, "hover");
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position_params;
+        let Some(document) = self.get_document(&text_document.uri) else {
+            return Ok(None);
+        };
+        // Resolve the `var(--name)` reference to the custom property declared before it
+        let node = self.node_at(&document, position).or_else(|| {
+            let lines = document.text.lines().collect::<Vec<_>>();
+            let line = position.line as usize;
+            crate::parser::parse_with_context(
+                lines.get(line)?,
+                byte_offset(lines.get(line)?, position.character) + 1,
+                &lines[..line],
+            )
+        });
+        let Some(node) = node else {
+            return Ok(None);
+        };
 
-        // self.client
-        //     .log_message(MessageType::INFO, format!("document_color {:?}\n", colors))
-        //     .await;
+        let mut value = hover_markdown(&node);
+        if let Some(hint) = translucency_hint(&node, &document.language_id) {
+            value.push_str("\n\n");
+            value.push_str(hint);
+        }
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(node.lsp_range()),
+        }))
+    }
 
+    async fn document_color(&self, params: DocumentColorParams) -> Result<Vec<ColorInformation>> {
+        let colors = self.document_colors(&params.text_document.uri).await;
+        tracing::debug!(
+            uri = %params.text_document.uri,
+            color_count = colors.len(),
+            "document_color"
+        );
         Ok(colors)
     }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        tracing::debug!(uri = %params.text_document.uri, "selection_range");
+        let Some(document) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let index = ColorIndex::new(self.parse(&document));
+
+        // One selection range for each position, the position itself if it's not in a color
+        let ranges = params
+            .positions
+            .into_iter()
+            .map(|position| {
+                index.at(position).map_or_else(
+                    || SelectionRange {
+                        range: lsp_types::Range::new(position, position),
+                        parent: None,
+                    },
+                    |node| color_selection_range(node, position),
+                )
+            })
+            .collect();
+        Ok(Some(ranges))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        tracing::debug!(uri = %params.text_document.uri, "code_lens");
+        let Some(document) = self.get_document(&params.text_document.uri) else {
+            return Ok(None);
+        };
+
+        let colors = self.document_colors(&document.uri).await;
+        Ok(Some(color_count_lenses(&document.text, &colors)))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        tracing::debug!(command = %params.command, "execute_command");
+        match params.command.as_str() {
+            GENERATE_TAILWIND_CONFIG_COMMAND => {
+                let prefix = params
+                    .arguments
+                    .first()
+                    .and_then(|arg| arg.as_str())
+                    .unwrap_or("--");
+
+                let mut documents = self
+                    .documents
+                    .iter()
+                    .map(|entry| entry.value().clone())
+                    .collect::<Vec<_>>();
+                documents.sort_by(|a, b| a.uri.cmp(&b.uri));
+                let documents = documents
+                    .iter()
+                    .map(|document| (document.text.as_str(), self.parse(document)))
+                    .collect::<Vec<_>>();
+
+                Ok(Some(tailwind_config(&documents, prefix).into()))
+            }
+            SCAN_WORKSPACE_COMMAND => {
+                let count = self.scan_workspace().await;
+                Ok(Some(count.into()))
+            }
+            XCASSETS_COLOR_SET_COMMAND => {
+                let params = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .map(serde_json::from_value::<TextDocumentPositionParams>)
+                    .ok_or_else(|| Error::invalid_params("Missing the text document position"))?
+                    .map_err(|err| Error::invalid_params(err.to_string()))?;
+
+                let node = self
+                    .get_document(&params.text_document.uri)
+                    .and_then(|document| self.node_at(&document, params.position));
+                Ok(node.map(|node| node.to_xcassets_json()))
+            }
+            COLOR_COUNT_COMMAND => Ok(None),
+            CONVERT_ALL_TO_HEX_COMMAND => {
+                let uri = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .map(serde_json::from_value::<Url>)
+                    .ok_or_else(|| Error::invalid_params("Missing the document URI"))?
+                    .map_err(|err| Error::invalid_params(err.to_string()))?;
+                let Some(document) = self.get_document(&uri) else {
+                    return Ok(None);
+                };
+
+                let edits = hex_conversion_edits(&self.parse(&document));
+                if edits.is_empty() {
+                    return Ok(Some(false.into()));
+                }
+                let edit = WorkspaceEdit::new(HashMap::from([(uri, edits)]));
+                let response = self.client.apply_edit(edit).await?;
+                Ok(Some(response.applied.into()))
+            }
+            FIND_SIMILAR_COLORS_COMMAND => {
+                let params = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .map(serde_json::from_value::<FindSimilarColorsParams>)
+                    .ok_or_else(|| Error::invalid_params("Missing the color position"))?
+                    .map_err(|err| Error::invalid_params(err.to_string()))?;
+                let Some(document) = self.get_document(&params.uri) else {
+                    return Ok(None);
+                };
+
+                let nodes = self.parse(&document);
+                let Some(node) = nodes
+                    .iter()
+                    .find(|node| range_contains(&node.lsp_range(), params.position))
+                else {
+                    return Ok(None);
+                };
+                let colors = similar_colors(node, &nodes, params.threshold)
+                    .into_iter()
+                    .map(|(other, delta_e)| {
+                        let [r, g, b, a] = other.color.to_rgba8();
+                        serde_json::json!({
+                            "uri": params.uri,
+                            "range": other.lsp_range(),
+                            "hex8": format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+                            "delta_e": delta_e,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok(Some(colors.into()))
+            }
+            PALETTE_COMMAND => Ok(Some(self.palette_json().into())),
+            DOCUMENT_PALETTE_COMMAND => {
+                let uri = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .map(serde_json::from_value::<Url>)
+                    .ok_or_else(|| Error::invalid_params("Missing the document URI"))?
+                    .map_err(|err| Error::invalid_params(err.to_string()))?;
+                Ok(self.document_palette(&uri).map(|palette| palette.to_json()))
+            }
+            command => Err(Error::invalid_params(format!("Unknown command: {command}"))),
+        }
+    }
+
+    async fn color_presentation(
+        &self,
+        params: ColorPresentationParams,
+    ) -> Result<Vec<ColorPresentation>> {
+        tracing::debug!(uri = %params.text_document.uri, "color_presentation");
+        let color = csscolorparser::Color::new(
+            params.color.red,
+            params.color.green,
+            params.color.blue,
+            params.color.alpha,
+        );
+
+        let mut presentations = [color.to_css_hex(), color.to_css_rgb(), color.to_css_hsl()]
+            .into_iter()
+            .map(|label| ColorPresentation {
+                text_edit: Some(TextEdit::new(params.range, label.clone())),
+                label,
+                additional_text_edits: None,
+            })
+            .collect::<Vec<_>>();
+        presentations.push(normalized_presentation(&color, params.range));
+
+        if let Some(document) = self.get_document(&params.text_document.uri) {
+            if is_css_language(&document.language_id) {
+                presentations.push(custom_property_presentation(
+                    &document.text,
+                    &color,
+                    params.range,
+                ));
+            }
+            if matches!(document.language_id.as_str(), "java" | "kotlin") {
+                presentations.extend(jvm_presentations(
+                    &document.language_id,
+                    &color,
+                    params.range,
+                ));
+            }
+            if document.language_id == "cpp" {
+                presentations.extend(qt_presentations(&color, params.range));
+            }
+            if document.language_id == "gdscript" {
+                presentations.extend(godot_presentations(&color, params.range));
+            }
+
+            let start = params.range.start;
+            let line = document
+                .text
+                .lines()
+                .nth(start.line as usize)
+                .unwrap_or_default();
+            let property =
+                crate::parser::css_property_name(line, byte_offset(line, start.character));
+
+            let node = self.node_at(&document, start);
+            if let Some(presentation) = node
+                .as_ref()
+                .and_then(|node| theme_presentation(node, &color, params.range))
+            {
+                presentations.insert(0, presentation);
+            }
+            if let Some(presentation) = node
+                .as_ref()
+                .and_then(|node| gradient_presentation(line, node, &color))
+            {
+                presentations.push(presentation);
+            }
+
+            if let (Some("filter" | "backdrop-filter"), Some(node)) = (property, node) {
+                let label = ColorNode { color, ..node }.css_filter_string();
+                presentations.push(ColorPresentation {
+                    text_edit: Some(TextEdit::new(params.range, label.clone())),
+                    label,
+                    additional_text_edits: None,
+                });
+            }
+        }
+
+        Ok(presentations)
+    }
 }
 
-pub async fn start() {
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
-
-    let (service, socket) = LspService::new(|client| Backend {
-        client,
-        work_dir: RwLock::new(PathBuf::new()),
-        documents: RwLock::new(HashMap::new()),
-        diagnostics: RwLock::new(HashMap::new()),
-        colors: RwLock::new(HashMap::new()),
+/// Build the presentations of the color as `java.awt.Color` and Android color int for Java
+/// and Kotlin, which has no `new` keyword.
+fn jvm_presentations(
+    language_id: &str,
+    color: &csscolorparser::Color,
+    range: lsp_types::Range,
+) -> Vec<ColorPresentation> {
+    let [r, g, b, a] = color.to_rgba8();
+    let node = ColorNode::from_rgb_255_tuple(r, g, b, a);
+    let java_color = node.to_java_color();
+    let java_color = match language_id {
+        "kotlin" => java_color.trim_start_matches("new ").to_string(),
+        _ => java_color,
+    };
+
+    [java_color, node.to_android_color_int()]
+        .into_iter()
+        .map(|label| ColorPresentation {
+            text_edit: Some(TextEdit::new(range, label.clone())),
+            label,
+            additional_text_edits: None,
+        })
+        .collect()
+}
+
+/// Build the presentations of the color as Qt `QColor` for C++, with 0..255 channels, the hex
+/// string and 0..1 channels of `QColor::fromRgbF`.
+fn qt_presentations(
+    color: &csscolorparser::Color,
+    range: lsp_types::Range,
+) -> Vec<ColorPresentation> {
+    let [r, g, b, a] = color.to_rgba8();
+    let node = ColorNode::from_rgb_255_tuple(r, g, b, a);
+
+    [
+        node.to_qcolor(),
+        node.to_qcolor_hex(),
+        node.to_qcolor_rgb_f(),
+    ]
+    .into_iter()
+    .map(|label| ColorPresentation {
+        text_edit: Some(TextEdit::new(range, label.clone())),
+        label,
+        additional_text_edits: None,
+    })
+    .collect()
+}
+
+/// Build the presentations of the color as Godot `Color` for GDScript, with 0..1 channels and
+/// the `Color.html()` hex.
+fn godot_presentations(
+    color: &csscolorparser::Color,
+    range: lsp_types::Range,
+) -> Vec<ColorPresentation> {
+    let node = ColorNode::new("", color.clone(), 1, 1);
+
+    [node.to_godot_color_string(), node.to_godot_hex()]
+        .into_iter()
+        .map(|label| ColorPresentation {
+            text_edit: Some(TextEdit::new(range, label.clone())),
+            label,
+            additional_text_edits: None,
+        })
+        .collect()
+}
+
+/// Build the presentation of the color as the Tailwind CSS `theme()` call of the node,
+/// keeping the original call if the color is unchanged, or `None` if the node isn't a `theme()`
+/// call or the color isn't in the default palette.
+fn theme_presentation(
+    node: &ColorNode,
+    color: &csscolorparser::Color,
+    range: lsp_types::Range,
+) -> Option<ColorPresentation> {
+    let args = node.matched.strip_prefix("theme(")?;
+    let label = if node.color.to_rgba8() == color.to_rgba8() {
+        node.matched.clone()
+    } else {
+        let path = crate::parser::tailwind_theme_path(color)?;
+        let quote = args.chars().next().filter(|c| matches!(c, '\'' | '"'));
+        let quote = quote.map(String::from).unwrap_or_default();
+        format!("theme({quote}{path}{quote})")
+    };
+    Some(ColorPresentation {
+        text_edit: Some(TextEdit::new(range, label.clone())),
+        label,
+        additional_text_edits: None,
+    })
+}
+
+/// Build the presentation that replaces the gradient around the node with a single-color
+/// `linear-gradient(#rrggbb, #rrggbb)`, for the properties that only accept gradients,
+/// or `None` if the node isn't in a gradient of the `line`.
+fn gradient_presentation(
+    line: &str,
+    node: &ColorNode,
+    color: &csscolorparser::Color,
+) -> Option<ColorPresentation> {
+    let node_start = node.range.col as usize - 1;
+    let node_end = node_start + node.range.len as usize;
+    let (start, gradient) = line
+        .char_indices()
+        .take_while(|(ix, _)| *ix < node_start)
+        .filter_map(|(ix, _)| Some((ix, crate::parser::take_gradient(&line[ix..])?)))
+        .filter(|(ix, gradient)| ix + gradient.len() >= node_end)
+        .last()?;
+
+    let line_ix = node.range.line - 1;
+    let utf16_col = |offset: usize| line[..offset].encode_utf16().count() as u32;
+    let range = lsp_types::Range::new(
+        Position::new(line_ix, utf16_col(start)),
+        Position::new(line_ix, utf16_col(start + gradient.len())),
+    );
+    let hex = color.to_css_hex();
+    Some(ColorPresentation {
+        label: "Single-color gradient (IE11 fallback)".into(),
+        text_edit: Some(TextEdit::new(
+            range,
+            format!("linear-gradient({hex}, {hex})"),
+        )),
+        additional_text_edits: None,
+    })
+}
+
+/// Build the presentation of the color as `rgba(R, G, B, A)` with integer channels and 2 decimals
+/// alpha, which is stable across reformatting and keeps the diffs of design tokens small.
+fn normalized_presentation(
+    color: &csscolorparser::Color,
+    range: lsp_types::Range,
+) -> ColorPresentation {
+    let [r, g, b, _] = color.to_rgba8();
+    let a = color.a.clamp(0., 1.);
+    ColorPresentation {
+        label: "Normalized (diff-friendly)".into(),
+        text_edit: Some(TextEdit::new(range, format!("rgba({r}, {g}, {b}, {a:.2})"))),
+        additional_text_edits: None,
+    }
+}
+
+/// Return true if the build time (Unix seconds) is more than `OUTDATED_AFTER_SECS` before `now`.
+fn is_outdated(build_time: &str, now: u64) -> bool {
+    build_time
+        .parse::<u64>()
+        .is_ok_and(|build_time| now.saturating_sub(build_time) > OUTDATED_AFTER_SECS)
+}
+
+/// The hover content of the color node, with the color in CSS formats.
+fn hover_markdown(node: &ColorNode) -> String {
+    let color = &node.color;
+    let mut lines = vec![
+        format!("**{}**", color.to_css_hex()),
+        String::new(),
+        format!("- `{}`", color.to_css_rgb()),
+        format!("- `{}`", color.to_css_hsl()),
+    ];
+    if let Some(components) = node
+        .original_space
+        .and_then(|space| space_components(space, color))
+    {
+        lines.push(format!("- {components}"));
+    }
+    if let Some(layer) = &node.layer {
+        lines.push(String::new());
+        lines.push(format!("Layer: `{layer}`"));
+    }
+    if let Some(warning) = node.to_p3_gamut_warning() {
+        lines.push(String::new());
+        lines.push(format!("⚠️ {warning}"));
+    }
+    lines.join("\n")
+}
+
+/// The hover hint of the semi-transparent colors in CSS and CSS-in-JS, which look different
+/// over different backdrops, e.g. the glassmorphism panels with `backdrop-filter: blur()`.
+fn translucency_hint(node: &ColorNode, language_id: &str) -> Option<&'static str> {
+    let is_css = is_css_language(language_id)
+        || matches!(
+            language_id,
+            "javascript" | "typescript" | "javascriptreact" | "typescriptreact"
+        ) && node.is_css_notation();
+    (is_css && node.color.a < 0.9)
+        .then_some("Semi-transparent color; may render differently depending on backdrop.")
+}
+
+/// Format the components of the color in its original color space, like the lightness, chroma
+/// and hue of `oklch()`, or `None` for the spaces already shown as `rgb()` and `hsl()`.
+fn space_components(space: ColorSpace, color: &csscolorparser::Color) -> Option<String> {
+    let degrees = |h: f32| format!("{:.2}°", h.to_degrees().rem_euclid(360.));
+    let components = match space {
+        ColorSpace::Srgb | ColorSpace::Hsl => return None,
+        ColorSpace::Hwb => {
+            let [h, w, b, _] = color.to_hwba();
+            format!(
+                "HWB: H `{:.2}°` W `{:.1}%` B `{:.1}%`",
+                if h.is_nan() { 0. } else { h },
+                w * 100.,
+                b * 100.
+            )
+        }
+        ColorSpace::Lab => {
+            let [l, a, b, _] = color.to_laba();
+            format!("Lab: L `{l:.2}` a `{a:.2}` b `{b:.2}`")
+        }
+        ColorSpace::Lch => {
+            let [l, c, h, _] = color.to_lcha();
+            format!("LCH: L `{l:.2}` C `{c:.2}` H `{}`", degrees(h))
+        }
+        ColorSpace::Oklab => {
+            let [l, a, b, _] = color.to_oklaba();
+            format!("OKLab: L `{l:.3}` a `{a:.3}` b `{b:.3}`")
+        }
+        ColorSpace::Oklch => {
+            let [l, c, h, _] = color.to_oklcha();
+            format!("OKLCH: L `{l:.3}` C `{c:.3}` H `{}`", degrees(h))
+        }
+    };
+    Some(components)
+}
+
+/// Build a code lens at the start of each top-level `{}` block, with the count of colors in it.
+fn color_count_lenses(text: &str, colors: &[ColorInformation]) -> Vec<CodeLens> {
+    let mut blocks = vec![];
+    let mut depth = 0usize;
+    let mut open = Position::default();
+
+    for (line, line_text) in text.lines().enumerate() {
+        let mut character = 0;
+        for c in line_text.chars() {
+            let position = Position::new(line as u32, character);
+            character += c.len_utf16() as u32;
+            match c {
+                '{' => {
+                    if depth == 0 {
+                        open = position;
+                    }
+                    depth += 1;
+                }
+                '}' if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        blocks.push(lsp_types::Range::new(open, position));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    blocks
+        .into_iter()
+        .map(|block| {
+            let count = colors
+                .iter()
+                .filter(|info| range_contains(&block, info.range.start))
+                .count();
+            let title = match count {
+                1 => "1 color".to_string(),
+                n => format!("{n} colors"),
+            };
+            let line_start = Position::new(block.start.line, 0);
+
+            CodeLens {
+                range: lsp_types::Range::new(line_start, line_start),
+                command: Some(Command::new(title, COLOR_COUNT_COMMAND.into(), None)),
+                data: None,
+            }
+        })
+        .collect()
+}
+
+/// Generate a `tailwind.config.js` with the colors of the documents as `theme.colors`.
+///
+/// Colors declared as custom properties are named by the property, without the `prefix`,
+/// e.g. `--color-primary: #…` is `"primary": "#…"` with the `--color-` prefix.
+/// Other colors get a generated `color-N` name.
+fn tailwind_config(documents: &[(&str, Vec<ColorNode>)], prefix: &str) -> String {
+    let mut colors: Vec<(String, String)> = vec![];
+    let mut generated = 0;
+
+    for (text, nodes) in documents {
+        for node in nodes {
+            let hex = node.color.to_css_hex();
+            if colors.iter().any(|(_, value)| value == &hex) {
+                continue;
+            }
+
+            let line = text
+                .lines()
+                .nth(node.range.line as usize - 1)
+                .unwrap_or_default();
+            let property = crate::parser::custom_property_declarations(line)
+                .into_iter()
+                .find(|(_, offset, _)| *offset as u32 == node.range.col - 1)
+                .map(|(name, _, _)| {
+                    name.strip_prefix(prefix)
+                        .unwrap_or(name.trim_start_matches('-'))
+                        .to_string()
+                });
+            let name = match property {
+                Some(name) if !colors.iter().any(|(key, _)| key == &name) => name,
+                _ => {
+                    generated += 1;
+                    format!("color-{generated}")
+                }
+            };
+            colors.push((name, hex));
+        }
+    }
+
+    let mut config = String::from("module.exports = {\n  theme: {\n    colors: {\n");
+    for (name, hex) in colors {
+        config.push_str(&format!("      \"{name}\": \"{hex}\",\n"));
+    }
+    config.push_str("    },\n  },\n};\n");
+    config
+}
+
+/// Check the WCAG contrast ratio of the `color` and `background(-color)` pairs on the same
+/// or adjacent lines, the diagnostic is on the foreground color.
+fn contrast_diagnostics(text: &str, nodes: &[ColorNode]) -> Vec<Diagnostic> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let property = |node: &ColorNode| {
+        let line = lines.get(node.range.line as usize - 1)?;
+        crate::parser::css_property_name(line, node.range.col as usize - 1)
+    };
+
+    let mut diagnostics = vec![];
+    for foreground in nodes.iter().filter(|node| property(node) == Some("color")) {
+        let background = nodes.iter().find(|node| {
+            node.range.line.abs_diff(foreground.range.line) <= 1
+                && matches!(property(node), Some("background" | "background-color"))
+        });
+        let Some(background) = background else {
+            continue;
+        };
+
+        if wcag_aa_passes(foreground, background, false) {
+            continue;
+        }
+        // Round down, so the ratio just below 4.5 is not displayed as 4.5
+        let ratio = (foreground.contrast_ratio_with(background) * 10.).floor() / 10.;
+        let message = if wcag_aa_passes(foreground, background, true) {
+            format!("Contrast ratio {ratio:.1}:1 is below WCAG AA (4.5:1)")
+        } else {
+            format!("Contrast ratio {ratio:.1}:1 is below WCAG AA for large text (3:1)")
+        };
+
+        diagnostics.push(Diagnostic {
+            range: foreground.lsp_range(),
+            severity: Some(DiagnosticSeverity::HINT),
+            source: Some(LSP_NAME.into()),
+            message,
+            ..Default::default()
+        });
+    }
+
+    diagnostics
+}
+
+/// Hint the colors that are near a different color before them, which is likely meant to be the same.
+fn near_duplicate_diagnostics(nodes: &[ColorNode]) -> Vec<Diagnostic> {
+    nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, node)| {
+            let rgba = node.to_rgb_255_tuple();
+            let before = &nodes[..ix];
+            // The color has been used as is
+            if before.iter().any(|other| other.to_rgb_255_tuple() == rgba) {
+                return None;
+            }
+            let near = before.iter().find(|other| {
+                other.to_rgb_255_tuple().3 == rgba.3 && node.is_near(other, DEFAULT_NEAR_THRESHOLD)
+            })?;
+
+            Some(Diagnostic {
+                range: node.lsp_range(),
+                severity: Some(DiagnosticSeverity::HINT),
+                source: Some(LSP_NAME.into()),
+                message: format!(
+                    "Color is near `{}` on line {}, consider using the same color",
+                    near.matched, near.range.line
+                ),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Inform the colors that appear before in another notation, e.g. `red` after `#ff0000`,
+/// to keep the notation of a color consistent in the document.
+///
+/// Only the CSS notations are compared, not the references like `var(--red)`.
+fn duplicate_notation_diagnostics(nodes: &[ColorNode]) -> Vec<Diagnostic> {
+    let mut groups: HashMap<String, Vec<&ColorNode>> = HashMap::new();
+    let mut diagnostics = vec![];
+
+    for node in nodes.iter().filter(|node| node.is_css_notation()) {
+        let hex = node.color.to_css_hex();
+        let group = groups.entry(hex.clone()).or_default();
+        if let Some(other) = group.iter().find(|other| other.matched != node.matched) {
+            diagnostics.push(Diagnostic {
+                range: node.lsp_range(),
+                severity: Some(DiagnosticSeverity::INFORMATION),
+                source: Some(LSP_NAME.into()),
+                message: format!(
+                    "Color {hex} also appears as '{}' on line {}",
+                    other.matched, other.range.line
+                ),
+                ..Default::default()
+            });
+        }
+        group.push(node);
+    }
+
+    diagnostics
+}
+
+/// Find all files with `SCAN_EXTENSIONS` in the dir recursively, with their language id,
+/// and the errors of the dirs that failed to read.
+///
+/// The hidden dirs and `SCAN_SKIP_DIRS` are skipped.
+async fn workspace_files(dir: PathBuf) -> (Vec<(PathBuf, &'static str)>, Vec<String>) {
+    let mut files = vec![];
+    let mut errors = vec![];
+    let mut dirs = vec![dir];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(err) => {
+                errors.push(format!("Failed to read dir {}: {err}", dir.display()));
+                continue;
+            }
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+
+            if file_type.is_dir() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with('.') && !SCAN_SKIP_DIRS.contains(&name.as_str()) {
+                    dirs.push(path);
+                }
+            } else if let Some(&(_, language_id)) = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .and_then(|ext| SCAN_EXTENSIONS.iter().find(|(e, _)| *e == ext))
+            {
+                files.push((path, language_id));
+            }
+        }
+    }
+
+    files.sort();
+    (files, errors)
+}
+
+fn range_contains(range: &lsp_types::Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+/// Build the edit to insert `)` at the position right after a color function opening like `rgb(`,
+/// which leaves the cursor between the parentheses.
+///
+/// Return `None` if the parenthesis is already closed, e.g. by the editor.
+fn close_color_function_edit(text: &str, position: Position) -> Option<TextEdit> {
+    let line = text.lines().nth(position.line as usize)?;
+    let offset = byte_offset(line, position.character);
+    let before = line[..offset].strip_suffix('(')?;
+    let is_function = AUTO_CLOSE_FUNCTIONS.iter().any(|name| {
+        before.strip_suffix(name).is_some_and(|s| {
+            !s.ends_with(|c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        })
     });
+    if !is_function || line[offset..].starts_with(')') {
+        return None;
+    }
+    Some(TextEdit::new(
+        lsp_types::Range::new(position, position),
+        ")".into(),
+    ))
+}
+
+/// Find the CSS custom property name like `--name` at the position, in a declaration or
+/// a `var()` reference, return the range of the name and the name.
+fn custom_property_name_at(text: &str, position: Position) -> Option<(lsp_types::Range, String)> {
+    let line = text.lines().nth(position.line as usize)?;
+    let offset = byte_offset(line, position.character);
+    let (start, name) = crate::parser::custom_property_names(line)
+        .into_iter()
+        .find(|(start, name)| (*start..=start + name.len()).contains(&offset))?;
+
+    let utf16_col = |offset: usize| line[..offset].encode_utf16().count() as u32;
+    let range = lsp_types::Range::new(
+        Position::new(position.line, utf16_col(start)),
+        Position::new(position.line, utf16_col(start + name.len())),
+    );
+    Some((range, name.to_string()))
+}
+
+/// Find the CSS custom property name of the declaration like `--name: value` at the position,
+/// return the range of the name and the name.
+fn custom_property_declaration_at(
+    text: &str,
+    position: Position,
+) -> Option<(lsp_types::Range, String)> {
+    let (range, name) = custom_property_name_at(text, position)?;
+    let line = text.lines().nth(position.line as usize)?;
+    let end = byte_offset(line, range.end.character);
+    line[end..]
+        .trim_start()
+        .starts_with(':')
+        .then_some((range, name))
+}
+
+/// Build the edits to rename the CSS custom property `name` in the declarations and
+/// the `var()` references of the text.
+fn custom_property_rename_edits(text: &str, name: &str, new_name: &str) -> Vec<TextEdit> {
+    let mut edits = vec![];
+    for (ix, line) in text.lines().enumerate() {
+        let utf16_col = |offset: usize| line[..offset].encode_utf16().count() as u32;
+        for (start, _) in crate::parser::custom_property_names(line)
+            .into_iter()
+            .filter(|(_, n)| *n == name)
+        {
+            let range = lsp_types::Range::new(
+                Position::new(ix as u32, utf16_col(start)),
+                Position::new(ix as u32, utf16_col(start + name.len())),
+            );
+            edits.push(TextEdit::new(range, new_name.to_string()));
+        }
+    }
+    edits
+}
+
+/// Build the selection range of the color at the position, from the arguments of
+/// the color function like `255, 0, 0` to the whole color like `rgb(255, 0, 0)`.
+fn color_selection_range(node: &ColorNode, position: Position) -> SelectionRange {
+    let range = node.lsp_range();
+    let whole = SelectionRange {
+        range,
+        parent: None,
+    };
+    let Some(open) = node
+        .matched
+        .strip_suffix(')')
+        .and_then(|matched| matched.find('('))
+    else {
+        return whole;
+    };
+
+    let start = range.start.character + node.matched[..=open].encode_utf16().count() as u32;
+    let args = lsp_types::Range::new(
+        Position::new(range.start.line, start),
+        Position::new(range.end.line, range.end.character - 1),
+    );
+    if !range_contains(&args, position) {
+        return whole;
+    }
+    SelectionRange {
+        range: args,
+        parent: Some(Box::new(whole)),
+    }
+}
 
-    Server::new(stdin, stdout, socket).serve(service).await;
+/// Build a code action to move the color into a `:root` custom property named by its hex,
+/// and replace it with a `var()` reference.
+fn extract_css_variable_action(document: &TextDocumentItem, node: &ColorNode) -> CodeAction {
+    let name = format!("--color-{}", &node.color.to_css_hex()[1..]);
+    let mut edits = custom_property_edits(&document.text, &name, &node.matched);
+    edits.push(TextEdit::new(node.lsp_range(), format!("var({name})")));
+
+    CodeAction {
+        title: "Extract to CSS variable".into(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(document.uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Build the action to move all the unique colors of the document into `--color-N` custom
+/// properties of a new `:root` rule, replacing the color literals with `var()` references.
+///
+/// The colors are declared as `#rrggbbaa` in the order they first appear, the numbers already
+/// declared in the document are skipped.
+fn extract_palette_action(document: &TextDocumentItem, nodes: &[ColorNode]) -> Option<CodeAction> {
+    let nodes = nodes
+        .iter()
+        .filter(|node| node.is_css_notation())
+        .collect::<Vec<_>>();
+    if nodes.is_empty() {
+        return None;
+    }
+
+    let mut names = (1..)
+        .map(|n| format!("--color-{n}"))
+        .filter(|name| !document.text.contains(&format!("{name}:")));
+    let mut palette: Vec<(String, &ColorNode)> = vec![];
+    let mut edits = vec![];
+    for node in nodes {
+        let rgba = node.color.to_rgba8();
+        let name = match palette
+            .iter()
+            .find(|(_, other)| other.color.to_rgba8() == rgba)
+        {
+            Some((name, _)) => name.clone(),
+            None => {
+                let name = names.next()?;
+                palette.push((name.clone(), node));
+                name
+            }
+        };
+        edits.push(TextEdit::new(node.lsp_range(), format!("var({name})")));
+    }
+
+    let declarations = palette
+        .iter()
+        .map(|(name, node)| node.to_css_custom_property_declaration(name))
+        .collect::<Option<Vec<_>>>()?;
+    edits.insert(0, new_root_rule_edit(&document.text, &declarations));
+
+    Some(CodeAction {
+        title: "Extract palette to :root".into(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(document.uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Build the edit to declare the custom property in the first `:root` rule of the CSS `text`,
+/// or in a new `:root` rule after the leading `@charset` and `@import` rules.
+///
+/// Return no edit if the property has been declared.
+fn custom_property_edits(text: &str, name: &str, value: &str) -> Vec<TextEdit> {
+    if text.contains(&format!("{name}:")) {
+        return vec![];
+    }
+
+    let declaration = format!("{name}: {value};");
+    let edit = match root_rule_body(text) {
+        Some(offset) => {
+            let position = offset_to_position(text, offset);
+            TextEdit::new(
+                lsp_types::Range::new(position, position),
+                format!("\n  {declaration}"),
+            )
+        }
+        None => new_root_rule_edit(text, &[declaration]),
+    };
+    vec![edit]
+}
+
+/// Build the edit to insert a new `:root` rule with the `declarations`, after the leading
+/// `@charset` and `@import` rules of the CSS `text`.
+fn new_root_rule_edit(text: &str, declarations: &[String]) -> TextEdit {
+    let lines = text.lines().collect::<Vec<_>>();
+    let at_rules = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with("@charset") && !line.starts_with("@import")
+        })
+        .unwrap_or(lines.len());
+    let at_rules = lines[..at_rules]
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |ix| ix + 1);
+
+    let rule = declarations
+        .iter()
+        .map(|declaration| format!("  {declaration}\n"))
+        .collect::<String>();
+    let rule = format!(":root {{\n{rule}}}\n");
+    if at_rules == 0 {
+        TextEdit::new(
+            lsp_types::Range::new(Position::new(0, 0), Position::new(0, 0)),
+            format!("{rule}\n"),
+        )
+    } else if at_rules < lines.len() || text.ends_with('\n') {
+        let position = Position::new(at_rules as u32, 0);
+        TextEdit::new(
+            lsp_types::Range::new(position, position),
+            format!("\n{rule}"),
+        )
+    } else {
+        let position = offset_to_position(text, text.len());
+        TextEdit::new(
+            lsp_types::Range::new(position, position),
+            format!("\n\n{rule}"),
+        )
+    }
+}
+
+/// Return the byte offset after the `{` of the first `:root` rule in the CSS `text`,
+/// skipping the `:root` in comments and in other selectors like `.a:root-ish`.
+fn root_rule_body(text: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(ix) = text[from..].find(":root") {
+        let start = from + ix;
+        from = start + ":root".len();
+
+        let in_comment = text[..start]
+            .rfind("/*")
+            .is_some_and(|open| !text[open..start].contains("*/"));
+        let line_start = text[..start].rfind('\n').map_or(0, |ix| ix + 1);
+        let in_line_comment = text[line_start..start].trim_start().starts_with("//");
+        let is_selector_start = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || matches!(c, '}' | ';' | ','));
+        if in_comment || in_line_comment || !is_selector_start {
+            continue;
+        }
+
+        let after = &text[from..];
+        let rest = after.trim_start();
+        if rest.starts_with('{') {
+            return Some(from + after.len() - rest.len() + 1);
+        }
+    }
+    None
+}
+
+/// Convert the byte offset of the text to the LSP position, in UTF-16 code units.
+fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |ix| ix + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+/// Build the code action to rewrite the color in the next notation of the cycle
+/// hex → `rgb()` → `hsl()` → `oklch()` → hex, other CSS notations like named colors go to hex.
+///
+/// Only for the CSS colors in CSS and HTML, where all the notations are valid.
+fn cycle_format_action(document: &TextDocumentItem, node: &ColorNode) -> Option<CodeAction> {
+    let language_id = document.language_id.as_str();
+    if !(is_css_language(language_id) || language_id == "html") || !node.is_css_notation() {
+        return None;
+    }
+
+    let matched = node.matched.to_ascii_lowercase();
+    let new_text = if matched.starts_with('#') {
+        node.to_rgb_string()
+    } else if matched.starts_with("rgb(") || matched.starts_with("rgba(") {
+        node.to_hsl_string()
+    } else if matched.starts_with("hsl(") || matched.starts_with("hsla(") {
+        node.to_oklch_string()
+    } else {
+        node.to_hex_string()
+    };
+
+    let edit = TextEdit::new(node.lsp_range(), new_text);
+    Some(CodeAction {
+        title: "Cycle color format".into(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(document.uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Build the code action to replace the color with the nearest CSS named color,
+/// or the next two nearest if it's already a named color.
+///
+/// Only for the CSS colors in CSS and HTML, where the named colors are valid.
+fn nearest_named_color_actions(document: &TextDocumentItem, node: &ColorNode) -> Vec<CodeAction> {
+    let language_id = document.language_id.as_str();
+    if !(is_css_language(language_id) || language_id == "html") || !node.is_css_notation() {
+        return vec![];
+    }
+    // The named colors are opaque
+    if node.color.a < 1. {
+        return vec![];
+    }
+
+    let is_named = csscolorparser::NAMED_COLORS.contains_key(node.matched.to_lowercase().as_str());
+    let names = crate::parser::nearest_named_colors(&node.color)
+        .into_iter()
+        .filter(|(_, distance)| !is_named || *distance > 0.)
+        // Only suggest the named color that looks the same, unless it's already a named color
+        .filter(|(name, _)| {
+            is_named
+                || csscolorparser::parse(name).is_ok_and(|color| {
+                    node.is_near(
+                        &ColorNode {
+                            color,
+                            ..node.clone()
+                        },
+                        DEFAULT_NEAR_THRESHOLD,
+                    )
+                })
+        })
+        .take(if is_named { 2 } else { 1 })
+        .map(|(name, _)| name);
+
+    names
+        .map(|name| {
+            let edit = TextEdit::new(node.lsp_range(), name.to_string());
+            CodeAction {
+                title: format!("Replace with nearest named color `{name}`"),
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(document.uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Build the code actions to darken, lighten, saturate and desaturate the color by 10% in HSL.
+fn adjust_color_actions(document: &TextDocumentItem, node: &ColorNode) -> Vec<CodeAction> {
+    [
+        ("Darken 10%", 0., -0.1),
+        ("Lighten 10%", 0., 0.1),
+        ("Saturate 10%", 0.1, 0.),
+        ("Desaturate 10%", -0.1, 0.),
+    ]
+    .into_iter()
+    .filter_map(|(title, saturation, lightness)| {
+        let color = adjust_hsl(&node.color, saturation, lightness);
+        // Skip the colors that can't be written back and the no-op adjustments
+        let text = node
+            .format_like(&color)
+            .filter(|text| text != &node.matched)?;
+        let edit = TextEdit::new(node.lsp_range(), text);
+
+        Some(CodeAction {
+            title: title.into(),
+            kind: Some(CodeActionKind::REFACTOR_REWRITE),
+            edit: Some(WorkspaceEdit {
+                changes: Some(HashMap::from([(document.uri.clone(), vec![edit])])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    })
+    .collect()
+}
+
+/// Add the deltas to the HSL saturation and lightness of the color, clamped to 0..1.
+fn adjust_hsl(
+    color: &csscolorparser::Color,
+    saturation: f32,
+    lightness: f32,
+) -> csscolorparser::Color {
+    let [h, s, l, a] = color.to_hsla();
+    csscolorparser::Color::from_hsla(
+        h,
+        (s + saturation).clamp(0., 1.),
+        (l + lightness).clamp(0., 1.),
+        a,
+    )
+}
+
+/// Build the edits to normalize the hex colors in CSS declaration values, in reverse document order.
+///
+/// Hex-like tokens in selectors (`#add`) and comments (`/* #123 */`) are kept as is.
+fn hex_format_edits(text: &str, nodes: &[ColorNode], config: &Config) -> Vec<TextEdit> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut edits = nodes
+        .iter()
+        .filter(|node| {
+            let line = lines.get(node.range.line as usize - 1).unwrap_or(&"");
+            is_declaration_value(line, node.range.col as usize - 1)
+        })
+        .filter_map(|node| {
+            let hex = node.normalized_hex(config.hex_case)?;
+            (hex != node.matched).then(|| TextEdit::new(node.lsp_range(), hex))
+        })
+        .collect::<Vec<_>>();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+    edits
+}
+
+/// Return true if the byte `offset` of the CSS `line` is in a declaration value,
+/// not in a selector like `a:hover #bad {` or a comment.
+fn is_declaration_value(line: &str, offset: usize) -> bool {
+    let rest = line.get(offset..).unwrap_or_default();
+    let value_end = rest.find([';', '}']).unwrap_or(rest.len());
+    crate::parser::css_property_name(line, offset).is_some() && !rest[..value_end].contains('{')
+}
+
+/// The argument of `color-lsp.findSimilarColors`.
+#[derive(serde::Deserialize)]
+struct FindSimilarColorsParams {
+    uri: Url,
+    position: Position,
+    threshold: f64,
+}
+
+/// The other colors within the `threshold` ΔE of the `node` with their ΔE, the nearest first.
+fn similar_colors<'a>(
+    node: &ColorNode,
+    nodes: &'a [ColorNode],
+    threshold: f64,
+) -> Vec<(&'a ColorNode, f64)> {
+    let mut colors = nodes
+        .iter()
+        .filter(|other| other.range != node.range)
+        .map(|other| (other, node.delta_e(other)))
+        .filter(|(_, delta_e)| *delta_e <= threshold)
+        .collect::<Vec<_>>();
+    colors.sort_by(|a, b| a.1.total_cmp(&b.1));
+    colors
+}
+
+/// Build the edits to convert the CSS colors other than hex, like `rgb()` and named colors,
+/// to lowercase hex, in reverse document order.
+fn hex_conversion_edits(nodes: &[ColorNode]) -> Vec<TextEdit> {
+    let mut edits = nodes
+        .iter()
+        .filter(|node| !node.matched.starts_with('#') && node.is_css_notation())
+        .map(|node| TextEdit::new(node.lsp_range(), node.color.to_css_hex()))
+        .collect::<Vec<_>>();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+    edits
+}
+
+/// Build the edits to upgrade the legacy color functions to CSS Color Level 4 syntax,
+/// in reverse document order.
+fn css4_syntax_edits(nodes: &[ColorNode]) -> Vec<TextEdit> {
+    let mut edits = nodes
+        .iter()
+        .filter_map(|node| Some(TextEdit::new(node.lsp_range(), node.css4_syntax()?)))
+        .collect::<Vec<_>>();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+    edits
+}
+
+/// Build a presentation that moves the color into a `:root` CSS custom property named by its hex,
+/// like the extract code action, replacing the token with a `var()` reference.
+fn custom_property_presentation(
+    text: &str,
+    color: &csscolorparser::Color,
+    range: lsp_types::Range,
+) -> ColorPresentation {
+    let hex = color.to_css_hex();
+    let name = format!("--color-{}", &hex[1..]);
+    let edits = custom_property_edits(text, &name, &hex);
+    let label = format!("var({name})");
+
+    ColorPresentation {
+        text_edit: Some(TextEdit::new(range, label.clone())),
+        label,
+        additional_text_edits: (!edits.is_empty()).then_some(edits),
+    }
+}
+
+/// Parse the colors of the document with the custom `patterns` and the built-in patterns,
+/// with the Android `@color/name` references resolved by `android_colors`.
+fn parse_document(
+    document: &TextDocumentItem,
+    patterns: Option<Arc<ColorPatternRegistry>>,
+    config: &Config,
+    android_colors: &HashMap<String, Color>,
+) -> Vec<ColorNode> {
+    let start = std::time::Instant::now();
+    let mut registry = ColorPatternRegistry::new();
+    if let Some(patterns) = patterns {
+        registry.register(patterns);
+    }
+    registry.register(ColorPatternRegistry::for_document(
+        &document.language_id,
+        &document.text,
+        config,
+    ));
+
+    let mut nodes = crate::parser::parse_with_registry(&document.text, &registry);
+    crate::parser::resolve_var_references(&document.text, &mut nodes);
+    if is_css_language(&document.language_id) {
+        crate::parser::resolve_css_module_values(&document.text, &mut nodes);
+        crate::parser::tag_css_layers(&document.text, &mut nodes);
+    }
+    crate::parser::resolve_android_color_references(&document.text, android_colors, &mut nodes);
+    tracing::debug!(
+        uri = %document.uri,
+        color_count = nodes.len(),
+        duration_ms = start.elapsed().as_secs_f64() * 1000.,
+        "parse"
+    );
+    nodes
+}
+
+/// Build the [`Backend`], the client is a stub without a connection unless `with_client` is called.
+#[derive(Default)]
+struct BackendBuilder {
+    config: Config,
+    client: Option<Client>,
+    patterns: Option<Arc<ColorPatternRegistry>>,
+    prefs_path: Option<PathBuf>,
+}
+
+#[allow(unused)]
+impl BackendBuilder {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn with_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Custom color patterns, tried before the built-in patterns.
+    fn with_patterns(mut self, patterns: ColorPatternRegistry) -> Self {
+        self.patterns = Some(Arc::new(patterns));
+        self
+    }
+
+    /// Persist the format choices of the user to the file, and start with the choices in it.
+    fn with_prefs_path(mut self, path: Option<PathBuf>) -> Self {
+        self.prefs_path = path;
+        self
+    }
+
+    fn build(self) -> Backend {
+        let config = match &self.prefs_path {
+            Some(path) => self.config.merge_file(path).unwrap_or_else(|err| {
+                tracing::warn!(error = %err, "load prefs failed");
+                self.config.clone()
+            }),
+            None => self.config,
+        };
+
+        Backend {
+            client: self.client.unwrap_or_else(stub_client),
+            work_dir_list: RwLock::new(vec![]),
+            config: RwLock::new(config),
+            patterns: self.patterns,
+            documents: DashMap::new(),
+            diagnostics: DashMap::new(),
+            colors: DashMap::new(),
+            color_versions: DashMap::new(),
+            custom_properties: DashMap::new(),
+            changes: DashMap::new(),
+            prefs_path: self.prefs_path,
+        }
+    }
+
+    /// Build the service with the client of the service, ignoring the client of the builder.
+    fn service(self) -> (LspService<Backend>, ClientSocket) {
+        LspService::new(|client| self.with_client(client).build())
+    }
+
+    /// Serve the backend on the `transport`, the pipe and socket serve the first connection.
+    async fn build_and_serve(self, transport: Transport) -> std::io::Result<()> {
+        match transport {
+            Transport::Stdio => {
+                self.serve(tokio::io::stdin(), tokio::io::stdout()).await;
+            }
+            #[cfg(unix)]
+            Transport::Pipe(path) => {
+                let listener = tokio::net::UnixListener::bind(path)?;
+                let (stream, _) = listener.accept().await?;
+                let (read, write) = stream.into_split();
+                self.serve(read, write).await;
+            }
+            #[cfg(windows)]
+            Transport::Pipe(path) => {
+                let server = tokio::net::windows::named_pipe::ServerOptions::new()
+                    .first_pipe_instance(true)
+                    .create(path)?;
+                server.connect().await?;
+                let (read, write) = tokio::io::split(server);
+                self.serve(read, write).await;
+            }
+            #[cfg(not(any(unix, windows)))]
+            Transport::Pipe(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "The pipe transport is not supported on this platform",
+                ));
+            }
+            Transport::Socket(port) => {
+                let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+                let (stream, _) = listener.accept().await?;
+                let (read, write) = stream.into_split();
+                self.serve(read, write).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn serve(
+        self,
+        read: impl tokio::io::AsyncRead + Unpin,
+        write: impl tokio::io::AsyncWrite,
+    ) {
+        let (service, socket) = self.service();
+        Server::new(read, write, socket).serve(service).await;
+    }
+}
+
+/// The transport between the server and the client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// The stdin and stdout of the process.
+    #[default]
+    Stdio,
+    /// The Unix domain socket, or the Windows named pipe like `\\.\pipe\color-lsp`, at the path.
+    Pipe(PathBuf),
+    /// The TCP port on localhost.
+    Socket(u16),
+}
+
+impl Transport {
+    /// Parse the transport from the `--stdio`, `--pipe <path>` or `--socket <port>` arguments,
+    /// `Stdio` if none of them is given.
+    pub fn from_args(args: &[String]) -> std::result::Result<Self, String> {
+        let value = |ix: usize| {
+            args.get(ix + 1)
+                .ok_or_else(|| format!("Missing the value of {}", args[ix]))
+        };
+
+        let mut transport = Transport::Stdio;
+        for (ix, arg) in args.iter().enumerate() {
+            transport = match arg.as_str() {
+                "--stdio" => Transport::Stdio,
+                "--pipe" => Transport::Pipe(value(ix)?.into()),
+                "--socket" => {
+                    let port = value(ix)?;
+                    Transport::Socket(
+                        port.parse()
+                            .map_err(|_| format!("Invalid socket port: {port}"))?,
+                    )
+                }
+                _ => continue,
+            };
+        }
+        Ok(transport)
+    }
+}
+
+/// A client of a service that is never served, the messages to it are dropped.
+fn stub_client() -> Client {
+    let (service, _socket) = BackendBuilder::new().service();
+    service.inner().client.clone()
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        BackendBuilder::new().build()
+    }
+}
+
+/// Create the server with the default config and a client without connection,
+/// to call the [`LanguageServer`] methods directly, e.g. in benchmarks.
+pub fn server() -> impl LanguageServer {
+    Backend::default()
+}
+
+pub async fn start() {
+    // Serving on stdio doesn't fail
+    let _ = start_with_transport(Transport::Stdio).await;
+}
+
+/// Start the server on the `transport`, the error is of binding or accepting the connection.
+pub async fn start_with_transport(transport: Transport) -> std::io::Result<()> {
+    BackendBuilder::new()
+        .with_prefs_path(crate::config::prefs_path())
+        .build_and_serve(transport)
+        .await
+}
+
+/// Start the server with the custom color `patterns`, tried before the built-in patterns.
+pub async fn start_with_patterns(patterns: ColorPatternRegistry) {
+    let _ = BackendBuilder::new()
+        .with_patterns(patterns)
+        .with_prefs_path(crate::config::prefs_path())
+        .build_and_serve(Transport::Stdio)
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tower_lsp::lsp_types::{
+        CodeAction, DidChangeConfigurationParams, DidChangeTextDocumentParams,
+        DidChangeWorkspaceFoldersParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+        DocumentColorParams, ExecuteCommandParams, Hover, HoverContents, HoverParams,
+        InitializeParams, LinkedEditingRangeParams, Position, PrepareRenameResponse, Range,
+        RenameParams, SelectionRangeParams, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+        TextDocumentItem, TextDocumentPositionParams, TextDocumentSaveReason, TextEdit, Url,
+        VersionedTextDocumentIdentifier, WillSaveTextDocumentParams, WorkspaceFolder,
+        WorkspaceFoldersChangeEvent,
+    };
+    use tower_lsp::{ClientSocket, LanguageServer, LspService, Server};
+
+    use super::{
+        adjust_color_actions, close_color_function_edit, color_count_lenses, contrast_diagnostics,
+        css4_syntax_edits, custom_property_presentation, cycle_format_action,
+        duplicate_notation_diagnostics, extract_css_variable_action, extract_palette_action,
+        godot_presentations, gradient_presentation, hex_format_edits, hover_markdown, is_outdated,
+        jvm_presentations, near_duplicate_diagnostics, nearest_named_color_actions,
+        normalized_presentation, qt_presentations, root_rule_body, start_with_transport,
+        tailwind_config, theme_presentation, translucency_hint, Backend, BackendBuilder, Transport,
+        COLOR_COUNT_COMMAND,
+    };
+    use crate::config::{Config, HexCase};
+    use crate::index::ColorIndex;
+    use crate::parser::{parse, ColorNode, ColorPattern, ColorPatternRegistry};
+
+    fn service() -> (LspService<Backend>, ClientSocket) {
+        BackendBuilder::new().service()
+    }
+
+    /// Write a JSON-RPC message with the `Content-Length` header.
+    async fn write_message(writer: &mut (impl AsyncWriteExt + Unpin), message: serde_json::Value) {
+        let body = message.to_string();
+        let header = format!("Content-Length: {}\r\n\r\n", body.len());
+        writer.write_all(header.as_bytes()).await.unwrap();
+        writer.write_all(body.as_bytes()).await.unwrap();
+    }
+
+    /// Read a JSON-RPC message with the `Content-Length` header.
+    async fn read_message(reader: &mut (impl AsyncBufReadExt + Unpin)) -> serde_json::Value {
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some(len) = line.strip_prefix("Content-Length:") {
+                content_length = len.trim().parse().unwrap();
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Apply the edits from the end of the document, each edit must be in a single line.
+    fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+        let mut edits = edits.to_vec();
+        edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+
+        let mut lines = text.lines().map(String::from).collect::<Vec<_>>();
+        for edit in edits {
+            let line = &mut lines[edit.range.start.line as usize];
+            line.replace_range(
+                edit.range.start.character as usize..edit.range.end.character as usize,
+                &edit.new_text,
+            );
+        }
+        lines.join("\n")
+    }
+
+    /// Apply the edits of the single document code action, return the title and the new text.
+    fn apply_action(text: &str, action: CodeAction) -> (String, String) {
+        let changes = action.edit.unwrap().changes.unwrap();
+        assert_eq!(changes.len(), 1);
+        let edits = changes.into_values().next().unwrap();
+        (action.title, apply_edits(text, &edits))
+    }
+
+    #[test]
+    fn test_is_outdated() {
+        let build_time = 1_700_000_000;
+        let day = 24 * 60 * 60;
+        assert!(!is_outdated(&build_time.to_string(), build_time));
+        assert!(!is_outdated(
+            &build_time.to_string(),
+            build_time + 180 * day
+        ));
+        assert!(is_outdated(&build_time.to_string(), build_time + 200 * day));
+        assert!(!is_outdated("", build_time + 200 * day));
+        assert!(env!("COLOR_LSP_BUILD_TIME").parse::<u64>().is_ok());
+    }
+
+    #[test]
+    fn test_normalized_presentation() {
+        let range = Range::new(Position::new(0, 7), Position::new(0, 11));
+        let normalized = |s| {
+            let presentation = normalized_presentation(&csscolorparser::parse(s).unwrap(), range);
+            assert_eq!(presentation.label, "Normalized (diff-friendly)");
+            presentation.text_edit.unwrap().new_text
+        };
+
+        assert_eq!(normalized("#F80"), "rgba(255, 136, 0, 1.00)");
+        assert_eq!(
+            normalized("hsl(0 100% 50% / 33.3%)"),
+            "rgba(255, 0, 0, 0.33)"
+        );
+        assert_eq!(normalized("transparent"), "rgba(0, 0, 0, 0.00)");
+    }
+
+    #[test]
+    fn test_jvm_presentations() {
+        let range = Range::new(Position::new(0, 0), Position::new(0, 8));
+        let color = csscolorparser::parse("#ff8800").unwrap();
+        let labels = |language_id| {
+            jvm_presentations(language_id, &color, range)
+                .into_iter()
+                .map(|presentation| presentation.label)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            labels("java"),
+            ["new java.awt.Color(255, 136, 0, 255)", "0xFFFF8800"]
+        );
+        assert_eq!(
+            labels("kotlin"),
+            ["java.awt.Color(255, 136, 0, 255)", "0xFFFF8800"]
+        );
+    }
+
+    #[test]
+    fn test_qt_presentations() {
+        let range = Range::new(Position::new(0, 7), Position::new(0, 31));
+        let color = csscolorparser::parse("#ff8800").unwrap();
+        let presentations = qt_presentations(&color, range);
+
+        assert_eq!(
+            presentations
+                .iter()
+                .map(|presentation| presentation.label.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "QColor(255, 136, 0, 255)",
+                "QColor(\"#ff8800\")",
+                "QColor::fromRgbF(1.0, 0.533, 0.0, 1.0)"
+            ]
+        );
+        assert_eq!(
+            presentations[0].text_edit,
+            Some(TextEdit::new(range, "QColor(255, 136, 0, 255)".into()))
+        );
+    }
+
+    #[test]
+    fn test_godot_presentations() {
+        let range = Range::new(Position::new(0, 14), Position::new(0, 36));
+        let color = csscolorparser::parse("#ff8800").unwrap();
+
+        assert_eq!(
+            godot_presentations(&color, range)
+                .into_iter()
+                .map(|presentation| presentation.label)
+                .collect::<Vec<_>>(),
+            [
+                "Color(1.0000, 0.5333, 0.0000, 1.0000)",
+                "Color.html(\"#ff8800\")"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_theme_presentation() {
+        let range = Range::new(Position::new(0, 16), Position::new(0, 40));
+        let node = crate::parser::parse("a { background: theme('colors.blue.500'); }").remove(0);
+        let theme = |s| {
+            let color = csscolorparser::parse(s).unwrap();
+            theme_presentation(&node, &color, range).map(|presentation| presentation.label)
+        };
+
+        assert_eq!(
+            theme("#3b82f6").as_deref(),
+            Some("theme('colors.blue.500')")
+        );
+        assert_eq!(theme("#ef4444").as_deref(), Some("theme('colors.red.500')"));
+        assert_eq!(theme("#123456"), None);
+
+        let node = crate::parser::parse("#3b82f6").remove(0);
+        let color = csscolorparser::parse("#3b82f6").unwrap();
+        assert!(theme_presentation(&node, &color, range).is_none());
+    }
+
+    #[test]
+    fn test_gradient_presentation() {
+        let line = "a { background: linear-gradient(90deg, #ff0000 0%, blue 100%), #fff; }";
+        let nodes = crate::parser::parse(line);
+        let color = csscolorparser::parse("#ff8800").unwrap();
+        let presentation = gradient_presentation(line, &nodes[0], &color).unwrap();
+
+        assert_eq!(presentation.label, "Single-color gradient (IE11 fallback)");
+        assert_eq!(
+            presentation.text_edit,
+            Some(TextEdit::new(
+                Range::new(Position::new(0, 16), Position::new(0, 61)),
+                "linear-gradient(#ff8800, #ff8800)".into()
+            ))
+        );
+        assert!(gradient_presentation(line, &nodes[1], &color).is_some());
+        assert!(gradient_presentation(line, &nodes[2], &color).is_none());
+    }
+
+    #[test]
+    fn test_custom_property_presentation() {
+        let text = "a {\n  color: red;\n}\nb { color: #ff8800; }";
+        let range = Range::new(Position::new(3, 11), Position::new(3, 18));
+        let color = csscolorparser::parse("#ff8800").unwrap();
+        let presentation = custom_property_presentation(text, &color, range);
+
+        assert_eq!(presentation.label, "var(--color-ff8800)");
+        assert_eq!(
+            presentation.text_edit,
+            Some(TextEdit::new(range, "var(--color-ff8800)".into()))
+        );
+        assert_eq!(
+            presentation.additional_text_edits,
+            Some(vec![TextEdit::new(
+                Range::new(Position::new(0, 0), Position::new(0, 0)),
+                ":root {\n  --color-ff8800: #ff8800;\n}\n\n".into()
+            )])
+        );
+
+        // Named with the alpha, and declared in the existing `:root` rule
+        let text = ":root {\n}\nb { color: #ff000080; }";
+        let color = csscolorparser::parse("rgba(255, 0, 0, 0.5)").unwrap();
+        let presentation = custom_property_presentation(text, &color, range);
+        assert_eq!(presentation.label, "var(--color-ff000080)");
+        assert_eq!(
+            presentation.additional_text_edits,
+            Some(vec![TextEdit::new(
+                Range::new(Position::new(0, 7), Position::new(0, 7)),
+                "\n  --color-ff000080: #ff000080;".into()
+            )])
+        );
+
+        // Reuse the declared property
+        let text = ":root {\n  --color-ff000080: #ff000080;\n}";
+        let presentation = custom_property_presentation(text, &color, range);
+        assert_eq!(presentation.additional_text_edits, None);
+    }
+
+    #[test]
+    fn test_hex_format_edits() {
+        let text = "a { color: #FFF; }\nb { color: #AABBCC; border-color: #aAbBcCdD; }\nc { color: #ff8800; }";
+        let nodes = parse(text);
+
+        let edits = hex_format_edits(text, &nodes, &Config::default());
+        assert_eq!(edits.len(), 3);
+        assert!(edits
+            .windows(2)
+            .all(|pair| pair[0].range.start > pair[1].range.start));
+        assert_eq!(
+            apply_edits(text, &edits),
+            "a { color: #ffffff; }\nb { color: #aabbcc; border-color: #aabbccdd; }\nc { color: #ff8800; }"
+        );
+
+        let config = Config {
+            hex_case: HexCase::Upper,
+            ..Default::default()
+        };
+        let edits = hex_format_edits(text, &nodes, &config);
+        assert_eq!(edits.len(), 3);
+        assert_eq!(
+            apply_edits(text, &edits),
+            "a { color: #FFFFFF; }\nb { color: #AABBCC; border-color: #AABBCCDD; }\nc { color: #FF8800; }"
+        );
+
+        let text =
+            "#add, a:hover #bad {\n  /* fixes #123 */\n  color: #CAFE;\n}\n#cafe { color: #FFF; }";
+        let nodes = parse(text);
+        let edits = hex_format_edits(text, &nodes, &Config::default());
+        assert_eq!(
+            apply_edits(text, &edits),
+            "#add, a:hover #bad {\n  /* fixes #123 */\n  color: #ccaaffee;\n}\n#cafe { color: #ffffff; }"
+        );
+    }
+
+    #[test]
+    fn test_css4_syntax_edits() {
+        let text = "a { color: rgba(255, 0, 0, 0.5); background: hsla(120, 100%, 50%, 0.8); }\nb { color: rgb(0 0 0); border-color: #fff; }";
+        let edits = css4_syntax_edits(&parse(text));
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            apply_edits(text, &edits),
+            "a { color: rgb(255 0 0 / 50%); background: hsl(120 100% 50% / 80%); }\nb { color: rgb(0 0 0); border-color: #fff; }"
+        );
+    }
+
+    #[test]
+    fn test_extract_palette_action() {
+        let uri = Url::parse("file:///test.css").unwrap();
+        let extract = |text: &str| {
+            let document = TextDocumentItem::new(uri.clone(), "css".into(), 1, text.into());
+            let action = extract_palette_action(&document, &parse(text))?;
+            assert_eq!(action.title, "Extract palette to :root");
+            Some(apply_action(text, action).1)
+        };
+
+        assert_eq!(
+            extract("a {\n  color: #F00;\n  background: rgba(255, 0, 0, 0.5);\n}\nb { color: red; border-color: #ff0000; }")
+                .as_deref(),
+            Some(
+                ":root {\n  --color-1: #ff0000ff;\n  --color-2: #ff000080;\n}\n\n\
+                a {\n  color: var(--color-1);\n  background: var(--color-2);\n}\n\
+                b { color: red; border-color: var(--color-1); }"
+            )
+        );
+        // Skip the numbers that have been declared, keep `@import` first
+        assert_eq!(
+            extract("@import \"a.css\";\n:root { --color-1: #fff; }").as_deref(),
+            Some("@import \"a.css\";\n\n:root {\n  --color-2: #ffffffff;\n}\n:root { --color-1: var(--color-2); }")
+        );
+        assert_eq!(extract("a { color: red; }"), None);
+    }
+
+    #[test]
+    fn test_extract_css_variable_action() {
+        let uri = Url::parse("file:///test.css").unwrap();
+        let extract = |text: &str, line: usize| {
+            let document = TextDocumentItem::new(uri.clone(), "css".into(), 1, text.into());
+            let node = parse(text)
+                .into_iter()
+                .find(|node| node.range.line as usize == line)
+                .unwrap();
+            apply_action(text, extract_css_variable_action(&document, &node)).1
+        };
+
+        assert_eq!(
+            extract("a {\n  color: #FF8800;\n}", 2),
+            ":root {\n  --color-ff8800: #FF8800;\n}\n\na {\n  color: var(--color-ff8800);\n}"
+        );
+        assert_eq!(
+            extract(
+                ":root {\n  --bg: #fff;\n}\na {\n  color: rgba(255, 0, 0, 0.5);\n}",
+                5
+            ),
+            ":root {\n  --color-ff000080: rgba(255, 0, 0, 0.5);\n  --bg: #fff;\n}\na {\n  color: var(--color-ff000080);\n}"
+        );
+        assert_eq!(
+            extract(":root { --bg: #fff; }\na { color: red; border-color: #f00; }", 2),
+            ":root {\n  --color-ff0000: #f00; --bg: #fff; }\na { color: red; border-color: var(--color-ff0000); }"
+        );
+        // Reuse the variable if it has been declared
+        assert_eq!(
+            extract(":root {\n  --color-ff0000: red;\n}\na { color: #f00; }", 4),
+            ":root {\n  --color-ff0000: red;\n}\na { color: var(--color-ff0000); }"
+        );
+        // Skip `:root` in comments and other selectors
+        assert_eq!(
+            extract("/* :root { } */\n.a:root-ish {}\na { color: #f00; }", 3),
+            ":root {\n  --color-ff0000: #f00;\n}\n\n/* :root { } */\n.a:root-ish {}\na { color: var(--color-ff0000); }"
+        );
+        assert_eq!(
+            extract("html,\n:root\n{\n}\na { color: #f00; }", 5),
+            "html,\n:root\n{\n  --color-ff0000: #f00;\n}\na { color: var(--color-ff0000); }"
+        );
+        // `@charset` and `@import` must stay before the other rules
+        assert_eq!(
+            extract("@charset \"utf-8\";\n@import url(a.css);\n\na { color: #f00; }", 4),
+            "@charset \"utf-8\";\n@import url(a.css);\n\n:root {\n  --color-ff0000: #f00;\n}\n\na { color: var(--color-ff0000); }"
+        );
+    }
+
+    #[test]
+    fn test_root_rule_body() {
+        assert_eq!(root_rule_body(":root { }"), Some(7));
+        assert_eq!(root_rule_body("a {}\n:root{}"), Some(11));
+        assert_eq!(root_rule_body("/* :root {} */"), None);
+        assert_eq!(root_rule_body("// :root {}"), None);
+        assert_eq!(root_rule_body(".a:root {}"), None);
+        assert_eq!(root_rule_body(":root-ish {}"), None);
+    }
+
+    #[tokio::test]
+    async fn test_lsp_initialize() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (server_read, server_write) = tokio::io::split(server);
+        let (service, socket) = service();
+        tokio::spawn(Server::new(server_read, server_write, socket).serve(service));
+
+        let (client_read, mut client_write) = tokio::io::split(client);
+        let mut client_read = BufReader::new(client_read);
+        write_message(
+            &mut client_write,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "capabilities": {} },
+            }),
+        )
+        .await;
+
+        let response = read_message(&mut client_read).await;
+        assert_eq!(response["id"], 1);
+        let result = &response["result"];
+        assert_eq!(result["serverInfo"]["name"], "ColorLSP");
+        assert_eq!(result["serverInfo"]["version"], env!("CARGO_PKG_VERSION"));
+
+        assert_eq!(
+            result["capabilities"],
+            serde_json::json!({
+                "workspace": {
+                    "workspaceFolders": {
+                        "supported": true,
+                        "changeNotifications": true,
+                    },
+                },
+                "textDocumentSync": {
+                    "openClose": true,
+                    "change": 1,
+                    "willSaveWaitUntil": true,
+                },
+                "colorProvider": true,
+                "hoverProvider": true,
+                "documentFormattingProvider": true,
+                "codeLensProvider": {
+                    "resolveProvider": false,
+                },
+                "renameProvider": { "prepareProvider": true },
+                "linkedEditingRangeProvider": true,
+                "documentOnTypeFormattingProvider": { "firstTriggerCharacter": "(" },
+                "selectionRangeProvider": true,
+                "executeCommandProvider": {
+                    "commands": [
+                        "color-lsp.generateTailwindConfig",
+                        "color-lsp.scanWorkspace",
+                        "color-lsp.xcassetsColorSet",
+                        "color-lsp.colorCount",
+                        "color-lsp.convertAllToHex",
+                        "color-lsp.findSimilarColors",
+                        "color-lsp.palette",
+                        "color-lsp.documentPalette",
+                    ],
+                },
+                "codeActionProvider": {
+                    "codeActionKinds": [
+                        "quickfix",
+                        "refactor.extract",
+                        "refactor.rewrite",
+                        "source.fixAll",
+                    ],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_transport_from_args() {
+        let transport = |args: &[&str]| {
+            Transport::from_args(&args.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(transport(&[]), Ok(Transport::Stdio));
+        assert_eq!(transport(&["--stdio"]), Ok(Transport::Stdio));
+        assert_eq!(
+            transport(&["--log", "a.log", "--pipe", "/tmp/color-lsp.sock"]),
+            Ok(Transport::Pipe("/tmp/color-lsp.sock".into()))
+        );
+        assert_eq!(
+            transport(&["--socket", "9257"]),
+            Ok(Transport::Socket(9257))
+        );
+        assert_eq!(
+            transport(&["--socket", "port"]),
+            Err("Invalid socket port: port".into())
+        );
+        assert_eq!(
+            transport(&["--pipe"]),
+            Err("Missing the value of --pipe".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socket_transport() {
+        // Find a free port
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        tokio::spawn(start_with_transport(Transport::Socket(port)));
+
+        let stream = loop {
+            match tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        };
+        let (client_read, mut client_write) = stream.into_split();
+        let mut client_read = BufReader::new(client_read);
+        write_message(
+            &mut client_write,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "capabilities": {} },
+            }),
+        )
+        .await;
+
+        let response = read_message(&mut client_read).await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["serverInfo"]["name"], "ColorLSP");
+    }
+
+    #[tokio::test]
+    async fn test_lsp_log_messages() {
+        let work_dir = std::env::temp_dir().join(format!("color-lsp-log-{}", std::process::id()));
+        std::fs::create_dir_all(&work_dir).unwrap();
+        std::fs::write(work_dir.join(".color-lsp.json"), "{ invalid").unwrap();
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (server_read, server_write) = tokio::io::split(server);
+        let (service, socket) = service();
+        tokio::spawn(Server::new(server_read, server_write, socket).serve(service));
+
+        let (client_read, mut client_write) = tokio::io::split(client);
+        let mut client_read = BufReader::new(client_read);
+        write_message(
+            &mut client_write,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "rootUri": Url::from_file_path(&work_dir).unwrap(),
+                    "capabilities": {},
+                },
+            }),
+        )
+        .await;
+
+        let warning = read_message(&mut client_read).await;
+        std::fs::remove_dir_all(&work_dir).unwrap();
+        assert_eq!(warning["method"], "window/logMessage");
+        assert_eq!(warning["params"]["type"], 2);
+        assert!(warning["params"]["message"]
+            .as_str()
+            .unwrap()
+            .ends_with(", use default config"));
+        assert_eq!(read_message(&mut client_read).await["id"], 1);
+
+        write_message(
+            &mut client_write,
+            serde_json::json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        )
+        .await;
+        let info = read_message(&mut client_read).await;
+        assert_eq!(info["method"], "window/logMessage");
+        assert_eq!(info["params"]["type"], 3);
+        assert!(info["params"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("ColorLSP v"));
+    }
+
+    #[tokio::test]
+    async fn test_convert_all_to_hex_command() {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let (server_read, server_write) = tokio::io::split(server);
+        let (service, socket) = service();
+        tokio::spawn(Server::new(server_read, server_write, socket).serve(service));
+
+        let (client_read, mut client_write) = tokio::io::split(client);
+        let mut client_read = BufReader::new(client_read);
+        let text =
+            "a { color: rgb(255, 0, 0); }\nb { color: hsl(120, 100%, 50%); background: #fff; }";
+        let uri = "file:///test.css";
+        write_message(
+            &mut client_write,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "capabilities": {} },
+            }),
+        )
+        .await;
+        assert_eq!(read_message(&mut client_read).await["id"], 1);
+
+        // Wait for the diagnostics of the opened document before running the command
+        write_message(
+            &mut client_write,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": {
+                    "textDocument": { "uri": uri, "languageId": "css", "version": 1, "text": text },
+                },
+            }),
+        )
+        .await;
+        let diagnostics = read_message(&mut client_read).await;
+        assert_eq!(diagnostics["method"], "textDocument/publishDiagnostics");
+
+        write_message(
+            &mut client_write,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "workspace/executeCommand",
+                "params": { "command": "color-lsp.convertAllToHex", "arguments": [uri] },
+            }),
+        )
+        .await;
+        let request = read_message(&mut client_read).await;
+        assert_eq!(request["method"], "workspace/applyEdit");
+        let edits: Vec<TextEdit> =
+            serde_json::from_value(request["params"]["edit"]["changes"][uri].clone()).unwrap();
+        assert_eq!(edits.len(), 2);
+        assert!(edits[0].range.start > edits[1].range.start);
+        assert_eq!(
+            apply_edits(text, &edits),
+            "a { color: #ff0000; }\nb { color: #00ff00; background: #fff; }"
+        );
+
+        write_message(
+            &mut client_write,
+            serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "applied": true } }),
+        )
+        .await;
+        let response = read_message(&mut client_read).await;
+        assert_eq!(response["id"], 2);
+        assert_eq!(response["result"], true);
+    }
+
+    #[tokio::test]
+    async fn test_scan_document_off_runtime() {
+        let backend = Backend::default();
+        let uri = Url::parse("file:///large.json").unwrap();
+        let text = (0..10_000)
+            .map(|ix| format!("\"#{:06x}\",\n", ix * 0x0101))
+            .collect::<String>();
+        let document = TextDocumentItem::new(uri.clone(), "json".into(), 1, text);
+
+        // The runtime keeps running other tasks while the document is parsed
+        let ticks = std::cell::Cell::new(0);
+        tokio::select! {
+            biased;
+            _ = backend.scan_document(&document) => {}
+            _ = async {
+                loop {
+                    tokio::task::yield_now().await;
+                    ticks.set(ticks.get() + 1);
+                }
+            } => {}
+        }
+        assert!(ticks.get() > 0);
+        assert_eq!(backend.colors.get(&uri).unwrap().len(), 10_000);
+    }
+
+    #[tokio::test]
+    async fn test_document_colors_cache() {
+        let backend = Backend::default();
+        let uri = Url::parse("file:///test.css").unwrap();
+        let document =
+            TextDocumentItem::new(uri.clone(), "css".into(), 1, "a { color: #f00; }".into());
+        backend.upsert_document(Arc::new(document.clone()));
+        assert_eq!(backend.document_colors(&uri).await.len(), 1);
+        assert_eq!(*backend.color_versions.get(&uri).unwrap(), 1);
+
+        // The cached colors are returned without scanning for the same version
+        backend
+            .colors
+            .insert(uri.clone(), Arc::new(ColorIndex::default()));
+        assert!(backend.document_colors(&uri).await.is_empty());
+
+        // A new version of the document is scanned again
+        let text = "a { color: #f00; background: #00f; }";
+        backend.upsert_document(Arc::new(TextDocumentItem::new(
+            uri.clone(),
+            "css".into(),
+            2,
+            text.into(),
+        )));
+        assert_eq!(backend.document_colors(&uri).await.len(), 2);
+        assert_eq!(*backend.color_versions.get(&uri).unwrap(), 2);
+
+        backend.remove_document(&uri);
+        assert!(backend.color_versions.is_empty());
+    }
+
+    #[test]
+    fn test_backend_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Backend>();
+    }
+
+    #[tokio::test]
+    async fn test_workspace_folders() {
+        let backend = Backend::default();
+        let folder = |name: &str| WorkspaceFolder {
+            uri: Url::from_file_path(std::env::temp_dir().join(name)).unwrap(),
+            name: name.into(),
+        };
+        #[allow(deprecated)]
+        backend
+            .initialize(InitializeParams {
+                root_uri: Some(folder("color-lsp-root-a").uri),
+                workspace_folders: Some(vec![
+                    folder("color-lsp-root-a"),
+                    folder("color-lsp-root-b"),
+                ]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            backend.work_dir_list(),
+            [
+                std::env::temp_dir().join("color-lsp-root-a"),
+                std::env::temp_dir().join("color-lsp-root-b")
+            ]
+        );
+
+        backend
+            .did_change_workspace_folders(DidChangeWorkspaceFoldersParams {
+                event: WorkspaceFoldersChangeEvent {
+                    added: vec![folder("color-lsp-root-c"), folder("color-lsp-root-b")],
+                    removed: vec![folder("color-lsp-root-a")],
+                },
+            })
+            .await;
+        assert_eq!(
+            backend.work_dir_list(),
+            [
+                std::env::temp_dir().join("color-lsp-root-b"),
+                std::env::temp_dir().join("color-lsp-root-c")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_android_color_references() {
+        let backend = Backend::default();
+        let values_dir = std::env::temp_dir().join("color-lsp-android/app/src/main/res/values");
+        std::fs::create_dir_all(&values_dir).unwrap();
+        std::fs::write(
+            values_dir.join("colors.xml"),
+            r#"<color name="brand_red">#FFFF0000</color>"#,
+        )
+        .unwrap();
+
+        let themes = Arc::new(TextDocumentItem::new(
+            Url::from_file_path(values_dir.join("themes.xml")).unwrap(),
+            "xml".into(),
+            1,
+            r#"<item name="colorPrimary">@color/brand_red</item>"#.into(),
+        ));
+        backend.upsert_document(themes.clone());
+        // Read from disk, as `colors.xml` is not open
+        let nodes = backend.parse(&themes);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].matched, "@color/brand_red");
+        assert_eq!(nodes[0].color.to_rgba8(), [255, 0, 0, 255]);
+
+        backend.upsert_document(Arc::new(TextDocumentItem::new(
+            Url::from_file_path(values_dir.join("colors.xml")).unwrap(),
+            "xml".into(),
+            2,
+            r#"<color name="brand_red">#FF00FF00</color>"#.into(),
+        )));
+        assert_eq!(backend.parse(&themes)[0].color.to_rgba8(), [0, 255, 0, 255]);
+
+        std::fs::remove_dir_all(std::env::temp_dir().join("color-lsp-android")).unwrap();
+    }
+
+    #[test]
+    fn test_close_color_function_edit() {
+        let close = |text: &str| {
+            let character = text.find('|').unwrap() as u32;
+            close_color_function_edit(&text.replace('|', ""), Position::new(0, character))
+        };
+        let edit = TextEdit::new(
+            Range::new(Position::new(0, 15), Position::new(0, 15)),
+            ")".into(),
+        );
+
+        assert_eq!(close("a { color: rgb(| }"), Some(edit.clone()));
+        assert_eq!(
+            close("a { color: hsla(|"),
+            Some(TextEdit {
+                range: Range::new(Position::new(0, 16), Position::new(0, 16)),
+                ..edit.clone()
+            })
+        );
+        assert_eq!(close("a { color: rgb(|) }"), None);
+        assert_eq!(close("a { color: myrgb(| }"), None);
+        assert_eq!(close("a { color: var(| }"), None);
+        assert_eq!(close("a { color: rgb|( }"), None);
+    }
+
+    #[tokio::test]
+    async fn test_palette_json() {
+        let backend = Backend::default();
+        for (uri, text) in [
+            ("file:///b.css", "a { color: #ff0000; background: #00f; }"),
+            (
+                "file:///a.css",
+                "@layer base {\n  a { color: #ff0000; border-color: #800000; }\n}",
+            ),
+        ] {
+            backend.upsert_document(Arc::new(TextDocumentItem::new(
+                Url::parse(uri).unwrap(),
+                "css".into(),
+                1,
+                text.into(),
+            )));
+        }
+
+        let palette = serde_json::from_str::<serde_json::Value>(&backend.palette_json()).unwrap();
+        assert_eq!(
+            palette,
+            serde_json::json!([
+                { "hex": "#800000", "files": ["file:///a.css"], "count": 1, "layers": ["base"] },
+                {
+                    "hex": "#ff0000",
+                    "files": ["file:///a.css", "file:///b.css"],
+                    "count": 2,
+                    "layers": ["base"],
+                },
+                { "hex": "#0000ff", "files": ["file:///b.css"], "count": 1, "layers": [] },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_document_palette_command() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.css").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "css".into(),
+                    1,
+                    "a { color: #ff0000; background: #0000ff; }\nb { color: #fe0000; }".into(),
+                ),
+            })
+            .await;
+
+        let palette = backend.document_palette(&uri).unwrap();
+        assert_eq!(palette.groups.len(), 2);
+        assert_eq!(palette.groups[0].len(), 2);
+        assert_eq!(palette.groups[0][1].matched, "#fe0000");
+        assert!(backend
+            .document_palette(&Url::parse("file:///missing.css").unwrap())
+            .is_none());
+
+        let result = backend
+            .execute_command(ExecuteCommandParams {
+                command: "color-lsp.documentPalette".into(),
+                arguments: vec![uri.as_str().into()],
+                work_done_progress_params: Default::default(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, Some(palette.to_json()));
+    }
+
+    #[tokio::test]
+    async fn test_linked_editing_range() {
+        let backend = Backend::default();
+        let uri = Url::parse("file:///test.css").unwrap();
+        let text = ":root {\n  --accent: #ff8800;\n}\n\
+            a { color: var(--accent); }\nb { border-color: var(--accent); background: rgb(255, 128, 0); }";
+        backend.upsert_document(Arc::new(TextDocumentItem::new(
+            uri.clone(),
+            "css".into(),
+            1,
+            text.into(),
+        )));
+        let linked_editing_range = |line, character| {
+            backend.linked_editing_range(LinkedEditingRangeParams {
+                text_document_position_params: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier::new(uri.clone()),
+                    position: Position::new(line, character),
+                },
+                work_done_progress_params: Default::default(),
+            })
+        };
+        let range =
+            |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+        let expected = vec![range(1, 2, 10), range(3, 15, 23), range(4, 22, 30)];
+
+        // On the declaration and the references
+        for (line, character) in [(1, 5), (3, 15), (4, 30)] {
+            let ranges = linked_editing_range(line, character)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(ranges.ranges, expected);
+            assert_eq!(ranges.word_pattern.as_deref(), Some("--[\\w-]+"));
+        }
+
+        // Not in the color components
+        assert_eq!(linked_editing_range(4, 51).await.unwrap(), None);
+        assert_eq!(linked_editing_range(1, 14).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_rename_custom_property() {
+        let backend = Backend::default();
+        let theme = Url::parse("file:///theme.css").unwrap();
+        let app = Url::parse("file:///app.css").unwrap();
+        for (uri, text) in [
+            (
+                &theme,
+                ":root {\n  --primary: #ff0000;\n  --primary-dark: #800000;\n}",
+            ),
+            (
+                &app,
+                "a { color: var(--primary); border-color: var(--primary-dark, var(--primary)); }",
+            ),
+        ] {
+            backend.upsert_document(Arc::new(TextDocumentItem::new(
+                uri.clone(),
+                "css".into(),
+                1,
+                text.into(),
+            )));
+        }
+        let position = |uri: &Url, line, character| TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            position: Position::new(line, character),
+        };
+
+        assert_eq!(
+            backend
+                .prepare_rename(position(&theme, 1, 5))
+                .await
+                .unwrap(),
+            Some(PrepareRenameResponse::RangeWithPlaceholder {
+                range: Range::new(Position::new(1, 2), Position::new(1, 11)),
+                placeholder: "--primary".into(),
+            })
+        );
+        assert_eq!(
+            backend
+                .prepare_rename(position(&theme, 1, 15))
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            backend.prepare_rename(position(&app, 0, 18)).await.unwrap(),
+            None
+        );
+
+        let rename = |new_name: &str| {
+            backend.rename(RenameParams {
+                text_document_position: position(&theme, 1, 5),
+                new_name: new_name.into(),
+                work_done_progress_params: Default::default(),
+            })
+        };
+        let changes = rename("--brand").await.unwrap().unwrap().changes.unwrap();
+        let edit = |line, start, end| {
+            TextEdit::new(
+                Range::new(Position::new(line, start), Position::new(line, end)),
+                "--brand".into(),
+            )
+        };
+        assert_eq!(changes[&theme], vec![edit(1, 2, 11)]);
+        assert_eq!(changes[&app], vec![edit(0, 15, 24), edit(0, 65, 74)]);
+        assert!(rename("brand").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backend_default() {
+        let backend = Backend::default();
+        assert_eq!(backend.config(), Config::default());
+        let uri = Url::parse("file:///test.css").unwrap();
+        let document =
+            TextDocumentItem::new(uri.clone(), "css".into(), 1, "a { color: #ff0000; }".into());
+        backend.upsert_document(Arc::new(document.clone()));
+        assert_eq!(backend.get_document(&uri).unwrap().text, document.text);
+
+        backend.scan_document(&document).await;
+        assert_eq!(backend.colors.get(&uri).unwrap().len(), 1);
+        assert_eq!(*backend.diagnostics.get(&uri).unwrap(), vec![]);
+        assert!(backend.custom_properties.get(&uri).unwrap().is_empty());
+
+        let text = ":root { --primary: hsl(220, 90%, 56%); }";
+        let document = TextDocumentItem::new(uri.clone(), "css".into(), 2, text.into());
+        backend.scan_document(&document).await;
+        let custom_properties = backend.custom_properties.get(&uri).unwrap().clone();
+        assert_eq!(custom_properties["--primary"].matched, "hsl(220, 90%, 56%)");
+
+        let text = ":root { --primary: #f00; }\na { color: var(--primary); }";
+        let document = TextDocumentItem::new(uri.clone(), "css".into(), 3, text.into());
+        backend.scan_document(&document).await;
+        assert_eq!(backend.colors.get(&uri).unwrap().len(), 2);
+        backend.remove_document(&uri);
+        assert!(backend.custom_properties.is_empty());
+
+        let config = Config {
+            enable_hex: false,
+            ..Default::default()
+        };
+        let backend = BackendBuilder::new().with_config(config.clone()).build();
+        assert_eq!(backend.config(), config);
+    }
+
+    #[tokio::test]
+    async fn test_did_change_debounce() {
+        /// Count the parses by the matches tried at the start of the document.
+        struct CountPattern(Arc<AtomicUsize>);
+
+        impl ColorPattern for CountPattern {
+            fn try_match(&self, _: &str, line: usize, col: usize) -> Option<ColorNode> {
+                if (line, col) == (1, 1) {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
+                None
+            }
+        }
+
+        let parses = Arc::new(AtomicUsize::new(0));
+        let mut patterns = ColorPatternRegistry::new();
+        patterns.register(CountPattern(parses.clone()));
+        let backend = BackendBuilder::new().with_patterns(patterns).build();
+        let uri = Url::parse("file:///test.json").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(uri.clone(), "json".into(), 1, "a".into()),
+            })
+            .await;
+        assert_eq!(parses.load(Ordering::SeqCst), 1);
+
+        let change = |version: i32| {
+            backend.did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: format!("a #{version}{version}{version}"),
+                }],
+            })
+        };
+        tokio::join!(change(2), change(3), change(4), change(5), change(6));
+
+        assert_eq!(parses.load(Ordering::SeqCst), 2);
+        assert_eq!(*backend.color_versions.get(&uri).unwrap(), 6);
+        assert_eq!(backend.colors.get(&uri).unwrap().nodes()[0].color.r, 0.4);
+    }
+
+    #[tokio::test]
+    async fn test_service_with_patterns() {
+        struct BrandPattern;
+
+        impl ColorPattern for BrandPattern {
+            fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+                text.starts_with("$brand").then(|| {
+                    ColorNode::new(
+                        "$brand",
+                        csscolorparser::Color::new(1., 0., 0., 1.),
+                        line,
+                        col,
+                    )
+                })
+            }
+        }
+
+        let mut patterns = ColorPatternRegistry::new();
+        patterns.register(BrandPattern);
+        let (service, _socket) = BackendBuilder::new().with_patterns(patterns).service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.scss").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "scss".into(),
+                    1,
+                    "a { color: $brand; background: #fff; }".into(),
+                ),
+            })
+            .await;
+
+        let colors = backend.colors.get(&uri).unwrap().color_information();
+        assert_eq!(colors.len(), 2);
+        assert_eq!(colors[0].range.start, Position::new(0, 11));
+        assert_eq!(colors[0].color.red, 1.);
+    }
+
+    #[tokio::test]
+    async fn test_xcassets_color_set_command() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///Colors.swift").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "swift".into(),
+                    1,
+                    "let accent = \"#ff8800\"".into(),
+                ),
+            })
+            .await;
+        let execute = |arguments| {
+            backend.execute_command(ExecuteCommandParams {
+                command: "color-lsp.xcassetsColorSet".into(),
+                arguments,
+                work_done_progress_params: Default::default(),
+            })
+        };
+        let position = |character| {
+            serde_json::json!({
+                "textDocument": { "uri": uri.as_str() },
+                "position": { "line": 0, "character": character },
+            })
+        };
+
+        let result = execute(vec![position(15)]).await.unwrap().unwrap();
+        assert_eq!(result["colors"][0]["color"]["components"]["green"], "0.533");
+        assert_eq!(execute(vec![position(2)]).await.unwrap(), None);
+        assert!(execute(vec![]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_find_similar_colors_command() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.css").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "css".into(),
+                    1,
+                    "a { color: #ff0000; }\nb { color: #fe0101; background: #0000ff; }\nc { color: rgb(255 0 0 / 50%); }".into(),
+                ),
+            })
+            .await;
+        let execute = |character, threshold| {
+            backend.execute_command(ExecuteCommandParams {
+                command: "color-lsp.findSimilarColors".into(),
+                arguments: vec![serde_json::json!({
+                    "uri": uri.as_str(),
+                    "position": { "line": 0, "character": character },
+                    "threshold": threshold,
+                })],
+                work_done_progress_params: Default::default(),
+            })
+        };
+
+        let result = execute(12, 2.0).await.unwrap().unwrap();
+        let colors = result.as_array().unwrap();
+        assert_eq!(colors.len(), 2);
+        assert_eq!(colors[0]["hex8"], "#ff000080");
+        assert_eq!(colors[0]["delta_e"], 0.0);
+        assert_eq!(colors[1]["hex8"], "#fe0101ff");
+        assert_eq!(colors[1]["uri"], uri.as_str());
+        assert_eq!(
+            colors[1]["range"],
+            serde_json::json!({
+                "start": { "line": 1, "character": 11 },
+                "end": { "line": 1, "character": 18 },
+            })
+        );
+        assert!(colors[1]["delta_e"].as_f64().unwrap() > 0.0);
+
+        assert_eq!(
+            execute(12, 0.0).await.unwrap().unwrap()[0]["hex8"],
+            "#ff000080"
+        );
+        assert_eq!(execute(2, 2.0).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_selection_range() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.css").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "css".into(),
+                    1,
+                    "a { color: rgb(255, 0, 0); background: #fff; }".into(),
+                ),
+            })
+            .await;
+        let positions = [17, 12, 42, 2].map(|character| Position::new(0, character));
+        let ranges = backend
+            .selection_range(SelectionRangeParams {
+                text_document: TextDocumentIdentifier::new(uri),
+                positions: positions.to_vec(),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let range = |start, end| Range::new(Position::new(0, start), Position::new(0, end));
+
+        // In the arguments of `rgb(255, 0, 0)`
+        assert_eq!(ranges[0].range, range(15, 24));
+        let parent = ranges[0].parent.as_ref().unwrap();
+        assert_eq!(parent.range, range(11, 25));
+        assert!(parent.parent.is_none());
+
+        // On the function name
+        assert_eq!(ranges[1].range, range(11, 25));
+        assert!(ranges[1].parent.is_none());
+
+        // On `#fff` and outside the colors
+        assert_eq!(ranges[2].range, range(39, 43));
+        assert_eq!(ranges[3].range, range(2, 2));
+        assert!(ranges[3].parent.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_did_change_configuration() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.css").unwrap();
+        let document_color = || {
+            backend.document_color(DocumentColorParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+        };
+
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "css".into(),
+                    1,
+                    "a { color: #ff0000; background: rgb(0, 0, 255); }".into(),
+                ),
+            })
+            .await;
+        assert_eq!(document_color().await.unwrap().len(), 2);
+        let diagnostics = || backend.diagnostics.get(&uri).map(|d| d.clone());
+        assert_eq!(diagnostics().unwrap().len(), 1);
+
+        backend
+            .did_change_configuration(DidChangeConfigurationParams {
+                settings: serde_json::json!({ "color-lsp": { "enable_hex": false } }),
+            })
+            .await;
+        assert!(!backend.config().enable_hex);
+        // The open documents are scanned again
+        assert_eq!(backend.colors.get(&uri).unwrap().len(), 1);
+        assert_eq!(diagnostics(), Some(vec![]));
+
+        let colors = document_color().await.unwrap();
+        assert_eq!(colors.len(), 1);
+        assert_eq!(colors[0].range.start, Position::new(0, 32));
+    }
+
+    #[tokio::test]
+    async fn test_prefs_persistence() {
+        let dir = std::env::temp_dir().join(format!("color-lsp-prefs-lsp-{}", std::process::id()));
+        let prefs_path = dir.join("color-lsp/prefs.json");
+        let backend = BackendBuilder::new()
+            .with_prefs_path(Some(prefs_path.clone()))
+            .build();
+        assert_eq!(backend.config().hex_case, HexCase::Lower);
+
+        // Not a format choice
+        backend
+            .did_change_configuration(DidChangeConfigurationParams {
+                settings: serde_json::json!({ "color-lsp": { "enable_hex": false } }),
+            })
+            .await;
+        assert!(!prefs_path.exists());
+
+        backend
+            .did_change_configuration(DidChangeConfigurationParams {
+                settings: serde_json::json!({ "color-lsp": { "hex_case": "upper" } }),
+            })
+            .await;
+        assert!(prefs_path.exists());
+
+        // The next start reads the choice, and the workspace config is applied over it
+        let backend = BackendBuilder::new()
+            .with_prefs_path(Some(prefs_path.clone()))
+            .build();
+        assert_eq!(backend.config().hex_case, HexCase::Upper);
+        assert!(backend.config().enable_hex);
+
+        std::fs::write(
+            dir.join(".color-lsp.json"),
+            r#"{ "use_css4_syntax": true }"#,
+        )
+        .unwrap();
+        #[allow(deprecated)]
+        backend
+            .initialize(InitializeParams {
+                root_uri: Some(Url::from_file_path(&dir).unwrap()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(backend.config().hex_case, HexCase::Upper);
+        assert!(backend.config().use_css4_syntax);
+    }
+
+    #[tokio::test]
+    async fn test_contrast_diagnostics_lifecycle() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let text = "a {\n  color: #777;\n  background: #888;\n}";
+        let open = |uri: &Url, language_id: &str| {
+            backend.did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    language_id.into(),
+                    1,
+                    text.into(),
+                ),
+            })
+        };
+        let diagnostics = |uri: &Url| backend.diagnostics.get(uri).map(|d| d.clone());
+
+        let css = Url::parse("file:///test.css").unwrap();
+        open(&css, "css").await;
+        assert_eq!(diagnostics(&css).unwrap().len(), 1);
+        // Diagnostics are replaced on change, not accumulated
+        open(&css, "css").await;
+        assert_eq!(diagnostics(&css).unwrap().len(), 1);
+
+        backend
+            .did_close(DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier::new(css.clone()),
+            })
+            .await;
+        assert_eq!(diagnostics(&css), None);
+
+        let rust = Url::parse("file:///test.rs").unwrap();
+        open(&rust, "rust").await;
+        assert_eq!(diagnostics(&rust), None);
+    }
+
+    #[test]
+    fn test_color_count_lenses() {
+        let text = "a {\n  color: #ff0000;\n  background: rgb(0, 0, 255);\n}\n\nb {\n  display: block;\n}\n\n@media print {\n  c { color: #000; }\n}";
+        let colors = parse(text)
+            .iter()
+            .map(tower_lsp::lsp_types::ColorInformation::from)
+            .collect::<Vec<_>>();
+
+        let lenses = color_count_lenses(text, &colors);
+        let lenses = lenses
+            .iter()
+            .map(|lens| {
+                let command = lens.command.as_ref().unwrap();
+                assert_eq!(command.command, COLOR_COUNT_COMMAND);
+                (lens.range.start.line, command.title.as_str())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lenses,
+            vec![(0, "2 colors"), (5, "0 colors"), (9, "1 color")]
+        );
+    }
+
+    #[test]
+    fn test_tailwind_config() {
+        let css =
+            ":root {\n  --color-primary: #112233;\n  --accent: #ff0000;\n}\na { color: #112233; }";
+        let rust = "let c = rgb(0, 0, 255);";
+        let documents = [(css, parse(css)), (rust, parse(rust))];
+
+        assert_eq!(
+            tailwind_config(&documents, "--color-"),
+            r##"module.exports = {
+  theme: {
+    colors: {
+      "primary": "#112233",
+      "accent": "#ff0000",
+      "color-1": "#0000ff",
+    },
+  },
+};
+"##
+        );
+        assert!(tailwind_config(&documents, "--").contains(r##""color-primary": "#112233""##));
+    }
+
+    #[test]
+    fn test_hover_markdown() {
+        let node = &parse("color: #ff0000;")[0];
+        assert_eq!(
+            hover_markdown(node),
+            "**#ff0000**\n\n- `rgb(255 0 0)`\n- `hsl(0 100% 50%)`"
+        );
+
+        let node = ColorNode {
+            color: csscolorparser::Color::new(1.5, 0., 0., 1.),
+            ..node.clone()
+        };
+        assert!(hover_markdown(&node).ends_with("\n\n⚠️ Out of P3 gamut"));
+    }
+
+    #[test]
+    fn test_hover_markdown_original_space() {
+        let hover = |text| hover_markdown(&parse(text)[0]);
+
+        let oklch = hover("color: oklch(62.8% 0.2577 29.23);");
+        assert!(oklch.starts_with("**#ff0000**\n\n- `rgb(255 0 0)`\n"));
+        assert!(oklch.ends_with("\n- OKLCH: L `0.628` C `0.258` H `29.23°`"));
+        let components = |text| hover(text).lines().nth(4).unwrap().to_string();
+        assert_eq!(
+            components("color: oklab(0.628 0.225 0.126);"),
+            "- OKLab: L `0.628` a `0.225` b `0.126`"
+        );
+        assert_eq!(
+            components("color: lab(50 40 -20);"),
+            "- Lab: L `50.00` a `40.00` b `-20.00`"
+        );
+        assert_eq!(
+            components("color: hwb(120 20% 30%);"),
+            "- HWB: H `120.00°` W `20.0%` B `30.0%`"
+        );
+        // The components of rgb() and hsl() are shown already
+        assert_eq!(hover("color: rgb(255 0 0);").lines().count(), 4);
+        assert_eq!(hover("color: #f00;").lines().count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_scan_workspace() {
+        let work_dir = std::env::temp_dir().join(format!("color-lsp-scan-{}", std::process::id()));
+        std::fs::create_dir_all(work_dir.join("src")).unwrap();
+        std::fs::create_dir_all(work_dir.join(".git")).unwrap();
+        std::fs::write(work_dir.join("src/app.css"), "a { color: #ff0000; }").unwrap();
+        std::fs::write(work_dir.join("src/app.md"), "#00ff00").unwrap();
+        std::fs::write(work_dir.join(".git/config.json"), "\"#0000ff\"").unwrap();
+        std::fs::create_dir_all(work_dir.join("node_modules/a")).unwrap();
+        std::fs::write(work_dir.join("node_modules/a/a.css"), "a { color: #fff; }").unwrap();
+        std::fs::write(work_dir.join("src/open.css"), "a { color: #fff; }").unwrap();
+
+        let (service, _socket) = service();
+        let backend = service.inner();
+        backend.set_work_dir_list(vec![work_dir.clone()]);
+
+        // The open document is not scanned from the disk
+        let open_uri = Url::from_file_path(work_dir.join("src/open.css")).unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    open_uri.clone(),
+                    "css".into(),
+                    1,
+                    "a { color: #000; background: #111; }".into(),
+                ),
+            })
+            .await;
+
+        let result = backend
+            .execute_command(ExecuteCommandParams {
+                command: "color-lsp.scanWorkspace".into(),
+                arguments: vec![],
+                work_done_progress_params: Default::default(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, Some(serde_json::json!(1)));
+
+        let colors = backend
+            .document_color(DocumentColorParams {
+                text_document: TextDocumentIdentifier::new(
+                    Url::from_file_path(work_dir.join("src/app.css")).unwrap(),
+                ),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap();
+        let open_colors = backend.colors.get(&open_uri).unwrap().len();
+        std::fs::remove_dir_all(&work_dir).unwrap();
+
+        assert_eq!(open_colors, 2);
+        assert_eq!(colors.len(), 1);
+        assert_eq!(colors[0].range.start, Position::new(0, 11));
+        assert_eq!(colors[0].color.red, 1.);
+    }
+
+    #[test]
+    fn test_near_duplicate_diagnostics() {
+        let text = "a { color: #336699; }\nb { color: #346799; }\nc { color: #336699; background: #33669980; }\nd { color: #ff0000; }";
+        let diagnostics = near_duplicate_diagnostics(&parse(text));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 11), Position::new(1, 18))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Color is near `#336699` on line 1, consider using the same color"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_notation_diagnostics() {
+        let text = "a { color: #ff0000; }\nb { color: #ff0000; }\n\
+            :root { --red: rgb(255 0 0); }\nc { color: var(--red); }";
+        let diagnostics = duplicate_notation_diagnostics(&parse(text));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 15), Position::new(2, 27))
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(tower_lsp::lsp_types::DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Color #ff0000 also appears as '#ff0000' on line 1"
+        );
+
+        let red = csscolorparser::Color::new(1., 0., 0., 1.);
+        let nodes = vec![
+            ColorNode::new("#ff0000", red.clone(), 1, 12),
+            ColorNode::new("red", red, 4, 8),
+        ];
+        let diagnostics = duplicate_notation_diagnostics(&nodes);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(3, 7), Position::new(3, 10))
+        );
+    }
+
+    #[test]
+    fn test_contrast_diagnostics() {
+        let text = "a {\n  color: #777;\n  background: #999;\n}\nb {\n  color: #777;\n  background-color: #fff;\n}\nc {\n  color: #000;\n  background: #fff;\n}";
+        let diagnostics = contrast_diagnostics(text, &parse(text));
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 9), Position::new(1, 13))
+        );
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(tower_lsp::lsp_types::DiagnosticSeverity::HINT)
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Contrast ratio 1.5:1 is below WCAG AA for large text (3:1)"
+        );
+        assert_eq!(diagnostics[1].range.start, Position::new(5, 9));
+        assert_eq!(
+            diagnostics[1].message,
+            "Contrast ratio 4.4:1 is below WCAG AA (4.5:1)"
+        );
+
+        let text = "a {\n  color: #777;\n\n  background: #999;\n}";
+        assert!(contrast_diagnostics(text, &parse(text)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hover_var_reference() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.css").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "css".into(),
+                    1,
+                    ":root {\n  --accent: #ff8800;\n}\na { color: var(--accent); }".into(),
+                ),
+            })
+            .await;
+        let hover = |line, character| {
+            backend.hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(uri.clone()),
+                    Position::new(line, character),
+                ),
+                work_done_progress_params: Default::default(),
+            })
+        };
+
+        let hover_on_var = hover(3, 15).await.unwrap().unwrap();
+        assert_eq!(
+            hover_on_var.range,
+            Some(Range::new(Position::new(3, 11), Position::new(3, 24)))
+        );
+        let HoverContents::Markup(content) = hover_on_var.contents else {
+            panic!("expected markup hover contents");
+        };
+        assert!(content.value.starts_with("**#ff8800**"));
+        assert_eq!(hover(3, 2).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_will_save_wait_until() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.css").unwrap();
+        let text = "a { color: #AABBCC; }";
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(uri.clone(), "css".into(), 1, text.into()),
+            })
+            .await;
+        let will_save = |reason| {
+            backend.will_save_wait_until(WillSaveTextDocumentParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                reason,
+            })
+        };
+
+        let edits = will_save(TextDocumentSaveReason::MANUAL)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(apply_edits(text, &edits), "a { color: #aabbcc; }");
+        assert_eq!(
+            will_save(TextDocumentSaveReason::AFTER_DELAY)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_translucency_hint() {
+        let hint = |text: &str, language_id: &str| {
+            let node = &crate::parser::parse_document(text, language_id, &Config::default())[0];
+            translucency_hint(node, language_id)
+        };
+        let expected =
+            Some("Semi-transparent color; may render differently depending on backdrop.");
+
+        assert_eq!(hint("rgb(255 255 255 / 20%)", "css"), expected);
+        assert_eq!(hint("#ffffff33", "scss"), expected);
+        assert_eq!(hint("rgba(255, 255, 255, 0.9)", "css"), None);
+        assert_eq!(hint("#fff", "css"), None);
+        // CSS-in-JS
+        assert_eq!(
+            hint("background: 'rgba(0, 0, 0, 0.5)'", "typescriptreact"),
+            expected
+        );
+        // Not a CSS color, gpui `rgba()` in 0..1
+        assert_eq!(hint("rgba(0.5, 0.5, 0.5, 0.5)", "javascript"), None);
+        assert_eq!(hint("rgba(0, 0, 0, 0.5)", "rust"), None);
+    }
+
+    #[tokio::test]
+    async fn test_hover_translucency_hint() {
+        let (service, _socket) = service();
+        let backend = service.inner();
+        let uri = Url::parse("file:///test.css").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "css".into(),
+                    1,
+                    ".glass { background: rgb(255 255 255 / 15%); color: #000; }".into(),
+                ),
+            })
+            .await;
+        let hover = |character| {
+            backend.hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(uri.clone()),
+                    Position::new(0, character),
+                ),
+                work_done_progress_params: Default::default(),
+            })
+        };
+        let markdown = |hover: Option<Hover>| match hover.unwrap().contents {
+            HoverContents::Markup(content) => content.value,
+            _ => panic!("expected markup hover contents"),
+        };
+
+        let translucent = markdown(hover(22).await.unwrap());
+        assert!(translucent.ends_with(
+            "\n\nSemi-transparent color; may render differently depending on backdrop."
+        ));
+        let opaque = markdown(hover(53).await.unwrap());
+        assert!(!opaque.contains("Semi-transparent"));
+    }
+
+    #[test]
+    fn test_hover_markdown_with_layer() {
+        let text = "@layer base {\n  a { color: #ff0000; }\n}";
+        let node = &crate::parser::parse_document(text, "css", &Config::default())[0];
+        assert!(hover_markdown(node).ends_with("\n\nLayer: `base`"));
+    }
+
+    #[test]
+    fn test_adjust_color_actions() {
+        let uri = Url::parse("file:///test.css").unwrap();
+        let adjust = |text: &str| {
+            let document = TextDocumentItem::new(uri.clone(), "css".into(), 1, text.into());
+            let node = &parse(text)[0];
+            adjust_color_actions(&document, node)
+                .into_iter()
+                .map(|action| apply_action(text, action))
+                .collect::<Vec<_>>()
+        };
+        let hsl = |text: &str| csscolorparser::parse(text).unwrap().to_hsla();
+
+        let results = adjust("color: #ff8800;");
+        assert_eq!(
+            results,
+            vec![
+                ("Darken 10%".to_string(), "color: #cc6d00;".to_string()),
+                ("Lighten 10%".to_string(), "color: #ffa033;".to_string()),
+                ("Desaturate 10%".to_string(), "color: #f2870d;".to_string()),
+            ]
+        );
+        let [_, _, l, _] = hsl(&results[0].1[7..14]);
+        assert!((l - 0.4).abs() < 0.01);
+
+        let results = adjust("color: hsl(30, 100%, 50%);");
+        assert_eq!(
+            results,
+            vec![
+                (
+                    "Darken 10%".to_string(),
+                    "color: hsl(30, 100%, 40%);".to_string()
+                ),
+                (
+                    "Lighten 10%".to_string(),
+                    "color: hsl(30, 100%, 60%);".to_string()
+                ),
+                (
+                    "Desaturate 10%".to_string(),
+                    "color: hsl(30, 90%, 50%);".to_string()
+                ),
+            ]
+        );
+        for (_, text) in results {
+            let [h, _, _, _] = hsl(&text[7..text.len() - 1]);
+            assert!((h - 30.).abs() < 0.01);
+        }
+
+        // The gray has no saturation to remove
+        let titles = adjust("color: #808080;")
+            .into_iter()
+            .map(|(title, _)| title)
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Darken 10%", "Lighten 10%", "Saturate 10%"]);
+
+        // The gpui color keeps the 0..1 values
+        assert_eq!(
+            adjust("rgb(0.5, 0.5, 0.5)")[0],
+            ("Darken 10%".to_string(), "rgb(0.4, 0.4, 0.4)".to_string())
+        );
+        // The color that can't be written back has no action
+        let text = "Color.fromARGB(255, 17, 34, 51)";
+        let document = TextDocumentItem::new(uri.clone(), "dart".into(), 1, text.into());
+        let node = &crate::parser::parse_document(text, "dart", &Config::default())[0];
+        assert!(adjust_color_actions(&document, node).is_empty());
+    }
+
+    #[test]
+    fn test_cycle_format_action() {
+        let uri = Url::parse("file:///test.css").unwrap();
+        let cycle = |text: &str| {
+            let document = TextDocumentItem::new(uri.clone(), "css".into(), 1, text.into());
+            cycle_format_action(&document, &parse(text)[0]).map(|action| apply_action(text, action))
+        };
+
+        let mut text = "color: #ff8800;".to_string();
+        let mut formats = vec![];
+        for _ in 0..4 {
+            let (title, new_text) = cycle(&text).unwrap();
+            assert_eq!(title, "Cycle color format");
+            let color = &parse(&new_text)[0].color;
+            for (a, b) in color.to_array().iter().zip([1., 136. / 255., 0., 1.]) {
+                assert!((a - b).abs() < 0.005, "{new_text}");
+            }
+            text = new_text;
+            formats.push(text.clone());
+        }
+        assert_eq!(
+            formats,
+            vec![
+                "color: rgb(255 136 0);",
+                "color: hsl(32 100% 50%);",
+                "color: oklch(0.744 0.181 56.46);",
+                // Off by one of the rounding in `oklch()`
+                "color: #ff8801;",
+            ]
+        );
+
+        assert_eq!(
+            cycle("color: rgba(255, 0, 0, 0.5);").unwrap().1,
+            "color: hsl(0 100% 50% / 50%);"
+        );
+        // Not a CSS notation
+        assert!(cycle("rgb(1., 0., 0.)").is_none());
+
+        let document = TextDocumentItem::new(uri.clone(), "rust".into(), 1, "#ff8800".into());
+        assert!(cycle_format_action(&document, &parse("#ff8800")[0]).is_none());
+    }
+
+    #[test]
+    fn test_nearest_named_color_actions() {
+        let uri = Url::parse("file:///test.css").unwrap();
+        let replace = |text: &str| {
+            let document = TextDocumentItem::new(uri.clone(), "css".into(), 1, text.into());
+            nearest_named_color_actions(&document, &parse(text)[0])
+                .into_iter()
+                .map(|action| apply_action(text, action))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            replace("color: #e9967a;"),
+            vec![(
+                "Replace with nearest named color `darksalmon`".to_string(),
+                "color: darksalmon;".to_string()
+            )]
+        );
+        assert_eq!(replace("color: #ff0001;")[0].1, "color: red;");
+        assert!(replace("color: rgba(255, 0, 0, 0.5);").is_empty());
+        // Not a CSS notation
+        assert!(replace("rgb(1., 0., 0.)").is_empty());
+        // No named color looks like it
+        assert!(replace("color: #3a7b2c;").is_empty());
+
+        let document = TextDocumentItem::new(uri.clone(), "rust".into(), 1, "#e9967a".into());
+        assert!(nearest_named_color_actions(&document, &parse("#e9967a")[0]).is_empty());
+    }
 }
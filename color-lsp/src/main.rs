@@ -1,8 +1,20 @@
-mod lsp;
-mod parser;
+use std::path::Path;
+
+use color_lsp::{lsp, migrate, parser};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 
 #[tokio::main]
 async fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().is_some_and(|arg| arg == "migrate") {
+        if let Err(err) = migrate::run(&args[1..]) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     if std::env::args()
         .map(|s| s.to_lowercase())
         .any(|arg| arg == "-v" || arg == "--version")
@@ -14,11 +26,111 @@ async fn main() {
         .any(|arg| arg == "-h" || arg == "--help")
     {
         println!("Usage: color-lsp [options]");
+        println!(
+            "       color-lsp migrate --from hex6 --to oklch [--apply] [--diff-stdout] <file>"
+        );
         println!("Options:");
-        println!("  -v, --version    Print version information");
-        println!("  -h, --help       Print this help message");
+        println!("  -v, --version        Print version information");
+        println!("  -h, --help           Print this help message");
+        println!("  --list-formats       Print the supported color formats with examples");
+        println!("  --stdio              Communicate on stdin and stdout (default)");
+        println!("  --pipe <path>        Listen on the Unix domain socket or Windows named pipe");
+        println!("  --socket <port>      Listen on the TCP port of localhost");
+        println!("  --log <path>         Write JSON logs to the file");
+        println!("  --log-level <level>  The log level: trace, debug, info, warn or error (default: info)");
         return;
     }
 
-    lsp::start().await;
+    if args.iter().any(|arg| arg == "--list-formats") {
+        print_formats();
+        return;
+    }
+
+    let transport = match lsp::Transport::from_args(&args) {
+        Ok(transport) => transport,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|ix| args.get(ix + 1))
+    };
+    // Keep the guard to flush the logs until the server exits
+    let _log_guard = match option("--log") {
+        Some(path) => match init_log(path.as_ref(), option("--log-level").map_or("info", |s| s)) {
+            Ok(guard) => Some(guard),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    if let Err(err) = lsp::start_with_transport(transport).await {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+/// Print the table of the formats of the built-in patterns, with the languages they are limited to.
+fn print_formats() {
+    let formats = parser::supported_formats();
+    let width = |column: fn(&parser::ColorFormat) -> &str| {
+        formats
+            .iter()
+            .map(|(format, _)| column(format).len())
+            .max()
+            .unwrap_or_default()
+    };
+    let (name_width, syntax_width, example_width) = (
+        width(|format| format.name),
+        width(|format| format.syntax),
+        width(|format| format.example),
+    );
+
+    println!(
+        "{:name_width$}  {:syntax_width$}  {:example_width$}  LANGUAGES",
+        "FORMAT", "SYNTAX", "EXAMPLE"
+    );
+    for (format, languages) in &formats {
+        let languages = if languages.is_empty() {
+            "all".to_string()
+        } else {
+            languages.join(", ")
+        };
+        println!(
+            "{:name_width$}  {:syntax_width$}  {:example_width$}  {languages}",
+            format.name, format.syntax, format.example
+        );
+    }
+}
+
+/// Write the JSON log records of `level` and above to the file at `path`.
+fn init_log(path: &Path, level: &str) -> Result<WorkerGuard, String> {
+    let level = level
+        .parse::<tracing::Level>()
+        .map_err(|_| format!("Invalid log level: {level}"))?;
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name().and_then(|s| s.to_str()))
+    else {
+        return Err(format!("Invalid log path: {}", path.display()));
+    };
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::NEVER)
+        .filename_prefix(file_name)
+        .build(dir)
+        .map_err(|err| format!("Failed to open log file {}: {err}", path.display()))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    tracing_subscriber::fmt()
+        .json()
+        .with_max_level(level)
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_writer(writer)
+        .init();
+    Ok(guard)
 }
@@ -0,0 +1,251 @@
+//! The `color-lsp migrate` subcommand, to convert the color format of a file in bulk.
+use std::path::{Path, PathBuf};
+
+use crate::parser::ColorNode;
+
+const USAGE: &str =
+    "Usage: color-lsp migrate --from hex6 --to oklch [--apply] [--diff-stdout] <file>";
+
+/// A color format that can be migrated from or to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Hex color in 6 digits like `#rrggbb`.
+    Hex6,
+    /// `oklch(L C H)`.
+    Oklch,
+}
+
+impl Format {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "hex6" => Ok(Self::Hex6),
+            "oklch" => Ok(Self::Oklch),
+            _ => Err(format!("Unsupported format: {s}")),
+        }
+    }
+
+    fn matches(&self, node: &ColorNode) -> bool {
+        match self {
+            Self::Hex6 => {
+                node.matched.len() == 7
+                    && node.matched.starts_with('#')
+                    && node.matched[1..].chars().all(|c| c.is_ascii_hexdigit())
+            }
+            Self::Oklch => node.matched.starts_with("oklch("),
+        }
+    }
+
+    fn format(&self, node: &ColorNode) -> String {
+        match self {
+            Self::Hex6 => node.color.to_css_hex(),
+            Self::Oklch => node.color.to_css_oklch(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    from: Format,
+    to: Format,
+    apply: bool,
+    /// Print the diff in unified format, to be applied by `patch`.
+    diff_stdout: bool,
+    path: PathBuf,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let (mut from, mut to, mut apply, mut diff_stdout, mut path) =
+            (None, None, false, false, None);
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--from" => from = Some(Format::parse(args.next().ok_or(USAGE)?)?),
+                "--to" => to = Some(Format::parse(args.next().ok_or(USAGE)?)?),
+                "--apply" => apply = true,
+                "--diff-stdout" => diff_stdout = true,
+                arg if !arg.starts_with('-') && path.is_none() => path = Some(PathBuf::from(arg)),
+                arg => return Err(format!("Unknown argument: {arg}\n{USAGE}")),
+            }
+        }
+
+        match (from, to, path) {
+            (Some(from), Some(to), Some(path)) => Ok(Self {
+                from,
+                to,
+                apply,
+                diff_stdout,
+                path,
+            }),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+}
+
+/// Run the `migrate` subcommand with the arguments after `migrate`.
+///
+/// Print the diff of the replacements, in unified format if `--diff-stdout` is given,
+/// and write the file if `--apply` is given.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args)?;
+    let text = std::fs::read_to_string(&options.path)
+        .map_err(|err| format!("Failed to read {}: {err}", options.path.display()))?;
+
+    let (migrated, diff) = migrate(&text, options.from, options.to);
+    if options.diff_stdout {
+        print!("{}", unified_diff(&options.path, &text, &migrated));
+    } else {
+        print!("{diff}");
+    }
+
+    if options.apply && migrated != text {
+        std::fs::write(&options.path, migrated)
+            .map_err(|err| format!("Failed to write {}: {err}", options.path.display()))?;
+    }
+    Ok(())
+}
+
+/// Replace the colors in `from` format with the `to` format, return the new text and the diff.
+fn migrate(text: &str, from: Format, to: Format) -> (String, String) {
+    let nodes = crate::parser::parse(text);
+    let mut migrated = String::with_capacity(text.len());
+    let mut diff = String::new();
+
+    for (ix, line) in text.split_inclusive('\n').enumerate() {
+        let mut new_line = line.to_string();
+        let mut line_nodes = nodes
+            .iter()
+            .filter(|node| node.range.line as usize == ix + 1 && from.matches(node))
+            .collect::<Vec<_>>();
+        line_nodes.reverse();
+
+        for node in line_nodes {
+            let start = node.range.col as usize - 1;
+            let end = start + node.range.len as usize;
+            new_line.replace_range(start..end, &to.format(node));
+        }
+
+        if new_line != line {
+            diff.push_str(&format!("@@ -{0} +{0} @@\n", ix + 1));
+            diff.push_str(&format!("-{}\n", line.trim_end_matches(['\r', '\n'])));
+            diff.push_str(&format!("+{}\n", new_line.trim_end_matches(['\r', '\n'])));
+        }
+        migrated.push_str(&new_line);
+    }
+
+    (migrated, diff)
+}
+
+/// The `diff -u` patch of the migration of the file at `path`, without the context lines.
+///
+/// The migration keeps the line count, so each run of the changed lines is a hunk with the
+/// old lines removed and the new lines inserted.
+fn unified_diff(path: &Path, text: &str, migrated: &str) -> String {
+    let old_lines = text.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = migrated.split_inclusive('\n').collect::<Vec<_>>();
+    let mut diff = String::new();
+
+    let push_line = |diff: &mut String, prefix: char, line: &str| {
+        diff.push(prefix);
+        diff.push_str(line);
+        if !line.ends_with('\n') {
+            diff.push_str("\n\\ No newline at end of file\n");
+        }
+    };
+
+    let mut ix = 0;
+    while ix < old_lines.len() {
+        if old_lines[ix] == new_lines[ix] {
+            ix += 1;
+            continue;
+        }
+        let start = ix;
+        while ix < old_lines.len() && old_lines[ix] != new_lines[ix] {
+            ix += 1;
+        }
+
+        if diff.is_empty() {
+            diff.push_str(&format!("--- {0}\n+++ {0}\n", path.display()));
+        }
+        diff.push_str(&format!("@@ -{0},{1} +{0},{1} @@\n", start + 1, ix - start));
+        for line in &old_lines[start..ix] {
+            push_line(&mut diff, '-', line);
+        }
+        for line in &new_lines[start..ix] {
+            push_line(&mut diff, '+', line);
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{migrate, unified_diff, Format, Options};
+
+    #[test]
+    fn test_options_parse() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        assert_eq!(
+            Options::parse(&args("--from hex6 --to oklch style.css --apply")),
+            Ok(Options {
+                from: Format::Hex6,
+                to: Format::Oklch,
+                apply: true,
+                diff_stdout: false,
+                path: PathBuf::from("style.css"),
+            })
+        );
+        assert!(
+            Options::parse(&args("--diff-stdout --from hex6 --to oklch style.css"))
+                .unwrap()
+                .diff_stdout
+        );
+        assert!(Options::parse(&args("--from hex6 --to oklch")).is_err());
+        assert!(Options::parse(&args("--from hex3 --to oklch style.css")).is_err());
+        assert!(Options::parse(&args("--from hex6 --to oklch a.css b.css")).is_err());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let text =
+            "a {\n  color: #ff0000;\n  background: #fff;\n  border-color: #0000ff #00ff00;\n}\n";
+        let (migrated, diff) = migrate(text, Format::Hex6, Format::Oklch);
+
+        assert_eq!(
+            migrated,
+            "a {\n  color: oklch(0.628 0.258 29.23);\n  background: #fff;\n  border-color: oklch(0.452 0.313 264.05) oklch(0.866 0.295 142.5);\n}\n"
+        );
+        assert_eq!(
+            diff,
+            "@@ -2 +2 @@\n-  color: #ff0000;\n+  color: oklch(0.628 0.258 29.23);\n@@ -4 +4 @@\n-  border-color: #0000ff #00ff00;\n+  border-color: oklch(0.452 0.313 264.05) oklch(0.866 0.295 142.5);\n"
+        );
+
+        let (migrated, diff) = migrate(&migrated, Format::Oklch, Format::Hex6);
+        assert_eq!(migrated, text);
+        assert!(!diff.is_empty());
+    }
+    #[test]
+    fn test_unified_diff() {
+        let text = "a {\n  color: #ff0000;\n  background: #00ff00;\n}\nb { color: #0000ff; }";
+        let (migrated, _) = migrate(text, Format::Hex6, Format::Oklch);
+
+        assert_eq!(
+            unified_diff(&PathBuf::from("style.css"), text, &migrated),
+            "--- style.css\n+++ style.css\n\
+            @@ -2,2 +2,2 @@\n\
+            -  color: #ff0000;\n\
+            -  background: #00ff00;\n\
+            +  color: oklch(0.628 0.258 29.23);\n\
+            +  background: oklch(0.866 0.295 142.5);\n\
+            @@ -5,1 +5,1 @@\n\
+            -b { color: #0000ff; }\n\\ No newline at end of file\n\
+            +b { color: oklch(0.452 0.313 264.05); }\n\\ No newline at end of file\n"
+        );
+        assert_eq!(unified_diff(&PathBuf::from("style.css"), text, text), "");
+    }
+}
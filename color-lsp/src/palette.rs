@@ -0,0 +1,139 @@
+//! The palette of a document, the colors grouped by perceptual similarity.
+use crate::parser::ColorNode;
+
+/// The color nodes grouped by single-linkage clustering on the CIEDE2000 distance (ΔE),
+/// two nodes are in the same group if there is a chain of nodes between them with each step
+/// within the threshold.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColorPalette {
+    /// The groups in the order of their first node, the nodes keep their order in each group.
+    pub groups: Vec<Vec<ColorNode>>,
+}
+
+impl ColorPalette {
+    /// Group the `nodes` that are within the `threshold` ΔE, e.g. `DEFAULT_NEAR_THRESHOLD`.
+    pub fn from_nodes(nodes: Vec<ColorNode>, threshold: f64) -> Self {
+        // Union-find of the node indices, the root is the first node of the group
+        let mut parents = (0..nodes.len()).collect::<Vec<_>>();
+        fn root(parents: &mut [usize], mut ix: usize) -> usize {
+            while parents[ix] != ix {
+                parents[ix] = parents[parents[ix]];
+                ix = parents[ix];
+            }
+            ix
+        }
+
+        for i in 0..nodes.len() {
+            for j in i + 1..nodes.len() {
+                if nodes[i].delta_e(&nodes[j]) <= threshold {
+                    let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<ColorNode>> = vec![];
+        let mut group_ixs = vec![usize::MAX; nodes.len()];
+        for (ix, node) in nodes.into_iter().enumerate() {
+            let root = root(&mut parents, ix);
+            if group_ixs[root] == usize::MAX {
+                group_ixs[root] = groups.len();
+                groups.push(vec![]);
+            }
+            groups[group_ixs[root]].push(node);
+        }
+        Self { groups }
+    }
+
+    /// The JSON of the groups, each node is `{ "range", "matched", "hex8" }`.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|node| {
+                        let [r, g, b, a] = node.color.to_rgba8();
+                        serde_json::json!({
+                            "range": node.lsp_range(),
+                            "matched": node.matched,
+                            "hex8": format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ColorPalette;
+    use crate::parser::{parse, DEFAULT_NEAR_THRESHOLD};
+
+    fn matched(palette: &ColorPalette) -> Vec<Vec<&str>> {
+        palette
+            .groups
+            .iter()
+            .map(|group| group.iter().map(|node| node.matched.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_from_nodes() {
+        let palette = ColorPalette::from_nodes(parse("#ff0000 #0000ff #fe0000"), 5.0);
+        assert_eq!(
+            matched(&palette),
+            vec![vec!["#ff0000", "#fe0000"], vec!["#0000ff"]]
+        );
+
+        let palette = ColorPalette::from_nodes(parse("#ff0000 #0000ff"), 5.0);
+        assert_eq!(matched(&palette), vec![vec!["#ff0000"], vec!["#0000ff"]]);
+
+        assert_eq!(
+            ColorPalette::from_nodes(vec![], DEFAULT_NEAR_THRESHOLD),
+            ColorPalette::default()
+        );
+    }
+
+    #[test]
+    fn test_single_linkage() {
+        // The ends are not within the threshold, but the middle one links them
+        let nodes = parse("#ff0000 #0000ff #fa0000 #f50000");
+        assert!(nodes[0].delta_e(&nodes[3]) > 2.0);
+        assert!(nodes[0].delta_e(&nodes[2]) <= 2.0 && nodes[2].delta_e(&nodes[3]) <= 2.0);
+
+        let palette = ColorPalette::from_nodes(nodes, 2.0);
+        assert_eq!(
+            matched(&palette),
+            vec![vec!["#ff0000", "#fa0000", "#f50000"], vec!["#0000ff"]]
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let palette = ColorPalette::from_nodes(parse("#f00 rgb(255 0 0 / 50%)"), 5.0);
+        assert_eq!(
+            palette.to_json(),
+            serde_json::json!([[
+                {
+                    "range": {
+                        "start": { "line": 0, "character": 0 },
+                        "end": { "line": 0, "character": 4 },
+                    },
+                    "matched": "#f00",
+                    "hex8": "#ff0000ff",
+                },
+                {
+                    "range": {
+                        "start": { "line": 0, "character": 5 },
+                        "end": { "line": 0, "character": 23 },
+                    },
+                    "matched": "rgb(255 0 0 / 50%)",
+                    "hex8": "#ff000080",
+                },
+            ]])
+        );
+    }
+}
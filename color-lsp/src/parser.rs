@@ -1,38 +1,561 @@
+use std::collections::HashMap;
+
 use csscolorparser::{Color, ParseColorError};
 use tower_lsp::lsp_types;
 
+use crate::config::{Config, HexCase};
+
+/// The CIEDE2000 distance below which two colors are considered near.
+pub const DEFAULT_NEAR_THRESHOLD: f64 = 5.0;
+
+/// The range of a node in the text, in a single line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRange {
+    /// Line (1-based) of the range.
+    pub line: u32,
+    /// Byte column (1-based) of the start of the range.
+    pub col: u32,
+    /// Byte length of the range.
+    pub len: u32,
+}
+
+impl TextRange {
+    pub fn new(line: u32, col: u32, len: u32) -> Self {
+        Self { line, col, len }
+    }
+
+    /// Return true if the byte column (1-based) is in the range.
+    pub fn contains_col(&self, col: u32) -> bool {
+        (self.col..self.col + self.len).contains(&col)
+    }
+
+    /// Return true if the ranges share a column, regardless of the lines.
+    pub fn overlaps(&self, other: &TextRange) -> bool {
+        self.col < other.col + other.len && other.col < self.col + self.len
+    }
+}
+
+/// The color space of a color function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Hsl,
+    Hwb,
+    Lab,
+    Lch,
+    Oklab,
+    Oklch,
+}
+
+impl ColorSpace {
+    /// The color space of the function call `s` like `oklch(…)`, also in Tailwind `[oklch(…)]`.
+    pub fn from_notation(s: &str) -> Option<Self> {
+        let s = s.strip_prefix('[').unwrap_or(s);
+        let (name, _) = s.split_once('(')?;
+        match name.to_ascii_lowercase().as_str() {
+            "rgb" | "rgba" => Some(Self::Srgb),
+            "hsl" | "hsla" => Some(Self::Hsl),
+            "hwb" => Some(Self::Hwb),
+            "lab" => Some(Self::Lab),
+            "lch" => Some(Self::Lch),
+            "oklab" => Some(Self::Oklab),
+            "oklch" => Some(Self::Oklch),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ColorNode {
     pub color: Color,
     pub matched: String,
-    /// Line, Column (1-based) of the node in the text.
-    pub loc: (usize, usize),
+    pub range: TextRange,
+    /// The CSS cascade layer of the node, nested layers are joined by `.`, e.g. `base.theme`.
+    pub layer: Option<String>,
+    /// The color space of the functional notation of the node, e.g. `Oklch` for `oklch(…)`,
+    /// `None` for the other notations like hex and named colors.
+    pub original_space: Option<ColorSpace>,
+    /// Whether the alpha is given in the source, e.g. `#ff000080` and `rgb(0 0 0 / 1)`,
+    /// `false` for the colors that are opaque by default like `#ff0000` and `rgb(0 0 0)`.
+    pub alpha_explicit: bool,
+    /// The start (0-based) and end columns of the range in UTF-16 code units, for LSP positions.
+    pub(crate) utf16_cols: (u32, u32),
 }
 
 impl Eq for ColorNode {}
 impl PartialEq for ColorNode {
     fn eq(&self, other: &Self) -> bool {
         self.matched == other.matched
-            && self.loc == other.loc
+            && self.range == other.range
             && self.color.to_css_hex() == other.color.to_css_hex()
     }
 }
 
+impl PartialOrd for ColorNode {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Order by the position in the document, the nodes at the same position are ordered by
+/// the length, the text and the color to be consistent with [`PartialEq`].
+impl Ord for ColorNode {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.cmp_position(other.range.line as usize, other.range.col as usize)
+            .then_with(|| self.range.len.cmp(&other.range.len))
+            .then_with(|| self.matched.cmp(&other.matched))
+            .then_with(|| self.color.to_css_hex().cmp(&other.color.to_css_hex()))
+    }
+}
+
 impl ColorNode {
-    fn new(matched: &str, color: Color, line: usize, col: usize) -> Self {
+    /// Create a node at the `line` and byte `col` (1-based), the text before it is assumed
+    /// to be ASCII until `locate_utf16` is called with the line text.
+    pub fn new(matched: &str, color: Color, line: usize, col: usize) -> Self {
+        let start = col.saturating_sub(1) as u32;
         Self {
             matched: matched.to_string(),
-            loc: (line, col),
+            range: TextRange::new(line as u32, col as u32, matched.len() as u32),
             color,
+            layer: None,
+            original_space: ColorSpace::from_notation(matched),
+            alpha_explicit: has_explicit_alpha(matched),
+            utf16_cols: (start, start + matched.encode_utf16().count() as u32),
+        }
+    }
+
+    /// Compare the start of the node to the `line` and byte `col` (1-based), e.g. to binary search
+    /// the sorted nodes by position.
+    pub fn cmp_position(&self, line: usize, col: usize) -> std::cmp::Ordering {
+        (self.range.line as usize, self.range.col as usize).cmp(&(line, col))
+    }
+
+    /// Create a node of the `#rrggbbaa` hex color from 0..255 channels at the start of the document.
+    pub fn from_rgb_255_tuple(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let color = Color::from_rgba8(r, g, b, a);
+        Self::new(&color.to_css_hex(), color, 1, 1)
+    }
+
+    /// Return the `(r, g, b, a)` channels in 0..255, clamped and rounded to the nearest integer.
+    pub fn to_rgb_255_tuple(&self) -> (u8, u8, u8, u8) {
+        let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let Color { r, g, b, a } = self.color;
+        (channel(r), channel(g), channel(b), channel(a))
+    }
+
+    /// Return the Java source code `new java.awt.Color(r, g, b, a)` with 0..255 channels.
+    pub fn to_java_color(&self) -> String {
+        let (r, g, b, a) = self.to_rgb_255_tuple();
+        format!("new java.awt.Color({r}, {g}, {b}, {a})")
+    }
+
+    /// Return the Android color int `0xAARRGGBB` hex literal, e.g. `0xFFFF8800`.
+    pub fn to_android_color_int(&self) -> String {
+        let (r, g, b, a) = self.to_rgb_255_tuple();
+        let value = u32::from_be_bytes([a, r, g, b]);
+        format!("0x{value:08X}")
+    }
+
+    /// Return the Qt `QColor(r, g, b, a)` with 0..255 channels.
+    pub fn to_qcolor(&self) -> String {
+        let (r, g, b, a) = self.to_rgb_255_tuple();
+        format!("QColor({r}, {g}, {b}, {a})")
+    }
+
+    /// Return the Qt `QColor("#rrggbb")`, or `QColor("#aarrggbb")` with the alpha first
+    /// as Qt reads it if the color isn't opaque.
+    pub fn to_qcolor_hex(&self) -> String {
+        let (r, g, b, a) = self.to_rgb_255_tuple();
+        if a == 255 {
+            format!("QColor(\"#{r:02x}{g:02x}{b:02x}\")")
+        } else {
+            format!("QColor(\"#{a:02x}{r:02x}{g:02x}{b:02x}\")")
+        }
+    }
+
+    /// Return the Qt `QColor::fromRgbF(r, g, b, a)` with 0..1 channels, e.g.
+    /// `QColor::fromRgbF(1.0, 0.533, 0.0, 1.0)`.
+    pub fn to_qcolor_rgb_f(&self) -> String {
+        let value = |v: f32| (v.clamp(0.0, 1.0) * 1000.0).round() / 1000.0;
+        let Color { r, g, b, a } = self.color;
+        format!(
+            "QColor::fromRgbF({:?}, {:?}, {:?}, {:?})",
+            value(r),
+            value(g),
+            value(b),
+            value(a)
+        )
+    }
+
+    /// Return the Godot `Color(r, g, b, a)` with 0..1 channels in 4 decimals,
+    /// e.g. `Color(1.0000, 0.5333, 0.0000, 1.0000)`.
+    pub fn to_godot_color_string(&self) -> String {
+        let Color { r, g, b, a } = self.color;
+        let [r, g, b, a] = [r, g, b, a].map(|v| v.clamp(0.0, 1.0));
+        format!("Color({r:.4}, {g:.4}, {b:.4}, {a:.4})")
+    }
+
+    /// Return the Godot `Color.html("#rrggbb")`, or `Color.html("#rrggbbaa")` if the color
+    /// isn't opaque.
+    pub fn to_godot_hex(&self) -> String {
+        let (r, g, b, a) = self.to_rgb_255_tuple();
+        if a == 255 {
+            format!("Color.html(\"#{r:02x}{g:02x}{b:02x}\")")
+        } else {
+            format!("Color.html(\"#{r:02x}{g:02x}{b:02x}{a:02x}\")")
         }
     }
 
-    #[allow(unused)]
+    /// Return the Nix attribute set of the 0..1 channels, e.g. `{ r = 1.0; g = 0.5; b = 0.0; a = 1.0; }`.
+    pub fn to_nix_attrset(&self) -> String {
+        let value = |v: f32| (v.clamp(0.0, 1.0) * 1000.0).round() / 1000.0;
+        let Color { r, g, b, a } = self.color;
+        format!(
+            "{{ r = {:?}; g = {:?}; b = {:?}; a = {:?}; }}",
+            value(r),
+            value(g),
+            value(b),
+            value(a)
+        )
+    }
+
+    /// Return the lowercase hex color, `#rrggbb` if opaque or `#rrggbbaa` otherwise,
+    /// collapsed to `#rgb` or `#rgba` if each channel has equal nibbles.
+    pub fn to_hex_string(&self) -> String {
+        let (r, g, b, a) = self.to_rgb_255_tuple();
+        let channels = if a == 255 {
+            &[r, g, b][..]
+        } else {
+            &[r, g, b, a][..]
+        };
+
+        if channels.iter().all(|c| c >> 4 == c & 0xf) {
+            channels
+                .iter()
+                .fold("#".to_string(), |hex, c| format!("{hex}{:x}", c & 0xf))
+        } else {
+            channels
+                .iter()
+                .fold("#".to_string(), |hex, c| format!("{hex}{c:02x}"))
+        }
+    }
+
+    /// Return the CSS custom property declaration of the color in `#rrggbbaa` like
+    /// `--primary: #ff8800ff;`, the `--` prefix of the `name` is optional.
+    ///
+    /// Return `None` if the `name` has characters other than `[a-zA-Z0-9-_]`.
+    pub fn to_css_custom_property_declaration(&self, name: &str) -> Option<String> {
+        let name = name.strip_prefix("--").unwrap_or(name);
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return None;
+        }
+        let [r, g, b, a] = self.color.to_rgba8();
+        Some(format!("--{name}: #{r:02x}{g:02x}{b:02x}{a:02x};"))
+    }
+
+    /// Return the CSS `rgb()` notation of the color, e.g. `rgb(255 136 0)`.
+    pub fn to_rgb_string(&self) -> String {
+        self.color.to_css_rgb()
+    }
+
+    /// Return the CSS `hsl()` notation of the color, e.g. `hsl(32 100% 50%)`.
+    pub fn to_hsl_string(&self) -> String {
+        self.color.to_css_hsl()
+    }
+
+    /// Return the CSS `oklch()` notation of the color, e.g. `oklch(0.628 0.258 29.23)`.
+    pub fn to_oklch_string(&self) -> String {
+        self.color.to_css_oklch()
+    }
+
+    /// Locate the UTF-16 columns of the node in the text of its line.
+    pub(crate) fn locate_utf16(&mut self, line_text: &str) {
+        let before = line_text
+            .get(..self.range.col as usize - 1)
+            .unwrap_or_default();
+        let start = before.encode_utf16().count() as u32;
+        self.utf16_cols = (start, start + self.matched.encode_utf16().count() as u32);
+    }
+
+    #[cfg(test)]
     fn must_parse(matched: &str, line: usize, col: usize) -> Self {
         let color = try_parse_color(matched).expect("The `matched` should be a valid CSS color");
         Self::new(matched, color, line, col)
     }
 
+    pub(crate) fn lsp_range(&self) -> lsp_types::Range {
+        let line = self.range.line.saturating_sub(1);
+        let (start, end) = self.utf16_cols;
+        lsp_types::Range {
+            start: lsp_types::Position {
+                line,
+                character: start,
+            },
+            end: lsp_types::Position {
+                line,
+                character: end,
+            },
+        }
+    }
+
+    /// Return the hex color in full 6 or 8 digits with the given case, `None` if it's not a hex color.
+    pub(crate) fn normalized_hex(&self, case: HexCase) -> Option<String> {
+        let digits = self.matched.strip_prefix('#')?;
+        let digits = match digits.len() {
+            3 | 4 => digits.chars().flat_map(|c| [c, c]).collect(),
+            6 | 8 => digits.to_string(),
+            _ => return None,
+        };
+
+        Some(match case {
+            HexCase::Lower => format!("#{}", digits.to_ascii_lowercase()),
+            HexCase::Upper => format!("#{}", digits.to_ascii_uppercase()),
+        })
+    }
+
+    /// Return true if the matched text is a CSS color of the same color, e.g. not a gpui `rgb(1., 0., 0.)`.
+    pub(crate) fn is_css_notation(&self) -> bool {
+        parse_css_color(&self.matched).is_ok_and(|color| color.to_rgba8() == self.color.to_rgba8())
+    }
+
+    /// Format the `color` in the same notation as the node, e.g. hex stays hex and `hsl()` stays `hsl()`.
+    ///
+    /// Return `None` for the notations that can't be written back, e.g. `Color.fromARGB()`.
+    pub(crate) fn format_like(&self, color: &Color) -> Option<String> {
+        let matched = self.matched.as_str();
+
+        // Tailwind arbitrary value like `[#fff]`, which uses `_` for spaces
+        if let Some(value) = matched.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            let node = ColorNode {
+                matched: value.replace('_', " "),
+                ..self.clone()
+            };
+            return Some(format!("[{}]", node.format_like(color)?.replace(' ', "_")));
+        }
+        if matched.starts_with("0x") || matched.starts_with("0X") {
+            return format_0x_like(matched, color);
+        }
+        if let Some(hex) = matched
+            .strip_prefix("Color(")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            return Some(format!("Color({})", format_0x_like(hex.trim(), color)?));
+        }
+
+        let name = matched
+            .split_once('(')
+            .map_or("", |(name, _)| name.trim_end());
+        let commas = matched.contains(',');
+        if !self.is_css_notation() {
+            return match name {
+                "rgb" | "rgba" | "hsl" | "hsla" => Some(format_gpui_like(matched, name, color)),
+                _ => None,
+            };
+        }
+
+        let alpha = (color.a.clamp(0., 1.) < 1.).then(|| format_number(color.a.clamp(0., 1.)));
+        let format_args = |args: [String; 3]| match (commas, alpha.clone()) {
+            (true, Some(alpha)) => format!("{}, {alpha}", args.join(", ")),
+            (true, None) if name.ends_with('a') => format!("{}, 1", args.join(", ")),
+            (true, None) => args.join(", "),
+            (false, Some(alpha)) => format!("{} / {alpha}", args.join(" ")),
+            (false, None) => args.join(" "),
+        };
+
+        let formatted = match name {
+            "rgb" | "rgba" => {
+                let [r, g, b, _] = color.to_rgba8();
+                let name = if commas && alpha.is_some() {
+                    "rgba"
+                } else {
+                    name
+                };
+                format!("{name}({})", format_args([r, g, b].map(|v| v.to_string())))
+            }
+            "hsl" | "hsla" => {
+                let [h, s, l, _] = color.to_hsla();
+                let name = if commas && alpha.is_some() {
+                    "hsla"
+                } else {
+                    name
+                };
+                let args = [
+                    format_number(h),
+                    format!("{}%", format_number(s * 100.)),
+                    format!("{}%", format_number(l * 100.)),
+                ];
+                format!("{name}({})", format_args(args))
+            }
+            "hwb" => color.to_css_hwb(),
+            "oklab" => color.to_css_oklab(),
+            "oklch" => color.to_css_oklch(),
+            "lab" => color.to_css_lab(),
+            "lch" => color.to_css_lch(),
+            // Other CSS notations like named colors fallback to hex
+            _ => {
+                let hex = color.to_css_hex();
+                if matched.starts_with('#') && matched.chars().any(|c| c.is_ascii_uppercase()) {
+                    hex.to_ascii_uppercase()
+                } else {
+                    hex
+                }
+            }
+        };
+        Some(formatted)
+    }
+
+    /// Return the legacy comma separated `rgb()`, `rgba()`, `hsl()` and `hsla()` in the
+    /// CSS Color Level 4 space separated syntax, e.g. `rgb(255 0 0 / 50%)`.
+    pub(crate) fn css4_syntax(&self) -> Option<String> {
+        let (name, args) = self.matched.split_once('(')?;
+        let name = match name.trim_end() {
+            "rgb" | "rgba" => "rgb",
+            "hsl" | "hsla" => "hsl",
+            _ => return None,
+        };
+        let args = args
+            .strip_suffix(')')?
+            .split(',')
+            .map(str::trim)
+            .collect::<Vec<_>>();
+
+        match args.as_slice() {
+            [a, b, c] => Some(format!("{name}({a} {b} {c})")),
+            [a, b, c, alpha] => {
+                let alpha = match alpha.parse::<f32>() {
+                    Ok(alpha) => format!("{}%", format_number(alpha * 100.)),
+                    Err(_) => alpha.to_string(),
+                };
+                Some(format!("{name}({a} {b} {c} / {alpha})"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the color as `rgba(r, g, b, a)` with 0..255 channels, the format accepted by CSS filters
+    /// like `drop-shadow()`.
+    pub(crate) fn css_filter_string(&self) -> String {
+        let (r, g, b, _) = self.to_rgb_255_tuple();
+        let a = (self.color.a.clamp(0.0, 1.0) * 100.0).round() / 100.0;
+        format!("rgba({r}, {g}, {b}, {a})")
+    }
+
+    /// Return the HSL hue in degrees, in `[0, 360)`, `0` for the grays.
+    pub fn hue(&self) -> f32 {
+        let hue = self.color.to_hsla()[0].rem_euclid(360.);
+        // `rem_euclid` may round a tiny negative hue up to 360
+        if hue >= 360. {
+            0.
+        } else {
+            hue
+        }
+    }
+
+    /// Return the tetradic (square) harmony of the color, the colors with the HSL hue rotated
+    /// by 90°, 180° and 270°, keeping the saturation, lightness and alpha.
+    pub fn tetradic(&self) -> [Color; 3] {
+        let [_, s, l, a] = self.color.to_hsla();
+        let hue = self.hue();
+        [90., 180., 270.].map(|rotation| Color::from_hsla((hue + rotation) % 360., s, l, a))
+    }
+
+    /// Return the CIEDE2000 distance (ΔE) to `other`.
+    pub fn delta_e(&self, other: &ColorNode) -> f64 {
+        ciede2000_distance(self, other)
+    }
+
+    /// Return true if the CIEDE2000 distance to `other` is less than `threshold`.
+    pub fn is_near(&self, other: &ColorNode, threshold: f64) -> bool {
+        self.delta_e(other) < threshold
+    }
+
+    /// Return a warning if the color is out of the sRGB gamut.
+    ///
+    /// The color channels are not clipped to sRGB by some color spaces (e.g. `oklch`),
+    /// so check it against the Display P3 gamut.
+    pub(crate) fn to_p3_gamut_warning(&self) -> Option<String> {
+        let Color { r, g, b, .. } = self.color;
+        if [r, g, b].into_iter().all(in_unit_range) {
+            return None;
+        }
+
+        if self.to_p3_values().is_some() {
+            Some("Extended sRGB, within P3 gamut".to_string())
+        } else {
+            Some("Out of P3 gamut".to_string())
+        }
+    }
+
+    /// Return the `(r, g, b)` channels of the color in Display P3, `None` if it's out of the P3 gamut.
+    pub fn to_p3_values(&self) -> Option<(f32, f32, f32)> {
+        let Color { r, g, b, .. } = self.color;
+        let [r, g, b] = to_display_p3([r, g, b]);
+        [r, g, b]
+            .into_iter()
+            .all(in_unit_range)
+            .then_some((r, g, b))
+    }
+
+    /// Return the Xcode Asset Catalog `Contents.json` of a color set with the color.
+    ///
+    /// The wide-gamut colors (out of sRGB) are in the `display-p3` color space.
+    pub fn to_xcassets_json(&self) -> serde_json::Value {
+        let Color { r, g, b, a } = self.color;
+        let (color_space, [r, g, b]) = match self.to_p3_gamut_warning() {
+            Some(_) => ("display-p3", to_display_p3([r, g, b])),
+            None => ("srgb", [r, g, b]),
+        };
+        let component = |v: f32| format!("{:.3}", v.clamp(0., 1.));
+
+        serde_json::json!({
+            "colors": [{
+                "color": {
+                    "color-space": color_space,
+                    "components": {
+                        "alpha": component(a),
+                        "blue": component(b),
+                        "green": component(g),
+                        "red": component(r),
+                    },
+                },
+                "idiom": "universal",
+            }],
+            "info": {
+                "author": "xcode",
+                "version": 1,
+            },
+        })
+    }
+
+    /// The relative luminance of the color.
+    ///
+    /// Ref https://www.w3.org/TR/WCAG21/#dfn-relative-luminance
+    pub fn relative_luminance(&self) -> f64 {
+        let linearize = |c: f32| {
+            let c = f64::from(c.clamp(0.0, 1.0));
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        let Color { r, g, b, .. } = self.color;
+        0.2126 * linearize(r) + 0.7152 * linearize(g) + 0.0722 * linearize(b)
+    }
+
+    /// The contrast ratio to `other`, in range 1.0..=21.0.
+    ///
+    /// Ref https://www.w3.org/TR/WCAG21/#dfn-contrast-ratio
+    pub fn contrast_ratio_with(&self, other: &ColorNode) -> f64 {
+        let (l1, l2) = (self.relative_luminance(), other.relative_luminance());
+        (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+    }
+
     pub(crate) fn lsp_color(&self) -> lsp_types::Color {
         lsp_types::Color {
             red: self.color.r,
@@ -43,16 +566,549 @@ impl ColorNode {
     }
 }
 
-fn try_parse_color(s: &str) -> Result<Color, ParseColorError> {
+impl From<&ColorNode> for lsp_types::ColorInformation {
+    fn from(node: &ColorNode) -> Self {
+        Self {
+            range: node.lsp_range(),
+            color: node.lsp_color(),
+        }
+    }
+}
+
+/// Look up the node of the color information in the full document text, the `matched` is the
+/// text in the range, which must be in one line.
+///
+/// The `layer` of the node is unknown without parsing the document, so it's `None`.
+impl TryFrom<(&lsp_types::ColorInformation, &str)> for ColorNode {
+    type Error = String;
+
+    fn try_from((info, text): (&lsp_types::ColorInformation, &str)) -> Result<Self, Self::Error> {
+        let lsp_types::Range { start, end } = info.range;
+        if start.line != end.line {
+            return Err(format!("The range {:?} is not in one line", info.range));
+        }
+        let line = text
+            .lines()
+            .nth(start.line as usize)
+            .ok_or_else(|| format!("The line {} is out of the text", start.line))?;
+        let offset = byte_offset(line, start.character);
+        let matched = line
+            .get(offset..byte_offset(line, end.character))
+            .filter(|matched| !matched.is_empty())
+            .ok_or_else(|| format!("The range {:?} is empty", info.range))?;
+
+        let lsp_types::Color {
+            red,
+            green,
+            blue,
+            alpha,
+        } = info.color;
+        let color = Color::new(red, green, blue, alpha);
+        let mut node = ColorNode::new(matched, color, start.line as usize + 1, offset + 1);
+        node.locate_utf16(line);
+        Ok(node)
+    }
+}
+
+/// Convert the LSP position `character` in UTF-16 code units to the byte offset of the `line`.
+pub(crate) fn byte_offset(line: &str, character: u32) -> usize {
+    let mut utf16 = 0;
+    for (offset, c) in line.char_indices() {
+        if utf16 >= character as usize {
+            return offset;
+        }
+        utf16 += c.len_utf16();
+    }
+    line.len()
+}
+
+/// Format the number with at most 2 decimals, e.g. `0.5`, `33.33` and `100`.
+fn format_number(v: f32) -> String {
+    let v = (v * 100.).round() / 100.;
+    // Avoid `-0`
+    format!("{}", v + 0.)
+}
+
+/// Format the `color` like the `0xRRGGBB` or `0xAARRGGBB` hex, keeping the prefix and case.
+///
+/// Return `None` for the hex with digit separators, or if `0xRRGGBB` can't keep the alpha.
+fn format_0x_like(hex: &str, color: &Color) -> Option<String> {
+    let (prefix, digits) = hex.split_at(2);
+    let [r, g, b, a] = color.to_rgba8();
+    let formatted = match digits.len() {
+        6 if a == 255 => format!("{r:02x}{g:02x}{b:02x}"),
+        8 => format!("{a:02x}{r:02x}{g:02x}{b:02x}"),
+        _ => return None,
+    };
+
+    if digits.chars().any(|c| c.is_ascii_uppercase()) {
+        Some(format!("{prefix}{}", formatted.to_ascii_uppercase()))
+    } else {
+        Some(format!("{prefix}{formatted}"))
+    }
+}
+
+/// Format the `color` like the gpui `rgb()` or `hsl()` with 0..1 values, keeping the separators.
+fn format_gpui_like(matched: &str, name: &str, color: &Color) -> String {
+    let mut args = if name.starts_with("hsl") {
+        let [h, s, l, _] = color.to_hsla();
+        vec![h / 360., s, l]
+    } else {
+        vec![color.r, color.g, color.b]
+    };
+    // Keep the alpha if the matched has it
+    if color.a < 1. || matched.split([',', ' ']).filter(|s| !s.is_empty()).count() > 3 {
+        args.push(color.a);
+    }
+
+    let separator = if matched.contains(',') { ", " } else { " " };
+    let args = args
+        .into_iter()
+        .map(|v| format_number(v.clamp(0., 1.)))
+        .collect::<Vec<_>>();
+    format!("{name}({})", args.join(separator))
+}
+
+/// All CSS named colors sorted by the Euclidean distance to `color` in sRGB (0..255),
+/// the names with the same distance are sorted alphabetically.
+pub(crate) fn nearest_named_colors(color: &Color) -> Vec<(&'static str, f64)> {
+    let [r, g, b, _] = color.to_rgba8().map(f64::from);
+    let mut colors = csscolorparser::NAMED_COLORS
+        .entries()
+        .map(|(name, rgb)| {
+            let [r2, g2, b2] = rgb.map(f64::from);
+            let distance = ((r - r2).powi(2) + (g - g2).powi(2) + (b - b2).powi(2)).sqrt();
+            (*name, distance)
+        })
+        .collect::<Vec<_>>();
+    colors.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(b.0)));
+    colors
+}
+
+/// Return true if the color text has the alpha channel, like the 4 and 8 digits hex, the `/ alpha`
+/// of CSS and the fourth argument of `rgba(r, g, b, a)` or `Color.fromARGB(a, r, g, b)`.
+fn has_explicit_alpha(matched: &str) -> bool {
+    if let Some(digits) = matched.strip_prefix('#') {
+        return matches!(digits.len(), 4 | 8);
+    }
+    let is_argb = |s: &str| {
+        s.trim()
+            .strip_prefix("0x")
+            .or_else(|| s.trim().strip_prefix("0X"))
+            .is_some_and(|digits| digits.len() == 8)
+    };
+    if is_argb(matched) || matched.contains('/') {
+        return true;
+    }
+    // Go `color.NRGBA{R: 255, A: 128}` and Nix `{ r = 1.0; g = 0.5; b = 0.0; a = 1.0; }`
+    if let Some((_, fields)) = matched.split_once('{') {
+        return fields.split([',', ';']).any(|field| {
+            let name = field.split([':', '=']).next().unwrap_or_default().trim();
+            name.eq_ignore_ascii_case("a")
+        });
+    }
+    let Some(args) = matched
+        .split_once('(')
+        .and_then(|(_, args)| args.strip_suffix(')'))
+    else {
+        return false;
+    };
+    let args = split_top_level(args, ',');
+    args.len() >= 4 || matches!(args[..], [arg] if is_argb(arg))
+}
+
+/// Return true if the channel is in 0..1, allowing the floating point error of the conversions.
+fn in_unit_range(v: f32) -> bool {
+    const EPSILON: f32 = 1e-4;
+    (-EPSILON..=1. + EPSILON).contains(&v)
+}
+
+/// Convert the (extended) sRGB channels to Display P3, the matrix is the sRGB to XYZ (D65)
+/// and XYZ to Display P3 matrices combined.
+fn to_display_p3([r, g, b]: [f32; 3]) -> [f32; 3] {
+    fn to_linear(v: f32) -> f32 {
+        if v.abs() <= 0.04045 {
+            v / 12.92
+        } else {
+            v.signum() * ((v.abs() + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn from_linear(v: f32) -> f32 {
+        if v.abs() <= 0.0031308 {
+            v * 12.92
+        } else {
+            v.signum() * (1.055 * v.abs().powf(1. / 2.4) - 0.055)
+        }
+    }
+
+    let [r, g, b] = [r, g, b].map(to_linear);
+    [
+        0.822_462_1 * r + 0.177_538 * g,
+        0.033_194_1 * r + 0.966_805_8 * g,
+        0.017_082_7 * r + 0.072_397_4 * g + 0.910_519_9 * b,
+    ]
+    .map(from_linear)
+}
+
+/// Return the CIEDE2000 perceptual distance (ΔE) of the colors of the nodes, about 1.0 is
+/// the just noticeable difference.
+pub fn ciede2000_distance(a: &ColorNode, b: &ColorNode) -> f64 {
+    ciede2000(&a.color, &b.color)
+}
+
+/// The CIEDE2000 color difference of two colors, compared in CIELAB (D65).
+fn ciede2000(color1: &Color, color2: &Color) -> f64 {
+    let [l1, a1, b1, _] = color1.to_laba().map(f64::from);
+    let [l2, a2, b2, _] = color2.to_laba().map(f64::from);
+    ciede2000_lab([l1, a1, b1], [l2, a2, b2])
+}
+
+/// The CIEDE2000 color difference of two CIELAB colors.
+///
+/// Ref: Sharma, Wu, Dalal, "The CIEDE2000 Color-Difference Formula", 2005.
+fn ciede2000_lab([l1, a1, b1]: [f64; 3], [l2, a2, b2]: [f64; 3]) -> f64 {
+    const POW25_7: f64 = 6_103_515_625.0;
+
+    let c_bar = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + POW25_7)).sqrt());
+    let (a1, a2) = ((1.0 + g) * a1, (1.0 + g) * a2);
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |a: f64, b: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= 180.0 {
+        h2 - h1
+    } else if h2 - h1 > 180.0 {
+        h2 - h1 - 360.0
+    } else {
+        h2 - h1 + 360.0
+    };
+    let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar = (c1 + c2) / 2.0;
+    let h_bar = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_bar - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * h_bar).to_radians().cos()
+        + 0.32 * (3.0 * h_bar + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_bar - 63.0).to_radians().cos();
+    let delta_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + POW25_7)).sqrt();
+    let s_l = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_bar;
+    let s_h = 1.0 + 0.015 * c_bar * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+    (l * l + c * c + h * h + r_t * c * h).sqrt()
+}
+
+/// Parse a color in any of the supported notations, like CSS colors, gpui `rgb(1., 0., 0.)`
+/// and Flutter `Color(0xFF112233)`.
+pub fn try_parse_color(s: &str) -> Result<Color, ParseColorError> {
+    let result = try_parse_any_color(s);
+    tracing::trace!(input = s, ok = result.is_ok(), "try_parse_color");
+    result
+}
+
+/// Return true if the `s` is a color in any of the notations of [`try_parse_color`].
+pub fn is_valid_css_color(s: &str) -> bool {
+    try_parse_color(s).is_ok()
+}
+
+/// Return true if the `s` is a hex color like `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`,
+/// without the other notations.
+pub fn is_valid_hex_color(s: &str) -> bool {
+    s.strip_prefix('#').is_some_and(|digits| {
+        matches!(digits.len(), 3 | 4 | 6 | 8) && digits.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+/// Return true if the contrast ratio of the colors meets WCAG AA, 4.5:1 for normal text
+/// or 3:1 for `large_text`.
+///
+/// Ref https://www.w3.org/TR/WCAG21/#contrast-minimum
+pub fn wcag_aa_passes(foreground: &ColorNode, background: &ColorNode, large_text: bool) -> bool {
+    let threshold = if large_text { 3.0 } else { 4.5 };
+    foreground.contrast_ratio_with(background) >= threshold
+}
+
+fn try_parse_any_color(s: &str) -> Result<Color, ParseColorError> {
     if let Ok(color) = try_parse_gpui_color(s) {
         return Ok(color);
     }
 
-    csscolorparser::parse(s)
+    if let Ok(color) = try_parse_flutter_color(s) {
+        return Ok(color);
+    }
+
+    if let Ok(color) = try_parse_color_mix(s) {
+        return Ok(color);
+    }
+
+    parse_css_color(s)
+}
+
+/// Parse the CSS color with `csscolorparser`, with the CSS Color Level 4 `none` channels
+/// of `lab()`, `lch()`, `oklab()` and `oklch()` as `0`, e.g. `oklch(0.5 none 180)`,
+/// and the `color()` function of [`try_parse_color_function`].
+///
+/// CSS colors are ASCII, and `csscolorparser` may panic on the non-ASCII units of the angles.
+fn parse_css_color(s: &str) -> Result<Color, ParseColorError> {
+    if !s.is_ascii() {
+        return Err(ParseColorError::InvalidUnknown);
+    }
+    let Some((name, args)) = s.split_once('(') else {
+        return csscolorparser::parse(s);
+    };
+    if name.trim_end().eq_ignore_ascii_case("color") {
+        return try_parse_color_function(s);
+    }
+    let is_lab = ["lab", "lch", "oklab", "oklch"]
+        .iter()
+        .any(|lab| name.trim_end().eq_ignore_ascii_case(lab));
+    if !is_lab {
+        return csscolorparser::parse(s);
+    }
+
+    let args = args
+        .split_inclusive([' ', '/', ')'])
+        .map(|arg| {
+            let (value, separator) = arg.split_at(arg.trim_end_matches([' ', '/', ')']).len());
+            if value.eq_ignore_ascii_case("none") {
+                format!("0{separator}")
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect::<String>();
+    csscolorparser::parse(&format!("{name}({args}"))
+}
+
+/// Multiply the 3x3 matrix `m` by the vector `v`.
+fn mul_matrix(m: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// Parse the CSS `color()` function with the predefined color spaces, like
+/// `color(display-p3 1 0 0)` and `color(xyz-d50 0.4 0.2 0.1 / 50%)`.
+///
+/// The colors are converted through XYZ (D65) to sRGB, the wide gamut colors are kept as the
+/// extended sRGB channels out of 0..1, like `oklch()`.
+///
+/// Ref https://www.w3.org/TR/css-color-4/#color-function
+fn try_parse_color_function(s: &str) -> Result<Color, ParseColorError> {
+    const LINEAR_P3_TO_XYZ: [[f64; 3]; 3] = [
+        [
+            0.486_570_948_648_216_2,
+            0.265_667_693_169_093_06,
+            0.198_217_285_234_362_5,
+        ],
+        [
+            0.228_974_564_069_748_8,
+            0.691_738_521_836_506_4,
+            0.079_286_914_093_745,
+        ],
+        [0., 0.045_113_381_858_902_64, 1.043_944_368_900_976],
+    ];
+    const LINEAR_A98_TO_XYZ: [[f64; 3]; 3] = [
+        [
+            0.576_669_042_910_130_5,
+            0.185_558_237_906_546_3,
+            0.188_228_646_234_994_7,
+        ],
+        [
+            0.297_344_975_250_536_05,
+            0.627_363_566_255_466_1,
+            0.075_291_458_493_997_88,
+        ],
+        [
+            0.027_031_361_386_412_34,
+            0.070_688_852_535_827_23,
+            0.991_337_536_837_638_8,
+        ],
+    ];
+    const LINEAR_PROPHOTO_TO_XYZ_D50: [[f64; 3]; 3] = [
+        [
+            0.797_766_644_900_642_3,
+            0.135_181_297_400_533_08,
+            0.031_347_734_128_392_2,
+        ],
+        [
+            0.288_074_828_819_401_3,
+            0.711_835_234_241_873,
+            0.000_089_936_938_725_64,
+        ],
+        [0., 0., 0.825_104_602_510_460_2],
+    ];
+    const LINEAR_REC2020_TO_XYZ: [[f64; 3]; 3] = [
+        [
+            0.636_958_048_301_291_4,
+            0.144_616_903_586_208_32,
+            0.168_880_975_164_172_1,
+        ],
+        [
+            0.262_700_212_011_267_1,
+            0.677_998_071_518_870_8,
+            0.059_301_716_469_861_96,
+        ],
+        [0., 0.028_072_693_049_087_428, 1.060_985_057_710_791],
+    ];
+    // Bradford chromatic adaptation
+    const XYZ_D50_TO_D65: [[f64; 3]; 3] = [
+        [
+            0.955_473_421_488_075,
+            -0.023_098_454_948_764_71,
+            0.063_259_243_200_570_72,
+        ],
+        [
+            -0.028_369_709_333_863_7,
+            1.009_995_398_081_304_1,
+            0.021_041_441_191_917_323,
+        ],
+        [
+            0.012_314_014_864_481_998,
+            -0.020_507_649_298_898_964,
+            1.330_365_926_242_124,
+        ],
+    ];
+    const XYZ_TO_LINEAR_SRGB: [[f64; 3]; 3] = [
+        [
+            3.240_969_941_904_522_6,
+            -1.537_383_177_570_094,
+            -0.498_610_760_293_003_4,
+        ],
+        [
+            -0.969_243_636_280_879_6,
+            1.875_967_501_507_720_2,
+            0.041_555_057_407_175_59,
+        ],
+        [
+            0.055_630_079_696_993_66,
+            -0.203_976_958_888_976_52,
+            1.056_971_514_242_878_6,
+        ],
+    ];
+
+    // The transfer functions are odd, to extend them to the negative values
+    let srgb_to_linear = |v: f64| {
+        if v.abs() <= 0.04045 {
+            v / 12.92
+        } else {
+            v.signum() * ((v.abs() + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let linear_to_srgb = |v: f64| {
+        if v.abs() <= 0.003_130_8 {
+            v * 12.92
+        } else {
+            v.signum() * (1.055 * v.abs().powf(1. / 2.4) - 0.055)
+        }
+    };
+    let a98_to_linear = |v: f64| v.signum() * v.abs().powf(563. / 256.);
+    let prophoto_to_linear = |v: f64| {
+        if v.abs() <= 16. / 512. {
+            v / 16.
+        } else {
+            v.signum() * v.abs().powf(1.8)
+        }
+    };
+    let rec2020_to_linear = |v: f64| {
+        const ALPHA: f64 = 1.099_296_826_809_44;
+        const BETA: f64 = 0.018_053_968_510_807;
+        if v.abs() < BETA * 4.5 {
+            v / 4.5
+        } else {
+            v.signum() * ((v.abs() + ALPHA - 1.) / ALPHA).powf(1. / 0.45)
+        }
+    };
+
+    let Some(args) = s
+        .trim()
+        .split_once('(')
+        .filter(|(name, _)| name.trim_end().eq_ignore_ascii_case("color"))
+        .and_then(|(_, args)| args.strip_suffix(')'))
+    else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    let (channels, alpha) = match args.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (args, None),
+    };
+    // The number or percentage, `none` is 0
+    let parse_value = |v: &str| {
+        if v.eq_ignore_ascii_case("none") {
+            return Some(0.);
+        }
+        match v.strip_suffix('%') {
+            Some(v) => v.parse::<f64>().ok().map(|v| v / 100.),
+            None => v.parse::<f64>().ok(),
+        }
+        .filter(|v| v.is_finite())
+    };
+
+    let [space, c1, c2, c3] = channels.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    let [Some(c1), Some(c2), Some(c3)] = [c1, c2, c3].map(parse_value) else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    let alpha = match alpha {
+        Some(alpha) => parse_value(alpha).ok_or(ParseColorError::InvalidFunction)?,
+        None => 1.,
+    };
+
+    let values = [c1, c2, c3];
+    let linear = match space.to_ascii_lowercase().as_str() {
+        "srgb" => values.map(srgb_to_linear),
+        "srgb-linear" => values,
+        "display-p3" => {
+            let xyz = mul_matrix(&LINEAR_P3_TO_XYZ, values.map(srgb_to_linear));
+            mul_matrix(&XYZ_TO_LINEAR_SRGB, xyz)
+        }
+        "a98-rgb" => {
+            let xyz = mul_matrix(&LINEAR_A98_TO_XYZ, values.map(a98_to_linear));
+            mul_matrix(&XYZ_TO_LINEAR_SRGB, xyz)
+        }
+        "prophoto-rgb" => {
+            let xyz = mul_matrix(&LINEAR_PROPHOTO_TO_XYZ_D50, values.map(prophoto_to_linear));
+            mul_matrix(&XYZ_TO_LINEAR_SRGB, mul_matrix(&XYZ_D50_TO_D65, xyz))
+        }
+        "rec2020" => {
+            let xyz = mul_matrix(&LINEAR_REC2020_TO_XYZ, values.map(rec2020_to_linear));
+            mul_matrix(&XYZ_TO_LINEAR_SRGB, xyz)
+        }
+        "xyz-d50" => mul_matrix(&XYZ_TO_LINEAR_SRGB, mul_matrix(&XYZ_D50_TO_D65, values)),
+        "xyz" | "xyz-d65" => mul_matrix(&XYZ_TO_LINEAR_SRGB, values),
+        _ => return Err(ParseColorError::InvalidFunction),
+    };
+
+    let [r, g, b] = linear.map(|v| linear_to_srgb(v) as f32);
+    Ok(Color::new(r, g, b, alpha.clamp(0., 1.) as f32))
 }
 
 /// Try to parse gpui color that values are 0..1
-fn try_parse_gpui_color(s: &str) -> Result<Color, ParseColorError> {
+pub fn try_parse_gpui_color(s: &str) -> Result<Color, ParseColorError> {
     let s = s.trim();
 
     /// Parse and ensure all value in 0..1
@@ -95,9 +1151,14 @@ fn try_parse_gpui_color(s: &str) -> Result<Color, ParseColorError> {
                 return Err(ParseColorError::InvalidFunction);
             }
         } else if fname.eq_ignore_ascii_case("hsl") || fname.eq_ignore_ascii_case("hsla") {
-            if let (Some(v0), Some(v1), Some(v2)) = (parse_f8(val0), parse_f8(val1), parse_f8(val2))
-            {
-                return Ok(Color::from_hsla(v0 * 360.0, v1, v2, alpha));
+            // A bare gpui hue is a fraction of a turn, but CSS angle units are accepted too.
+            let hue = if val0.ends_with(|c: char| c.is_ascii_alphabetic()) {
+                parse_angle(val0).filter(|h| h.is_finite())
+            } else {
+                parse_f8(val0).map(|v| v * 360.0)
+            };
+            if let (Some(h), Some(v1), Some(v2)) = (hue, parse_f8(val1), parse_f8(val2)) {
+                return Ok(Color::from_hsla(h, v1, v2, alpha));
             } else {
                 return Err(ParseColorError::InvalidFunction);
             }
@@ -107,234 +1168,4257 @@ fn try_parse_gpui_color(s: &str) -> Result<Color, ParseColorError> {
     Err(ParseColorError::InvalidUnknown)
 }
 
-fn is_hex_char(c: &char) -> bool {
-    matches!(c, '#' | 'a'..='f' | 'A'..='F' | '0'..='9')
+/// Parse a CSS hue angle like `120`, `120deg`, `2.1rad`, `133.3grad` or `0.33turn` into degrees.
+pub(crate) fn parse_angle(s: &str) -> Option<f32> {
+    let s = s.trim();
+    let (value, unit) = s.split_at(
+        s.char_indices()
+            .rfind(|(_, c)| !c.is_ascii_alphabetic())
+            .map_or(0, |(ix, c)| ix + c.len_utf8()),
+    );
+    let value: f32 = value.parse().ok()?;
+    match unit.to_ascii_lowercase().as_str() {
+        "" | "deg" => Some(value),
+        "rad" => Some(value.to_degrees()),
+        "grad" => Some(value * 0.9),
+        "turn" => Some(value * 360.0),
+        _ => None,
+    }
 }
 
-pub(super) fn parse(text: &str) -> Vec<ColorNode> {
-    let mut nodes = Vec::new();
+/// The UIKit system colors (light appearance) can be used in SwiftUI like `Color(.systemBlue)`.
+const SWIFT_SYSTEM_COLORS: &[(&str, [u8; 3])] = &[
+    ("systemRed", [0xff, 0x3b, 0x30]),
+    ("systemOrange", [0xff, 0x95, 0x00]),
+    ("systemYellow", [0xff, 0xcc, 0x00]),
+    ("systemGreen", [0x34, 0xc7, 0x59]),
+    ("systemMint", [0x00, 0xc7, 0xbe]),
+    ("systemTeal", [0x30, 0xb0, 0xc7]),
+    ("systemCyan", [0x32, 0xad, 0xe6]),
+    ("systemBlue", [0x00, 0x7a, 0xff]),
+    ("systemIndigo", [0x58, 0x56, 0xd6]),
+    ("systemPurple", [0xaf, 0x52, 0xde]),
+    ("systemPink", [0xff, 0x2d, 0x55]),
+    ("systemBrown", [0xa2, 0x84, 0x5e]),
+    ("systemGray", [0x8e, 0x8e, 0x93]),
+    ("systemGray2", [0xae, 0xae, 0xb2]),
+    ("systemGray3", [0xc7, 0xc7, 0xcc]),
+    ("systemGray4", [0xd1, 0xd1, 0xd6]),
+    ("systemGray5", [0xe5, 0xe5, 0xea]),
+    ("systemGray6", [0xf2, 0xf2, 0xf7]),
+    ("black", [0x00, 0x00, 0x00]),
+    ("white", [0xff, 0xff, 0xff]),
+];
 
-    for (ix, line_text) in text.lines().enumerate() {
-        let line_len = line_text.len();
-        let mut offset = 0;
-        let mut token = String::new();
-        while offset < line_text.len() {
-            let c = line_text.chars().nth(offset).unwrap_or(' ');
-            match c {
-                '#' => {
-                    token.clear();
-
-                    // Find the hex color code
-                    // 使用 char_indices 遍历字符
-                    let mut chars = line_text[offset..].char_indices().peekable();
-                    let mut hex = String::new();
-                    while let Some((i, c)) = chars.peek() {
-                        if !is_hex_char(*c) || hex.chars().count() >= 9 {
-                            break;
-                        }
-                        hex.push(*c);
-                        chars.next();
-                    }
-                    
-                    // hex 的字节长度 
-                    let hex_byte_len = hex.len();
-                    
-                    if let Some(node) = match_color(&hex, ix, offset) {
-                        nodes.push(node);
-                        offset += hex_byte_len; // 按字节偏移，这里 safe 因为 hex 是连续字符切出来的
-                        continue;
-                    }
+/// Parse SwiftUI `Color(hue: 0.5, saturation: 0.8, brightness: 1.0, opacity: 1.0)`
+/// with labels in any order, or a system color like `Color(.systemBlue)`.
+fn try_parse_swiftui_color(s: &str) -> Result<Color, ParseColorError> {
+    let Some(args) = s
+        .trim()
+        .strip_prefix("Color(")
+        .and_then(|s| s.strip_suffix(')'))
+    else {
+        return Err(ParseColorError::InvalidFunction);
+    };
 
-                }
-                'a'..='z' | 'A'..='Z' | '(' => {
-                    // Avoid `Ok(hsla(`, to get `hsla(`
-                    if token.contains('(') {
-                        token.clear();
-                    }
+    if let Some(name) = args.trim().strip_prefix('.') {
+        return SWIFT_SYSTEM_COLORS
+            .iter()
+            .find(|(system_name, _)| *system_name == name)
+            .map(|(_, [r, g, b])| Color::from_rgba8(*r, *g, *b, 255))
+            .ok_or(ParseColorError::InvalidUnknown);
+    }
 
-                    token.push(c);
-                    match token.as_ref() {
-                        // Ref https://github.com/mazznoer/csscolorparser-rs
-                        "hsl(" | "hsla(" | "rgb(" | "rgba(" | "hwb(" | "hwba(" | "oklab("
-                        | "oklch(" | "lab(" | "lch(" | "hsv(" => {
-                            // Find until the closing parenthesis
-                            let end = line_text[offset..]
-                                .chars()
-                                .position(|c| c == ')')
-                                .unwrap_or(0);
-                            let token_offset = offset.saturating_sub(token.len()) + 1;
-                            token.push_str(
-                                &line_text
-                                    [(offset + 1).min(line_len)..(offset + end + 1).min(line_len)],
-                            );
-
-                            if let Some(node) = match_color(&token, ix, token_offset) {
-                                token.clear();
-                                nodes.push(node);
-                                offset += end + 1;
-                                continue;
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {
-                    token.clear();
+    let (mut hue, mut saturation, mut brightness, mut opacity) = (None, None, None, 1.0);
+    for arg in args.split(',') {
+        let Some((label, value)) = arg.split_once(':') else {
+            return Err(ParseColorError::InvalidFunction);
+        };
+        let value = value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|v| (0.0..=1.0).contains(v))
+            .ok_or(ParseColorError::InvalidFunction)?;
+
+        match label.trim() {
+            "hue" => hue = Some(value),
+            "saturation" => saturation = Some(value),
+            "brightness" => brightness = Some(value),
+            "opacity" => opacity = value,
+            _ => return Err(ParseColorError::InvalidFunction),
+        }
+    }
+
+    let (Some(h), Some(s), Some(b)) = (hue, saturation, brightness) else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    Ok(Color::from_hsva(h * 360.0, s, b, opacity))
+}
+
+/// Parse UIKit `UIColor(red: 1.0, green: 0.5, blue: 0.0, alpha: 1.0)` with labels in any order,
+/// or the gray `UIColor(white: 0.5, alpha: 1.0)`.
+fn try_parse_uicolor(s: &str) -> Result<Color, ParseColorError> {
+    let Some(args) = s
+        .trim()
+        .strip_prefix("UIColor(")
+        .and_then(|s| s.strip_suffix(')'))
+    else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+
+    let (mut red, mut green, mut blue, mut white, mut alpha) = (None, None, None, None, 1.0);
+    for arg in args.split(',') {
+        let Some((label, value)) = arg.split_once(':') else {
+            return Err(ParseColorError::InvalidFunction);
+        };
+        let value = value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|v| (0.0..=1.0).contains(v))
+            .ok_or(ParseColorError::InvalidFunction)?;
+
+        match label.trim() {
+            "red" => red = Some(value),
+            "green" => green = Some(value),
+            "blue" => blue = Some(value),
+            "white" => white = Some(value),
+            "alpha" => alpha = value,
+            _ => return Err(ParseColorError::InvalidFunction),
+        }
+    }
+
+    match (red, green, blue, white) {
+        (Some(r), Some(g), Some(b), None) => Ok(Color::new(r, g, b, alpha)),
+        (None, None, None, Some(w)) => Ok(Color::new(w, w, w, alpha)),
+        _ => Err(ParseColorError::InvalidFunction),
+    }
+}
+
+/// Parse Go `color.RGBA{R: 255, G: 0, B: 0, A: 255}` and `color.NRGBA{R: 0xff, A: 0x80}` struct
+/// literals, the fields can be in any order and the missing ones are zero.
+///
+/// The channels of `color.RGBA` are alpha-premultiplied, so they are divided by the alpha.
+fn try_parse_go_color(s: &str) -> Result<Color, ParseColorError> {
+    let s = s.trim();
+    let (premultiplied, fields) = if let Some(fields) = s.strip_prefix("color.RGBA{") {
+        (true, fields)
+    } else if let Some(fields) = s.strip_prefix("color.NRGBA{") {
+        (false, fields)
+    } else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    let Some(fields) = fields.strip_suffix('}') else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+
+    let mut rgba = [0u8; 4];
+    for field in fields.split(',').filter(|field| !field.trim().is_empty()) {
+        let Some((name, value)) = field.split_once(':') else {
+            return Err(ParseColorError::InvalidFunction);
+        };
+        let value = value.trim();
+        let value = match value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|_| ParseColorError::InvalidFunction)?;
+
+        match name.trim() {
+            "R" => rgba[0] = value,
+            "G" => rgba[1] = value,
+            "B" => rgba[2] = value,
+            "A" => rgba[3] = value,
+            _ => return Err(ParseColorError::InvalidFunction),
+        }
+    }
+
+    let [r, g, b, a] = rgba.map(|v| v as f32 / 255.0);
+    if premultiplied && a > 0.0 {
+        Ok(Color::new(
+            (r / a).min(1.0),
+            (g / a).min(1.0),
+            (b / a).min(1.0),
+            a,
+        ))
+    } else {
+        Ok(Color::new(r, g, b, a))
+    }
+}
+
+/// Parse a Nix attribute set of 0..1 channels like `{ r = 1.0; g = 0.5; b = 0.0; a = 1.0; }`,
+/// the attributes can be in any order and `a` is 1.0 if missing.
+fn try_parse_nix_color(s: &str) -> Result<Color, ParseColorError> {
+    let Some(attrs) = s.trim().strip_prefix('{').and_then(|s| s.strip_suffix('}')) else {
+        return Err(ParseColorError::InvalidUnknown);
+    };
+
+    let mut rgba = [None, None, None, Some(1.0)];
+    for attr in attrs.split(';').filter(|attr| !attr.trim().is_empty()) {
+        let Some((name, value)) = attr.split_once('=') else {
+            return Err(ParseColorError::InvalidUnknown);
+        };
+        let value = value
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|v| (0.0..=1.0).contains(v))
+            .ok_or(ParseColorError::InvalidUnknown)?;
+
+        match name.trim() {
+            "r" => rgba[0] = Some(value),
+            "g" => rgba[1] = Some(value),
+            "b" => rgba[2] = Some(value),
+            "a" => rgba[3] = Some(value),
+            _ => return Err(ParseColorError::InvalidUnknown),
+        }
+    }
+
+    match rgba {
+        [Some(r), Some(g), Some(b), Some(a)] => Ok(Color::new(r, g, b, a)),
+        _ => Err(ParseColorError::InvalidUnknown),
+    }
+}
+
+/// Parse a Tk color like `#rrggbb`, `red`, `light blue`, `LightBlue` and `gray75`.
+///
+/// The Tk named colors are approximated by the CSS named colors, `grayN` and `greyN` are the
+/// grays of `N`% (0..100) lightness.
+fn try_parse_tk_color(s: &str) -> Result<Color, ParseColorError> {
+    if s.starts_with('#') {
+        return parse_css_color(s);
+    }
+    let name = s.replace(' ', "").to_ascii_lowercase();
+    let level = name
+        .strip_prefix("gray")
+        .or_else(|| name.strip_prefix("grey"))
+        .filter(|level| !level.is_empty());
+    if let Some(level) = level {
+        let level = level
+            .parse::<u32>()
+            .ok()
+            .filter(|level| *level <= 100)
+            .ok_or(ParseColorError::InvalidUnknown)?;
+        let v = ((level * 255 + 50) / 100) as u8;
+        return Ok(Color::from_rgba8(v, v, v, 255));
+    }
+    let [r, g, b] = csscolorparser::NAMED_COLORS
+        .get(name.as_str())
+        .ok_or(ParseColorError::InvalidUnknown)?;
+    Ok(Color::from_rgba8(*r, *g, *b, 255))
+}
+
+/// Try to parse CSS `color-mix(in <space>, <color> [<percentage>], <color> [<percentage>])`.
+///
+/// Ref https://developer.mozilla.org/en-US/docs/Web/CSS/color_value/color-mix
+fn try_parse_color_mix(s: &str) -> Result<Color, ParseColorError> {
+    let Some(args) = s
+        .trim()
+        .strip_prefix("color-mix(")
+        .and_then(|s| s.strip_suffix(')'))
+    else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+
+    let [space, color1, color2] = split_top_level(args, ',')[..] else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    let Some(space) = space.trim().strip_prefix("in ").map(str::trim) else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+
+    /// Parse `<color> [<percentage>]` or `<percentage> <color>`.
+    fn parse_mix_color(s: &str) -> Result<(Color, Option<f32>), ParseColorError> {
+        let s = s.trim();
+        let parse_percentage = |s: &str| {
+            s.strip_suffix('%')
+                .and_then(|v| v.parse::<f32>().ok())
+                .filter(|v| (0.0..=100.0).contains(v))
+        };
+
+        if let Some((color, percentage)) = s.rsplit_once(char::is_whitespace) {
+            if let Some(percentage) = parse_percentage(percentage) {
+                return Ok((try_parse_color(color)?, Some(percentage)));
+            }
+        }
+        if let Some((percentage, color)) = s.split_once(char::is_whitespace) {
+            if let Some(percentage) = parse_percentage(percentage) {
+                return Ok((try_parse_color(color)?, Some(percentage)));
+            }
+        }
+        Ok((try_parse_color(s)?, None))
+    }
+
+    let (color1, p1) = parse_mix_color(color1)?;
+    let (color2, p2) = parse_mix_color(color2)?;
+    let (p1, p2) = match (p1, p2) {
+        (None, None) => (50., 50.),
+        (Some(p1), None) => (p1, 100. - p1),
+        (None, Some(p2)) => (100. - p2, p2),
+        (Some(p1), Some(p2)) => (p1, p2),
+    };
+    if p1 + p2 <= 0. {
+        return Err(ParseColorError::InvalidFunction);
+    }
+
+    let t = p2 / (p1 + p2);
+    let mut color = match space {
+        "srgb" => color1.interpolate_rgb(&color2, t),
+        "srgb-linear" => color1.interpolate_linear_rgb(&color2, t),
+        "oklab" => color1.interpolate_oklab(&color2, t),
+        "lab" => color1.interpolate_lab(&color2, t),
+        "lch" => color1.interpolate_lch(&color2, t),
+        "oklch" => {
+            let [l1, c1, h1, a1] = color1.to_oklcha();
+            let [l2, c2, h2, a2] = color2.to_oklcha();
+            Color::from_oklcha(
+                l1 + t * (l2 - l1),
+                c1 + t * (c2 - c1),
+                lerp_hue(h1.to_degrees(), h2.to_degrees(), t).to_radians(),
+                a1 + t * (a2 - a1),
+            )
+        }
+        "hsl" => {
+            let [h1, s1, l1, a1] = color1.to_hsla();
+            let [h2, s2, l2, a2] = color2.to_hsla();
+            Color::from_hsla(
+                lerp_hue(h1, h2, t),
+                s1 + t * (s2 - s1),
+                l1 + t * (l2 - l1),
+                a1 + t * (a2 - a1),
+            )
+        }
+        _ => return Err(ParseColorError::InvalidFunction),
+    };
+
+    // The percentages sum less than 100% make the result transparent.
+    if p1 + p2 < 100. {
+        color.a *= (p1 + p2) / 100.;
+    }
+    Ok(color)
+}
+
+/// Interpolate the hue in degrees with the shorter arc.
+fn lerp_hue(h1: f32, h2: f32, t: f32) -> f32 {
+    let h1 = if h1.is_nan() { h2 } else { h1 };
+    let h2 = if h2.is_nan() { h1 } else { h2 };
+    let delta = (h2 - h1 + 540.).rem_euclid(360.) - 180.;
+    (h1 + t * delta).rem_euclid(360.)
+}
+
+/// Split `s` by `separator` that is not inside parentheses.
+fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start) = (0usize, 0);
+    for (ix, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if c == separator && depth == 0 => {
+                parts.push(&s[start..ix]);
+                start = ix + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Try to parse Flutter color like `Color(0xFF112233)`, `Color.fromARGB(255, 17, 34, 51)`
+/// and `Color.fromRGBO(17, 34, 51, 1.0)`.
+fn try_parse_flutter_color(s: &str) -> Result<Color, ParseColorError> {
+    let s = s.trim();
+    let (Some(idx), Some(s)) = (s.find('('), s.strip_suffix(')')) else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    let args = s[idx + 1..].split(',').map(str::trim).collect::<Vec<_>>();
+
+    match (&s[..idx], args.as_slice()) {
+        ("Color", [hex]) if hex.len() == 10 => parse_0x_color(hex),
+        ("Color.fromARGB", [a, r, g, b]) => {
+            let [a, r, g, b] = [a, r, g, b].map(|v| v.parse::<u8>().ok());
+            match (a, r, g, b) {
+                (Some(a), Some(r), Some(g), Some(b)) => Ok(Color::from_rgba8(r, g, b, a)),
+                _ => Err(ParseColorError::InvalidFunction),
+            }
+        }
+        ("Color.fromRGBO", [r, g, b, opacity]) => {
+            let [r, g, b] = [r, g, b].map(|v| v.parse::<u8>().ok());
+            let opacity = opacity
+                .parse::<f32>()
+                .ok()
+                .filter(|v| (0.0..=1.0).contains(v));
+            match (r, g, b, opacity) {
+                (Some(r), Some(g), Some(b), Some(opacity)) => {
+                    let [r, g, b, _] = Color::from_rgba8(r, g, b, 255).to_array();
+                    Ok(Color::new(r, g, b, opacity))
+                }
+                _ => Err(ParseColorError::InvalidFunction),
+            }
+        }
+        _ => Err(ParseColorError::InvalidFunction),
+    }
+}
+
+/// Return the Godot color constant like `RED` and `DARK_SLATE_BLUE`.
+///
+/// The constants are the X11 colors, which are the CSS named colors except `GRAY`, `GREEN`,
+/// `MAROON` and `PURPLE`, the CSS ones are prefixed with `WEB_`.
+///
+/// Ref https://docs.godotengine.org/en/stable/classes/class_color.html#constants
+fn godot_named_color(name: &str) -> Option<Color> {
+    match name {
+        "GRAY" => return Some(Color::from_rgba8(190, 190, 190, 255)),
+        "GREEN" => return Some(Color::from_rgba8(0, 255, 0, 255)),
+        "MAROON" => return Some(Color::from_rgba8(176, 48, 96, 255)),
+        "PURPLE" => return Some(Color::from_rgba8(160, 32, 240, 255)),
+        "TRANSPARENT" => return Some(Color::new(1., 1., 1., 0.)),
+        _ => {}
+    }
+    if !name.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+        return None;
+    }
+    let name = name.strip_prefix("WEB_").unwrap_or(name);
+    let [r, g, b] =
+        csscolorparser::NAMED_COLORS.get(name.replace('_', "").to_ascii_lowercase().as_str())?;
+    Some(Color::from_rgba8(*r, *g, *b, 255))
+}
+
+/// Parse Godot `Color(0.5, 0.2, 0.8, 1.0)` with 0..1 channels, `Color("#ff5722")` with a hex
+/// or a constant name, and the constants like `Color.RED`.
+fn try_parse_godot_color(s: &str) -> Result<Color, ParseColorError> {
+    let s = s.trim();
+    if let Some(name) = s.strip_prefix("Color.") {
+        return godot_named_color(name).ok_or(ParseColorError::InvalidUnknown);
+    }
+    let Some(args) = s.strip_prefix("Color(").and_then(|s| s.strip_suffix(')')) else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+    let args = args.split(',').map(str::trim).collect::<Vec<_>>();
+
+    match args.as_slice() {
+        [code] => {
+            let code = code
+                .strip_prefix('"')
+                .and_then(|s| s.strip_suffix('"'))
+                .or_else(|| code.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')))
+                .ok_or(ParseColorError::InvalidFunction)?;
+            let hex = format!("#{}", code.trim_start_matches('#'));
+            if is_valid_hex_color(&hex) {
+                parse_css_color(&hex)
+            } else {
+                godot_named_color(&code.to_ascii_uppercase().replace(' ', "_"))
+                    .ok_or(ParseColorError::InvalidUnknown)
+            }
+        }
+        [_, _, _] | [_, _, _, _] => {
+            let channels = args
+                .iter()
+                .map(|v| v.parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ParseColorError::InvalidFunction)?;
+            let a = channels.get(3).copied().unwrap_or(1.0);
+            Ok(Color::new(channels[0], channels[1], channels[2], a))
+        }
+        _ => Err(ParseColorError::InvalidFunction),
+    }
+}
+
+/// Parse the Haskell `colour` package colors, `sRGB24 255 128 0` with 0..255 channels,
+/// `sRGB 0.5 0.2 0.8` with 0..1 channels, and the names of `Data.Colour.Names` imported as
+/// `Colour` like `Colour.lightcoral`.
+fn try_parse_haskell_color(s: &str) -> Result<Color, ParseColorError> {
+    let s = s.trim();
+    if let Some(name) = s.strip_prefix("Colour.") {
+        let [r, g, b] = csscolorparser::NAMED_COLORS
+            .get(name)
+            .ok_or(ParseColorError::InvalidUnknown)?;
+        return Ok(Color::from_rgba8(*r, *g, *b, 255));
+    }
+
+    let (function, args) = s.split_once(' ').ok_or(ParseColorError::InvalidFunction)?;
+    let args = args.split_whitespace().collect::<Vec<_>>();
+    if args.len() != 3 {
+        return Err(ParseColorError::InvalidFunction);
+    }
+    match function {
+        "sRGB24" => {
+            let channels = args
+                .iter()
+                .map(|v| v.parse::<u8>().ok())
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ParseColorError::InvalidFunction)?;
+            Ok(Color::from_rgba8(
+                channels[0],
+                channels[1],
+                channels[2],
+                255,
+            ))
+        }
+        "sRGB" => {
+            let channels = args
+                .iter()
+                .map(|v| v.parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(ParseColorError::InvalidFunction)?;
+            Ok(Color::new(channels[0], channels[1], channels[2], 1.0))
+        }
+        _ => Err(ParseColorError::InvalidFunction),
+    }
+}
+
+/// Parse `0x` prefixed hex color, `0xRRGGBB` as RGB and `0xAARRGGBB` as ARGB.
+/// Parse the Android `#AARRGGBB` hex color, like `Color.parseColor` and the XML resources.
+fn try_parse_android_color(s: &str) -> Result<Color, ParseColorError> {
+    let digits = s
+        .strip_prefix('#')
+        .filter(|digits| digits.len() == 8 && digits.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or(ParseColorError::InvalidHex)?;
+    let argb = u32::from_str_radix(digits, 16).map_err(|_| ParseColorError::InvalidHex)?;
+    let [a, r, g, b] = argb.to_be_bytes();
+    Ok(Color::from_rgba8(r, g, b, a))
+}
+
+fn parse_0x_color(s: &str) -> Result<Color, ParseColorError> {
+    let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) else {
+        return Err(ParseColorError::InvalidHex);
+    };
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ParseColorError::InvalidHex);
+    }
+    let value = u32::from_str_radix(hex, 16).map_err(|_| ParseColorError::InvalidHex)?;
+    let [a, r, g, b] = value.to_be_bytes();
+
+    match hex.len() {
+        6 => Ok(Color::from_rgba8(r, g, b, 255)),
+        8 => Ok(Color::from_rgba8(r, g, b, a)),
+        _ => Err(ParseColorError::InvalidHex),
+    }
+}
+
+/// Take the `0x` prefix and all following hex digits from the start of `s`,
+/// the `_` digit separators are included if `separators` is true.
+fn take_0x_hex(s: &str, separators: bool) -> Option<&str> {
+    if !(s.starts_with("0x") || s.starts_with("0X")) {
+        return None;
+    }
+    let len = s[2..]
+        .find(|c: char| !(c.is_ascii_hexdigit() || separators && c == '_'))
+        .unwrap_or(s.len() - 2);
+    Some(s[..2 + len].trim_end_matches('_'))
+}
+
+/// Remove the `_` digit separators in number literal, e.g.: `0xFF_00_FF` to `0xFF00FF`.
+fn strip_digit_separators(s: &str) -> String {
+    s.replace('_', "")
+}
+
+/// Return true if the `offset` of `line` is not in the middle of a word.
+fn is_word_start(line: &str, offset: usize) -> bool {
+    line.get(..offset)
+        .is_none_or(|s| !s.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+/// A color format that can be matched in the text.
+pub trait ColorPattern: Send + Sync {
+    /// Try to match a color at the start of `text`, the `line` and `col` (1-based) are the location of
+    /// `text` in the document.
+    ///
+    /// The returned node may start after `col`, when the pattern needs the text before the color.
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode>;
+
+    /// The formats matched by the pattern, for `color-lsp --list-formats`.
+    fn formats(&self) -> Vec<ColorFormat> {
+        Vec::new()
+    }
+}
+
+impl<P: ColorPattern + ?Sized> ColorPattern for std::sync::Arc<P> {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        (**self).try_match(text, line, col)
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        (**self).formats()
+    }
+}
+
+/// A color format matched by a [`ColorPattern`], with the syntax and an example that the
+/// pattern matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorFormat {
+    pub name: &'static str,
+    pub syntax: &'static str,
+    pub example: &'static str,
+}
+
+impl ColorFormat {
+    pub const fn new(name: &'static str, syntax: &'static str, example: &'static str) -> Self {
+        Self {
+            name,
+            syntax,
+            example,
+        }
+    }
+}
+
+/// The language ids with extra patterns in [`ColorPatternRegistry::for_document`].
+const PATTERN_LANGUAGES: &[&str] = &[
+    "dart",
+    "gdscript",
+    "glsl",
+    "haskell",
+    "javascript",
+    "luau",
+    "nix",
+    "python",
+    "swift",
+    "typescript",
+];
+
+/// All the formats of the built-in patterns, with the language ids they are limited to,
+/// empty for all the languages.
+pub fn supported_formats() -> Vec<(ColorFormat, Vec<&'static str>)> {
+    let config = Config {
+        enable_android_argb: true,
+        ..Config::default()
+    };
+    let mut formats = ColorPatternRegistry::builtin("", &config)
+        .formats()
+        .into_iter()
+        .map(|format| (format, vec![]))
+        .collect::<Vec<_>>();
+    for &language_id in PATTERN_LANGUAGES {
+        for format in ColorPatternRegistry::for_document(language_id, "", &config).formats() {
+            match formats.iter_mut().find(|(f, _)| *f == format) {
+                Some((_, languages)) if languages.is_empty() => {}
+                Some((_, languages)) => languages.push(language_id),
+                None => formats.push((format, vec![language_id])),
+            }
+        }
+    }
+    formats
+}
+
+/// A list of [`ColorPattern`]s, the first matched pattern wins.
+#[derive(Default)]
+pub struct ColorPatternRegistry {
+    patterns: Vec<Box<dyn ColorPattern>>,
+}
+
+impl ColorPatternRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in patterns for the `language_id`.
+    pub fn builtin(language_id: &str, config: &Config) -> Self {
+        let mut registry = Self::new();
+        registry.register(CustomPropertyPattern {
+            hex: config.enable_hex,
+        });
+        registry
+            .register(TailwindPattern {
+                hex: config.enable_hex,
+            })
+            .register(TailwindThemePattern);
+        if config.enable_hex {
+            if config.enable_android_argb {
+                registry.register(AndroidArgbPattern);
+            }
+            registry.register(HexPattern);
+        }
+        if config.hex_0x_enabled(language_id) {
+            registry.register(Hex0xPattern {
+                digit_separators: matches!(language_id, "rust" | "zig"),
+            });
+        }
+        registry
+            .register(GpuiPattern)
+            .register(RgbPattern)
+            .register(HslPattern)
+            .register(CssFunctionPattern)
+            .register(ColorMixPattern {
+                hex: config.enable_hex,
+            });
+        if language_id == "dart" {
+            registry.register(FlutterPattern);
+        }
+        if matches!(
+            language_id,
+            "javascript" | "typescript" | "javascriptreact" | "typescriptreact"
+        ) {
+            registry.register(ThreeColorPattern);
+        }
+        if language_id == "swift" {
+            registry.register(SwiftUiPattern);
+        }
+        if config.enable_uicolor {
+            registry.register(UiColorPattern);
+        }
+        if config.enable_go_color {
+            registry.register(GoColorPattern);
+        }
+        if language_id == "glsl" {
+            registry.register(GlslUniformPattern);
+        }
+        if language_id == "python" {
+            registry.register(TkinterPattern);
+        }
+        if language_id == "nix" {
+            registry.register(NixAttrsetPattern);
+        }
+        if language_id == "gdscript" {
+            registry.register(GodotPattern);
+        }
+        if language_id == "haskell" {
+            registry.register(HaskellColourPattern);
+        }
+        registry
+    }
+
+    /// The built-in patterns for the document, with the patterns that depend on the text,
+    /// e.g. Roblox `Color3` in Lua scripts that use `game.` or `script.`.
+    pub fn for_document(language_id: &str, text: &str, config: &Config) -> Self {
+        let mut registry = Self::builtin(language_id, config);
+        let is_roblox = language_id == "luau"
+            || language_id == "lua" && (text.contains("game.") || text.contains("script."));
+        if is_roblox {
+            registry.register(RobloxColor3Pattern);
+        }
+        registry
+    }
+
+    pub fn register(&mut self, pattern: impl ColorPattern + 'static) -> &mut Self {
+        self.patterns.push(Box::new(pattern));
+        self
+    }
+}
+
+impl ColorPattern for ColorPatternRegistry {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        self.patterns
+            .iter()
+            .find_map(|pattern| pattern.try_match(text, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        self.patterns
+            .iter()
+            .flat_map(|pattern| pattern.formats())
+            .collect()
+    }
+}
+
+/// Take the function call at the start of `text` if it's one of `names`, until the first `)`.
+fn take_function<'a>(text: &'a str, names: &[&str]) -> Option<&'a str> {
+    let name = names.iter().find(|name| {
+        text.strip_prefix(**name)
+            .is_some_and(|rest| rest.starts_with('('))
+    })?;
+    let end = text[name.len()..].find(')')?;
+    Some(&text[..=name.len() + end])
+}
+
+/// Same as `take_function`, but the arguments can contain nested functions.
+fn take_nested_function<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let args = text.strip_prefix(name)?.strip_prefix('(')?;
+    let mut depth = 1;
+    for (ix, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[..name.len() + 2 + ix]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Hex color like `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa`.
+pub struct HexPattern;
+
+impl ColorPattern for HexPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let digits = text.strip_prefix('#')?;
+        let len = digits
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .unwrap_or(digits.len())
+            .min(8);
+        let hex = &text[..1 + len];
+        let color = parse_css_color(hex).ok()?;
+        Some(ColorNode::new(hex, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new("hex3", "#rgb", "#f52"),
+            ColorFormat::new("hex4", "#rgba", "#f528"),
+            ColorFormat::new("hex6", "#rrggbb", "#ff5722"),
+            ColorFormat::new("hex8", "#rrggbbaa", "#ff572280"),
+        ]
+    }
+}
+
+/// Android hex color `#AARRGGBB` with the alpha first, other lengths are left to [`HexPattern`].
+pub struct AndroidArgbPattern;
+
+impl ColorPattern for AndroidArgbPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let hex = text.get(..9)?;
+        if text[9..].starts_with(|c: char| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let color = try_parse_android_color(hex).ok()?;
+        Some(ColorNode::new(hex, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "argb8",
+            "#aarrggbb with enable_android_argb",
+            "#80ff5722",
+        )]
+    }
+}
+
+/// The color value of CSS custom property declarations like `--primary: red`, the node is the value.
+///
+/// Only the values that are a whole color are matched, other values like `--shadow: 0 0 4px #fff`
+/// are left to the other patterns.
+pub struct CustomPropertyPattern {
+    /// Allow hex values like `--primary: #fff`.
+    pub hex: bool,
+}
+
+impl ColorPattern for CustomPropertyPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        if !text.starts_with("--") {
+            return None;
+        }
+        let (name, offset, value) = custom_property_declarations(text).into_iter().next()?;
+        if !text.starts_with(name) || value.is_empty() || !self.hex && value.contains('#') {
+            return None;
+        }
+        let color = try_parse_color(value).ok()?;
+        Some(ColorNode::new(value, color, line, col + offset))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "custom-property",
+            "--name: <color>",
+            "--primary: #ff5722",
+        )]
+    }
+}
+
+/// Map the custom property names (with `--`) to the color nodes of their values,
+/// the later declarations override the earlier ones.
+pub(crate) fn custom_property_colors(
+    text: &str,
+    nodes: &[ColorNode],
+) -> HashMap<String, ColorNode> {
+    let mut colors = HashMap::new();
+    for (ix, line) in text.lines().enumerate() {
+        for (name, offset, value) in custom_property_declarations(line) {
+            let node = nodes.iter().find(|node| {
+                node.range.line as usize == ix + 1
+                    && node.range.col as usize == offset + 1
+                    && node.matched == value
+            });
+            if let Some(node) = node {
+                colors.insert(name.to_string(), node.clone());
+            }
+        }
+    }
+    colors
+}
+
+/// The Tailwind CSS utilities that accept a color.
+const TAILWIND_COLOR_UTILITIES: &[&str] = &[
+    "bg",
+    "text",
+    "border",
+    "border-x",
+    "border-y",
+    "border-s",
+    "border-e",
+    "border-t",
+    "border-r",
+    "border-b",
+    "border-l",
+    "outline",
+    "ring",
+    "ring-offset",
+    "divide",
+    "shadow",
+    "fill",
+    "stroke",
+    "accent",
+    "caret",
+    "decoration",
+    "placeholder",
+    "from",
+    "via",
+    "to",
+];
+
+/// Tailwind CSS arbitrary color values like `bg-[#ff0000]` and `text-[rgb(255,0,0)]`,
+/// the node is the `[…]` part.
+pub struct TailwindPattern {
+    /// Allow hex values like `bg-[#fff]`.
+    pub hex: bool,
+}
+
+impl ColorPattern for TailwindPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        // Only look in the current class name, to avoid scanning the rest of the line at each word
+        let token_end = text
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '`'))
+            .unwrap_or(text.len());
+        let (prefix, rest) = text[..token_end].split_once("-[")?;
+        if !TAILWIND_COLOR_UTILITIES.contains(&prefix) {
+            return None;
+        }
+
+        let end = rest.find(']')?;
+        // Tailwind use `_` for spaces in arbitrary values
+        let value = rest[..end].replace('_', " ");
+        if !self.hex && value.contains('#') {
+            return None;
+        }
+        let color = try_parse_color(&value).ok()?;
+        let bracket = prefix.len() + 1;
+        Some(ColorNode::new(
+            &text[bracket..bracket + end + 2],
+            color,
+            line,
+            col + bracket,
+        ))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "tailwind-arbitrary",
+            "<utility>-[<color>]",
+            "bg-[#ff5722]",
+        )]
+    }
+}
+
+/// The shades of the Tailwind CSS default color palette.
+const TAILWIND_SHADES: [&str; 11] = [
+    "50", "100", "200", "300", "400", "500", "600", "700", "800", "900", "950",
+];
+
+/// The Tailwind CSS default color palette, the colors are `0xRRGGBB` for each of `TAILWIND_SHADES`.
+#[rustfmt::skip]
+const TAILWIND_PALETTE: &[(&str, [u32; 11])] = &[
+    ("slate", [0xf8fafc, 0xf1f5f9, 0xe2e8f0, 0xcbd5e1, 0x94a3b8, 0x64748b, 0x475569, 0x334155, 0x1e293b, 0x0f172a, 0x020617]),
+    ("gray", [0xf9fafb, 0xf3f4f6, 0xe5e7eb, 0xd1d5db, 0x9ca3af, 0x6b7280, 0x4b5563, 0x374151, 0x1f2937, 0x111827, 0x030712]),
+    ("zinc", [0xfafafa, 0xf4f4f5, 0xe4e4e7, 0xd4d4d8, 0xa1a1aa, 0x71717a, 0x52525b, 0x3f3f46, 0x27272a, 0x18181b, 0x09090b]),
+    ("neutral", [0xfafafa, 0xf5f5f5, 0xe5e5e5, 0xd4d4d4, 0xa3a3a3, 0x737373, 0x525252, 0x404040, 0x262626, 0x171717, 0x0a0a0a]),
+    ("stone", [0xfafaf9, 0xf5f5f4, 0xe7e5e4, 0xd6d3d1, 0xa8a29e, 0x78716c, 0x57534e, 0x44403c, 0x292524, 0x1c1917, 0x0c0a09]),
+    ("red", [0xfef2f2, 0xfee2e2, 0xfecaca, 0xfca5a5, 0xf87171, 0xef4444, 0xdc2626, 0xb91c1c, 0x991b1b, 0x7f1d1d, 0x450a0a]),
+    ("orange", [0xfff7ed, 0xffedd5, 0xfed7aa, 0xfdba74, 0xfb923c, 0xf97316, 0xea580c, 0xc2410c, 0x9a3412, 0x7c2d12, 0x431407]),
+    ("amber", [0xfffbeb, 0xfef3c7, 0xfde68a, 0xfcd34d, 0xfbbf24, 0xf59e0b, 0xd97706, 0xb45309, 0x92400e, 0x78350f, 0x451a03]),
+    ("yellow", [0xfefce8, 0xfef9c3, 0xfef08a, 0xfde047, 0xfacc15, 0xeab308, 0xca8a04, 0xa16207, 0x854d0e, 0x713f12, 0x422006]),
+    ("lime", [0xf7fee7, 0xecfccb, 0xd9f99d, 0xbef264, 0xa3e635, 0x84cc16, 0x65a30d, 0x4d7c0f, 0x3f6212, 0x365314, 0x1a2e05]),
+    ("green", [0xf0fdf4, 0xdcfce7, 0xbbf7d0, 0x86efac, 0x4ade80, 0x22c55e, 0x16a34a, 0x15803d, 0x166534, 0x14532d, 0x052e16]),
+    ("emerald", [0xecfdf5, 0xd1fae5, 0xa7f3d0, 0x6ee7b7, 0x34d399, 0x10b981, 0x059669, 0x047857, 0x065f46, 0x064e3b, 0x022c22]),
+    ("teal", [0xf0fdfa, 0xccfbf1, 0x99f6e4, 0x5eead4, 0x2dd4bf, 0x14b8a6, 0x0d9488, 0x0f766e, 0x115e59, 0x134e4a, 0x042f2e]),
+    ("cyan", [0xecfeff, 0xcffafe, 0xa5f3fc, 0x67e8f9, 0x22d3ee, 0x06b6d4, 0x0891b2, 0x0e7490, 0x155e75, 0x164e63, 0x083344]),
+    ("sky", [0xf0f9ff, 0xe0f2fe, 0xbae6fd, 0x7dd3fc, 0x38bdf8, 0x0ea5e9, 0x0284c7, 0x0369a1, 0x075985, 0x0c4a6e, 0x082f49]),
+    ("blue", [0xeff6ff, 0xdbeafe, 0xbfdbfe, 0x93c5fd, 0x60a5fa, 0x3b82f6, 0x2563eb, 0x1d4ed8, 0x1e40af, 0x1e3a8a, 0x172554]),
+    ("indigo", [0xeef2ff, 0xe0e7ff, 0xc7d2fe, 0xa5b4fc, 0x818cf8, 0x6366f1, 0x4f46e5, 0x4338ca, 0x3730a3, 0x312e81, 0x1e1b4b]),
+    ("violet", [0xf5f3ff, 0xede9fe, 0xddd6fe, 0xc4b5fd, 0xa78bfa, 0x8b5cf6, 0x7c3aed, 0x6d28d9, 0x5b21b6, 0x4c1d95, 0x2e1065]),
+    ("purple", [0xfaf5ff, 0xf3e8ff, 0xe9d5ff, 0xd8b4fe, 0xc084fc, 0xa855f7, 0x9333ea, 0x7e22ce, 0x6b21a8, 0x581c87, 0x3b0764]),
+    ("fuchsia", [0xfdf4ff, 0xfae8ff, 0xf5d0fe, 0xf0abfc, 0xe879f9, 0xd946ef, 0xc026d3, 0xa21caf, 0x86198f, 0x701a75, 0x4a044e]),
+    ("pink", [0xfdf2f8, 0xfce7f3, 0xfbcfe8, 0xf9a8d4, 0xf472b6, 0xec4899, 0xdb2777, 0xbe185d, 0x9d174d, 0x831843, 0x500724]),
+    ("rose", [0xfff1f2, 0xffe4e6, 0xfecdd3, 0xfda4af, 0xfb7185, 0xf43f5e, 0xe11d48, 0xbe123c, 0x9f1239, 0x881337, 0x4c0519]),
+];
+
+/// The color of a Tailwind CSS theme path like `colors.blue.500`, `colors.white`.
+pub(crate) fn tailwind_theme_color(path: &str) -> Option<Color> {
+    let rest = path.strip_prefix("colors.")?;
+    let rgb = match rest.split_once('.') {
+        None => match rest {
+            "black" => 0x000000,
+            "white" => 0xffffff,
+            "transparent" => return Some(Color::new(0., 0., 0., 0.)),
+            _ => return None,
+        },
+        Some((name, shade)) => {
+            let (_, colors) = TAILWIND_PALETTE.iter().find(|(n, _)| *n == name)?;
+            let ix = TAILWIND_SHADES.iter().position(|s| *s == shade)?;
+            colors[ix]
+        }
+    };
+    Some(Color::from_rgba8(
+        (rgb >> 16) as u8,
+        (rgb >> 8) as u8,
+        rgb as u8,
+        255,
+    ))
+}
+
+/// The Tailwind CSS theme path like `colors.blue.500` of the color, if it's in the default palette.
+pub(crate) fn tailwind_theme_path(color: &Color) -> Option<String> {
+    let [r, g, b, a] = color.to_rgba8();
+    if a != 255 {
+        return None;
+    }
+    let rgb = u32::from_be_bytes([0, r, g, b]);
+    TAILWIND_PALETTE.iter().find_map(|(name, colors)| {
+        let ix = colors.iter().position(|c| *c == rgb)?;
+        Some(format!("colors.{name}.{}", TAILWIND_SHADES[ix]))
+    })
+}
+
+/// Tailwind CSS `theme()` function with a default palette color like `theme('colors.blue.500')`,
+/// with an optional alpha modifier like `theme('colors.blue.500 / 50%')`.
+pub struct TailwindThemePattern;
+
+impl ColorPattern for TailwindThemePattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let call = take_function(text, &["theme"])?;
+        let arg = call["theme(".len()..call.len() - 1].trim();
+        let arg = ['\'', '"']
+            .iter()
+            .find_map(|q| arg.strip_prefix(*q)?.strip_suffix(*q))
+            .unwrap_or(arg);
+        let (path, alpha) = match arg.split_once('/') {
+            Some((path, alpha)) => (path.trim(), Some(alpha.trim())),
+            None => (arg, None),
+        };
+        let mut color = tailwind_theme_color(path)?;
+        if let Some(alpha) = alpha {
+            let alpha = match alpha.strip_suffix('%') {
+                Some(percent) => percent.parse::<f32>().ok()? / 100.,
+                None => alpha.parse().ok()?,
+            };
+            if !(0.0..=1.0).contains(&alpha) {
+                return None;
+            }
+            color.a = alpha;
+        }
+        Some(ColorNode::new(call, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "tailwind-theme",
+            "theme('colors.<name>.<shade> / <alpha>')",
+            "theme('colors.blue.500 / 50%')",
+        )]
+    }
+}
+
+/// Hex color with `0x` prefix like `0xRRGGBB` and `0xAARRGGBB`.
+pub struct Hex0xPattern {
+    /// Allow `_` digit separators, e.g.: `0xFF_00_FF`.
+    pub digit_separators: bool,
+}
+
+impl ColorPattern for Hex0xPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let hex = take_0x_hex(text, self.digit_separators)?;
+        let color = parse_0x_color(&strip_digit_separators(hex)).ok()?;
+        Some(ColorNode::new(hex, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "hex0x",
+            "0xRRGGBB, 0xAARRGGBB",
+            "0xFFFF5722",
+        )]
+    }
+}
+
+/// The gpui colors like `rgb(1., 0.5, 0.)` and `hsla(0.5, 1., 0.5, 1.)` that values are 0..1.
+pub struct GpuiPattern;
+
+impl ColorPattern for GpuiPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["rgb", "rgba", "hsl", "hsla"])?;
+        let color = try_parse_gpui_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new(
+                "gpui-rgb",
+                "rgb(r, g, b), rgba(r, g, b, a) with 0..1",
+                "rgba(1., 0.34, 0.13, 1.)",
+            ),
+            ColorFormat::new(
+                "gpui-hsl",
+                "hsl(h, s, l), hsla(h, s, l, a) with 0..1",
+                "hsla(0.04, 1., 0.57, 1.)",
+            ),
+        ]
+    }
+}
+
+/// CSS `rgb()` and `rgba()` colors.
+pub struct RgbPattern;
+
+impl ColorPattern for RgbPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["rgb", "rgba"])?;
+        let color = parse_css_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new("rgb", "rgb(r, g, b)", "rgb(255 87 34)"),
+            ColorFormat::new("rgba", "rgba(r, g, b, a)", "rgba(255, 87, 34, 0.5)"),
+        ]
+    }
+}
+
+/// CSS `hsl()` and `hsla()` colors.
+pub struct HslPattern;
+
+impl ColorPattern for HslPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["hsl", "hsla"])?;
+        let color = parse_css_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new("hsl", "hsl(h, s, l)", "hsl(14 100% 57%)"),
+            ColorFormat::new("hsla", "hsla(h, s, l, a)", "hsla(14, 100%, 57%, 0.5)"),
+        ]
+    }
+}
+
+/// Other CSS color functions.
+///
+/// Ref https://github.com/mazznoer/csscolorparser-rs
+pub struct CssFunctionPattern;
+
+impl ColorPattern for CssFunctionPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(
+            text,
+            &[
+                "hwb", "hwba", "hsv", "oklab", "oklch", "lab", "lch", "color",
+            ],
+        )?;
+        let color = parse_css_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new("hwb", "hwb(h w b)", "hwb(14 13% 0%)"),
+            ColorFormat::new("hsv", "hsv(h, s, v)", "hsv(14, 87%, 100%)"),
+            ColorFormat::new("oklab", "oklab(l a b)", "oklab(0.68 0.17 0.12)"),
+            ColorFormat::new("oklch", "oklch(l c h)", "oklch(0.68 0.21 36)"),
+            ColorFormat::new("lab", "lab(l a b)", "lab(61 59 60)"),
+            ColorFormat::new("lch", "lch(l c h)", "lch(61 84 45)"),
+            ColorFormat::new(
+                "color",
+                "color(<space> r g b)",
+                "color(display-p3 0.93 0.4 0.2)",
+            ),
+        ]
+    }
+}
+
+/// CSS `color-mix(in srgb, #ff0000 50%, #0000ff)`.
+pub struct ColorMixPattern {
+    /// Allow hex colors to be mixed.
+    pub hex: bool,
+}
+
+impl ColorPattern for ColorMixPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_nested_function(text, "color-mix")?;
+        if !self.hex && matched.contains('#') {
+            return None;
+        }
+        let color = try_parse_color_mix(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "color-mix",
+            "color-mix(in <space>, <color> <p>, <color>)",
+            "color-mix(in srgb, #ff5722 50%, blue)",
+        )]
+    }
+}
+
+/// SwiftUI `Color(hue:saturation:brightness:)` and system colors like `Color(.systemBlue)`.
+pub struct SwiftUiPattern;
+
+impl ColorPattern for SwiftUiPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["Color"])?;
+        let color = try_parse_swiftui_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new(
+                "swiftui-hsb",
+                "Color(hue:saturation:brightness:opacity:)",
+                "Color(hue: 0.04, saturation: 0.87, brightness: 1.0)",
+            ),
+            ColorFormat::new(
+                "swiftui-system",
+                "Color(.<systemColor>)",
+                "Color(.systemOrange)",
+            ),
+        ]
+    }
+}
+
+/// UIKit `UIColor(red:green:blue:alpha:)` and `UIColor(white:alpha:)`.
+pub struct UiColorPattern;
+
+impl ColorPattern for UiColorPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["UIColor"])?;
+        let color = try_parse_uicolor(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new(
+                "uicolor",
+                "UIColor(red:green:blue:alpha:)",
+                "UIColor(red: 1.0, green: 0.34, blue: 0.13, alpha: 1.0)",
+            ),
+            ColorFormat::new(
+                "uicolor-white",
+                "UIColor(white:alpha:)",
+                "UIColor(white: 0.5, alpha: 1.0)",
+            ),
+        ]
+    }
+}
+
+/// Go `color.RGBA{…}` and `color.NRGBA{…}` struct literals.
+pub struct GoColorPattern;
+
+impl ColorPattern for GoColorPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        if !(text.starts_with("color.RGBA{") || text.starts_with("color.NRGBA{")) {
+            return None;
+        }
+        let matched = &text[..=text.find('}')?];
+        let color = try_parse_go_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new(
+                "go-rgba",
+                "color.RGBA{R, G, B, A}",
+                "color.RGBA{R: 255, G: 87, B: 34, A: 255}",
+            ),
+            ColorFormat::new(
+                "go-nrgba",
+                "color.NRGBA{R, G, B, A}",
+                "color.NRGBA{R: 255, G: 87, B: 34, A: 128}",
+            ),
+        ]
+    }
+}
+
+/// The Tkinter options that accept a color.
+const TKINTER_COLOR_OPTIONS: &[&str] = &["bg", "fg", "background", "foreground", "fill", "outline"];
+
+/// Python Tkinter color options in `config(…)`, `configure(…)` and widget constructors like
+/// `Label(root, bg="gray75", fg="#ff0000")`, the node is the string value without the quotes.
+pub struct TkinterPattern;
+
+impl ColorPattern for TkinterPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let (option, rest) = text.split_once('=')?;
+        if !TKINTER_COLOR_OPTIONS.contains(&option) {
+            return None;
+        }
+        let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let value = &rest[1..1 + rest[1..].find(quote)?];
+        let color = try_parse_tk_color(value).ok()?;
+        Some(ColorNode::new(value, color, line, col + option.len() + 2))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "tkinter",
+            "<option>=\"<color>\"",
+            "bg=\"gray75\"",
+        )]
+    }
+}
+
+/// Nix attribute set of 0..1 channels like `{ r = 1.0; g = 0.5; b = 0.0; }` in NixOS
+/// and Home Manager themes.
+pub struct NixAttrsetPattern;
+
+impl ColorPattern for NixAttrsetPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        if !text.starts_with('{') {
+            return None;
+        }
+        let matched = &text[..=text.find('}')?];
+        let color = try_parse_nix_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "nix-attrset",
+            "{ r = <r>; g = <g>; b = <b>; } with 0..1",
+            "{ r = 1.0; g = 0.34; b = 0.13; }",
+        )]
+    }
+}
+
+/// Godot color in GDScript like `Color(0.5, 0.2, 0.8)`, `Color("#ff5722")` and `Color.RED`.
+pub struct GodotPattern;
+
+impl ColorPattern for GodotPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = match text.strip_prefix("Color.") {
+            Some(rest) => {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                &text[.."Color.".len() + len]
+            }
+            None => take_function(text, &["Color"])?,
+        };
+        let color = try_parse_godot_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new(
+                "godot",
+                "Color(r, g, b, a) with 0..1",
+                "Color(1.0, 0.34, 0.13)",
+            ),
+            ColorFormat::new("godot-string", "Color(\"<color>\")", "Color(\"#ff5722\")"),
+            ColorFormat::new("godot-constant", "Color.<NAME>", "Color.ORANGE_RED"),
+        ]
+    }
+}
+
+/// Haskell `colour` package color like `sRGB24 255 128 0`, `sRGB 0.5 0.2 0.8` and
+/// `Colour.lightcoral`.
+pub struct HaskellColourPattern;
+
+impl ColorPattern for HaskellColourPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = match text.strip_prefix("Colour.") {
+            Some(rest) => {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                &text[.."Colour.".len() + len]
+            }
+            None => {
+                let function = ["sRGB24 ", "sRGB "]
+                    .into_iter()
+                    .find(|function| text.starts_with(function))?;
+                // The 3 channels separated by spaces, like `sRGB24 255 128 0`
+                let mut end = function.len();
+                for _ in 0..3 {
+                    let start = end + text[end..].len() - text[end..].trim_start().len();
+                    let len = text[start..]
+                        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                        .unwrap_or(text.len() - start);
+                    if len == 0 || start == end && end > function.len() {
+                        return None;
+                    }
+                    end = start + len;
+                }
+                &text[..end]
+            }
+        };
+        let color = try_parse_haskell_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new("haskell-srgb24", "sRGB24 r g b", "sRGB24 255 87 34"),
+            ColorFormat::new("haskell-srgb", "sRGB r g b with 0..1", "sRGB 1.0 0.34 0.13"),
+            ColorFormat::new("haskell-named", "Colour.<name>", "Colour.orangered"),
+        ]
+    }
+}
+
+/// Flutter color like `Color(0xFF112233)`, `Color.fromARGB(255, 17, 34, 51)`
+/// and `Color.fromRGBO(17, 34, 51, 1.0)`.
+pub struct FlutterPattern;
+
+impl ColorPattern for FlutterPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["Color", "Color.fromARGB", "Color.fromRGBO"])?;
+        let color = try_parse_flutter_color(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new("flutter", "Color(0xAARRGGBB)", "Color(0xFFFF5722)"),
+            ColorFormat::new(
+                "flutter-argb",
+                "Color.fromARGB(a, r, g, b)",
+                "Color.fromARGB(255, 255, 87, 34)",
+            ),
+            ColorFormat::new(
+                "flutter-rgbo",
+                "Color.fromRGBO(r, g, b, opacity)",
+                "Color.fromRGBO(255, 87, 34, 1.0)",
+            ),
+        ]
+    }
+}
+
+/// Three.js color like `new THREE.Color(0xff5722)` and `new THREE.Color(1.0, 0.5, 0.0)`,
+/// the node is the `THREE.Color(…)` part.
+pub struct ThreeColorPattern;
+
+impl ColorPattern for ThreeColorPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["THREE.Color"])?;
+        let args = matched["THREE.Color(".len()..matched.len() - 1]
+            .split(',')
+            .map(str::trim)
+            .collect::<Vec<_>>();
+
+        let color = match args.as_slice() {
+            [hex] if hex.len() == 8 => parse_0x_color(hex).ok()?,
+            [r, g, b] => {
+                let [r, g, b] =
+                    [r, g, b].map(|v| v.parse::<f32>().ok().filter(|v| (0.0..=1.0).contains(v)));
+                Color::new(r?, g?, b?, 1.)
+            }
+            _ => return None,
+        };
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new("three", "THREE.Color(0xRRGGBB)", "THREE.Color(0xff5722)"),
+            ColorFormat::new(
+                "three-rgb",
+                "THREE.Color(r, g, b) with 0..1",
+                "THREE.Color(1.0, 0.34, 0.13)",
+            ),
+        ]
+    }
+}
+
+/// Roblox Luau `Color3.fromRGB(255, 128, 0)` with 0..255 integers and `Color3.new(1, 0.5, 0)`
+/// with 0..1 floats.
+pub struct RobloxColor3Pattern;
+
+impl ColorPattern for RobloxColor3Pattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let matched = take_function(text, &["Color3.fromRGB", "Color3.new"])?;
+        let (name, args) = matched.split_once('(')?;
+        let args = args[..args.len() - 1]
+            .split(',')
+            .map(|v| v.trim().parse::<f32>().ok())
+            .collect::<Vec<_>>();
+        let [Some(r), Some(g), Some(b)] = args[..] else {
+            return None;
+        };
+
+        let max = if name == "Color3.fromRGB" { 255. } else { 1. };
+        if ![r, g, b].iter().all(|v| (0.0..=max).contains(v)) {
+            return None;
+        }
+        let color = Color::new(r / max, g / max, b / max, 1.);
+        Some(ColorNode::new(matched, color, line, col))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![
+            ColorFormat::new(
+                "roblox-rgb",
+                "Color3.fromRGB(r, g, b)",
+                "Color3.fromRGB(255, 87, 34)",
+            ),
+            ColorFormat::new(
+                "roblox",
+                "Color3.new(r, g, b) with 0..1",
+                "Color3.new(1, 0.34, 0.13)",
+            ),
+        ]
+    }
+}
+
+/// GLSL uniform with a default color value like `uniform vec4 uColor = vec4(0.5, 0.2, 0.8, 1.0);`,
+/// the node is the `vec4(…)` part.
+pub struct GlslUniformPattern;
+
+impl ColorPattern for GlslUniformPattern {
+    fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+        let rest = text.strip_prefix("uniform")?;
+        let (declaration, value) = rest.split_once('=')?;
+        if !declaration.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        let mut words = declaration
+            .split_whitespace()
+            .filter(|word| !matches!(*word, "lowp" | "mediump" | "highp"));
+        let (Some(ty @ ("vec3" | "vec4")), Some(_), None) =
+            (words.next(), words.next(), words.next())
+        else {
+            return None;
+        };
+
+        let start = text.len() - value.trim_start().len();
+        let matched = take_function(&text[start..], &[ty])?;
+        let color = try_parse_glsl_vec(matched).ok()?;
+        Some(ColorNode::new(matched, color, line, col + start))
+    }
+
+    fn formats(&self) -> Vec<ColorFormat> {
+        vec![ColorFormat::new(
+            "glsl-uniform",
+            "uniform vec3|vec4 <name> = vec4(r, g, b, a)",
+            "uniform vec4 uColor = vec4(1.0, 0.34, 0.13, 1.0);",
+        )]
+    }
+}
+
+/// Parse GLSL `vec3(r, g, b)` or `vec4(r, g, b, a)` with values in 0..1.
+fn try_parse_glsl_vec(s: &str) -> Result<Color, ParseColorError> {
+    let (len, args) = if let Some(args) = s.strip_prefix("vec3(") {
+        (3, args)
+    } else if let Some(args) = s.strip_prefix("vec4(") {
+        (4, args)
+    } else {
+        return Err(ParseColorError::InvalidFunction);
+    };
+
+    let values = args
+        .strip_suffix(')')
+        .ok_or(ParseColorError::InvalidFunction)?
+        .split(',')
+        .map(|v| {
+            v.trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|v| (0.0..=1.0).contains(v))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(ParseColorError::InvalidFunction)?;
+
+    match values[..] {
+        [r, g, b] if len == 3 => Ok(Color::new(r, g, b, 1.0)),
+        [r, g, b, a] if len == 4 => Ok(Color::new(r, g, b, a)),
+        _ => Err(ParseColorError::InvalidFunction),
+    }
+}
+
+pub(super) fn parse(text: &str) -> Vec<ColorNode> {
+    parse_document(text, "", &Config::default())
+}
+
+/// Parse colors in the text with the built-in patterns and the default config.
+///
+/// ```
+/// let nodes = color_lsp::parser::parse_str("a { color: #ff0000; background: rgb(0 0 255); }");
+/// assert_eq!(nodes[0].matched, "#ff0000");
+/// assert_eq!(nodes[1].color.to_css_hex(), "#0000ff");
+/// ```
+pub fn parse_str(text: &str) -> Vec<ColorNode> {
+    parse(text)
+}
+
+/// Read the file and parse colors in it, like [`parse_str`].
+///
+/// ```no_run
+/// for node in color_lsp::parser::parse_file("styles.css".as_ref())? {
+///     println!("{}:{} {}", node.range.line, node.range.col, node.color.to_css_hex());
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn parse_file(path: &std::path::Path) -> Result<Vec<ColorNode>, std::io::Error> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(parse_str(&text))
+}
+
+/// Parse colors in the value of a CSS declaration, the nodes are located in the `value` as
+/// the first line.
+///
+/// Only the whole tokens of the value are colors, like the color of the `border` shorthand and
+/// the colors of the `box-shadow` layers, not the `red` in `url(red.png)`. The gradients are only
+/// parsed for the `background`, `border-image` and `mask` properties.
+///
+/// ```
+/// use color_lsp::parser::parse_css_property_value;
+///
+/// let nodes = parse_css_property_value("box-shadow", "0 0 4px #000, inset 0 1px red");
+/// assert_eq!(nodes[0].matched, "#000");
+/// assert_eq!(nodes[1].matched, "red");
+/// assert!(parse_css_property_value("font-family", "red-hat, sans-serif").is_empty());
+/// ```
+pub fn parse_css_property_value(property: &str, value: &str) -> Vec<ColorNode> {
+    let property = property.trim().to_ascii_lowercase();
+    let has_gradients = ["background", "border-image", "mask"]
+        .iter()
+        .any(|name| property.starts_with(name));
+    let registry = ColorPatternRegistry::builtin("css", &Config::default());
+    let offset_of = |part: &str| part.as_ptr() as usize - value.as_ptr() as usize;
+
+    split_top_level(value, ',')
+        .into_iter()
+        .flat_map(|layer| split_top_level(layer, ' '))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+        .flat_map(|token| {
+            let col = offset_of(token) + 1;
+            if take_gradient(token) == Some(token) {
+                return if has_gradients {
+                    parse_gradient(token, 1, col, &registry)
+                } else {
+                    vec![]
+                };
+            }
+            let color = parse_css_color(token).or_else(|_| try_parse_color_mix(token));
+            color
+                .map(|color| ColorNode::new(token, color, 1, col))
+                .into_iter()
+                .collect()
+        })
+        .map(|mut node| {
+            node.locate_utf16(value);
+            node
+        })
+        .collect()
+}
+
+/// Parse colors in a document, with the `language_id` of the document to enable language specific formats.
+pub(super) fn parse_document(text: &str, language_id: &str, config: &Config) -> Vec<ColorNode> {
+    let mut nodes = parse_with_registry(
+        text,
+        &ColorPatternRegistry::for_document(language_id, text, config),
+    );
+    resolve_var_references(text, &mut nodes);
+    if is_css_language(language_id) {
+        resolve_css_module_values(text, &mut nodes);
+        tag_css_layers(text, &mut nodes);
+    }
+    nodes
+}
+
+/// Return true if the language has CSS syntax, where custom properties can be declared.
+pub(crate) fn is_css_language(language_id: &str) -> bool {
+    matches!(language_id, "css" | "scss" | "less" | "postcss")
+}
+
+/// Parse colors in the text by the patterns of the `registry`.
+///
+/// The patterns are only tried at the start of words, and the text of a matched node is skipped.
+pub fn parse_with_registry(text: &str, registry: &ColorPatternRegistry) -> Vec<ColorNode> {
+    let mut nodes = Vec::new();
+
+    for (ix, line_text) in text.lines().enumerate() {
+        let line_start = nodes.len();
+        let mut offset = 0;
+        while let Some(c) = line_text[offset..].chars().next() {
+            if is_word_start(line_text, offset) {
+                if let Some(gradient) = take_gradient(&line_text[offset..]) {
+                    nodes.extend(parse_gradient(gradient, ix + 1, offset + 1, registry));
+                    offset += gradient.len();
+                    continue;
+                }
+
+                if let Some(node) = registry.try_match(&line_text[offset..], ix + 1, offset + 1) {
+                    // The node may start after the `offset`, e.g.: `[#fff]` in `bg-[#fff]`
+                    let end = (node.range.col - 1 + node.range.len) as usize;
+                    offset = end.max(offset + 1);
+                    nodes.push(node);
+                    continue;
+                }
+            }
+
+            offset += c.len_utf8();
+        }
+
+        if !line_text.is_ascii() {
+            for node in &mut nodes[line_start..] {
+                node.locate_utf16(line_text);
+            }
+        }
+    }
+
+    nodes
+}
+
+/// Take the CSS gradient function like `linear-gradient(…)` from the start of `text`.
+pub(crate) fn take_gradient(text: &str) -> Option<&str> {
+    [
+        "linear-gradient",
+        "radial-gradient",
+        "conic-gradient",
+        "repeating-linear-gradient",
+        "repeating-radial-gradient",
+        "repeating-conic-gradient",
+    ]
+    .iter()
+    .find_map(|name| take_nested_function(text, name))
+}
+
+/// Parse the color stops of the CSS gradient function `s` located at the `line` and `col_offset` (1-based),
+/// e.g. `linear-gradient(90deg, #ff0000 0%, blue 100%)`.
+///
+/// The stops are matched by the patterns of the `registry`, and the color keywords like `blue`.
+pub(crate) fn parse_gradient(
+    s: &str,
+    line: usize,
+    col_offset: usize,
+    registry: &ColorPatternRegistry,
+) -> Vec<ColorNode> {
+    let Some(args) = s
+        .split_once('(')
+        .and_then(|(_, args)| args.strip_suffix(')'))
+    else {
+        return vec![];
+    };
+
+    let offset_of = |part: &str| part.as_ptr() as usize - s.as_ptr() as usize;
+    split_top_level(args, ',')
+        .into_iter()
+        .flat_map(|stop| split_top_level(stop, ' '))
+        .filter(|token| !token.is_empty())
+        // The positions like `0%` and `10px` are not colors
+        .filter(|token| !token.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '-'))
+        .filter_map(|token| {
+            let col = col_offset + offset_of(token);
+            if let Some(node) = registry.try_match(token, line, col) {
+                return (node.matched == token).then_some(node);
+            }
+            // The keywords like `blue` and `transparent` are not matched by the patterns
+            if !token.chars().all(|c| c.is_ascii_alphabetic()) {
+                return None;
+            }
+            match_color(token, line - 1, col - 1)
+        })
+        .collect()
+}
+
+/// Tag the nodes inside `@layer <name> { … }` blocks of the CSS text with the layer name.
+pub(crate) fn tag_css_layers(text: &str, nodes: &mut [ColorNode]) {
+    if !text.contains("@layer") {
+        return;
+    }
+
+    let layer_name = |stack: &[Option<&str>]| {
+        let names = stack.iter().flatten().copied().collect::<Vec<_>>();
+        (!names.is_empty()).then(|| names.join("."))
+    };
+
+    // The layer names of the open blocks, `None` for the blocks that are not layers.
+    let mut stack: Vec<Option<&str>> = vec![];
+    let mut pending = None;
+    let mut nodes = nodes.iter_mut().peekable();
+
+    for (ix, line) in text.lines().enumerate() {
+        for (offset, c) in line.char_indices() {
+            let position = (ix as u32 + 1, offset as u32 + 1);
+            while let Some(node) =
+                nodes.next_if(|node| (node.range.line, node.range.col) <= position)
+            {
+                node.layer = layer_name(&stack);
+            }
+
+            match c {
+                '@' => {
+                    let Some(rest) = line[offset..].strip_prefix("@layer") else {
+                        continue;
+                    };
+                    // `@layer a, b;` statement has no block, and the `{` may be on the next line
+                    let end = rest.find(['{', ';']).unwrap_or(rest.len());
+                    if !rest[end..].starts_with(';') {
+                        pending = Some(rest[..end].trim()).filter(|name| !name.is_empty());
+                    }
+                }
+                ';' => pending = None,
+                '{' => stack.push(pending.take()),
+                '}' => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for node in nodes {
+        node.layer = layer_name(&stack);
+    }
+}
+
+/// Return the CSS property name of the declaration that contains the byte `offset` of `line`,
+/// e.g. `filter` for a color in `filter: drop-shadow(0 0 2px #000);`.
+pub(crate) fn css_property_name(line: &str, offset: usize) -> Option<&str> {
+    let before = line.get(..offset)?;
+    let declaration = &before[before.rfind([';', '{']).map_or(0, |ix| ix + 1)..];
+    let (name, _) = declaration.split_once(':')?;
+    let name = name.trim();
+
+    (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+        .then_some(name)
+}
+
+/// Parse the color token at `col` (1-based) of `line`.
+///
+/// The `context_lines` are the lines before `line`, used to resolve `var(--name)` references
+/// to the custom properties declared in them. The returned node is located at the line after them.
+pub(super) fn parse_with_context(
+    line: &str,
+    col: usize,
+    context_lines: &[&str],
+) -> Option<ColorNode> {
+    let line_no = context_lines.len() + 1;
+
+    if let Some(mut node) = parse(line)
+        .into_iter()
+        .find(|node| node.range.contains_col(col as u32))
+    {
+        node.range.line = line_no as u32;
+        return Some(node);
+    }
+
+    let (offset, reference, name) =
+        var_references(line)
+            .into_iter()
+            .find(|(offset, reference, _)| {
+                TextRange::new(1, *offset as u32 + 1, reference.len() as u32)
+                    .contains_col(col as u32)
+            })?;
+    let color = context_lines.iter().rev().find_map(|context_line| {
+        custom_property_declarations(context_line)
+            .into_iter()
+            .rev()
+            .find(|(decl_name, _, _)| *decl_name == name)
+            .and_then(|(_, _, value)| try_parse_color(value).ok())
+    })?;
+
+    let mut node = ColorNode::new(reference, color, line_no, offset + 1);
+    node.locate_utf16(line);
+    Some(node)
+}
+
+/// Find all CSS custom property declarations like `--name: value` in the line.
+///
+/// Returns the name (with `--`), the byte offset of the value and the value.
+pub(crate) fn custom_property_declarations(line: &str) -> Vec<(&str, usize, &str)> {
+    let mut declarations = vec![];
+    let mut offset = 0;
+
+    while let Some(ix) = line[offset..].find("--") {
+        let start = offset + ix;
+        let name_len = line[start + 2..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(line.len() - start - 2);
+        let name_end = start + 2 + name_len;
+        offset = name_end.max(start + 2);
+
+        if name_len == 0 || !is_word_start(line, start) {
+            continue;
+        }
+        let Some(rest) = line[name_end..].trim_start().strip_prefix(':') else {
+            continue;
+        };
+
+        let value_end = rest.find([';', '}']).unwrap_or(rest.len());
+        let value = rest[..value_end].trim();
+        let value_offset = line.len() - rest.len() + (rest.len() - rest.trim_start().len());
+        declarations.push((&line[start..name_end], value_offset, value));
+        offset = line.len() - rest.len() + value_end;
+    }
+
+    declarations
+}
+
+/// Find all CSS custom property names like `--name` in the line, both in the declarations and
+/// the `var(--name)` references.
+///
+/// Returns the byte offset and the name (with `--`).
+pub(crate) fn custom_property_names(line: &str) -> Vec<(usize, &str)> {
+    let mut names = vec![];
+    let mut offset = 0;
+
+    while let Some(ix) = line[offset..].find("--") {
+        let start = offset + ix;
+        let name_len = line[start + 2..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(line.len() - start - 2);
+        offset = start + 2 + name_len;
+
+        if name_len > 0 && is_word_start(line, start) {
+            names.push((start, &line[start..offset]));
+        }
+    }
+
+    names
+}
+
+/// Find all `var(--name)` references in the line.
+///
+/// Returns the byte offset, the whole reference and the referenced name.
+fn var_references(line: &str) -> Vec<(usize, &str, &str)> {
+    let mut references = vec![];
+    let mut offset = 0;
+
+    while let Some(ix) = line[offset..].find("var(") {
+        let start = offset + ix;
+        offset = start + 4;
+
+        if !is_word_start(line, start) {
+            continue;
+        }
+        let Some(end) = line[start..].find(')') else {
+            break;
+        };
+        let args = &line[start + 4..start + end];
+        let name = args.split(',').next().unwrap_or_default().trim();
+        if name.starts_with("--") {
+            references.push((start, &line[start..start + end + 1], name));
+        }
+    }
+
+    references
+}
+
+/// Add the nodes of the `var(--name)` references to the custom properties declared before them,
+/// with the colors of the declarations.
+///
+/// The declarations are collected from the `nodes` of the first pass, a declaration can also be
+/// a resolved reference like `--link: var(--accent)`. The references that overlap a node, e.g. the
+/// fallback of `var(--name, #000)`, and the unknown names are skipped.
+pub(crate) fn resolve_var_references(text: &str, nodes: &mut Vec<ColorNode>) {
+    let mut colors: HashMap<&str, Color> = HashMap::new();
+    let mut resolved = vec![];
+
+    for (ix, line) in text.lines().enumerate() {
+        let line_no = ix as u32 + 1;
+        let mut references = var_references(line).into_iter().peekable();
+        let mut declarations = custom_property_declarations(line).into_iter().peekable();
+
+        loop {
+            // Resolve the references before the end of the next declaration value first,
+            // as the value itself can be a reference
+            let next_declaration_end = declarations
+                .peek()
+                .map(|(_, offset, value)| offset + value.len());
+            match (references.peek(), next_declaration_end) {
+                (Some((offset, _, _)), end) if end.is_none_or(|end| *offset < end) => {
+                    let (offset, reference, name) = references.next().unwrap();
+                    let range = TextRange::new(line_no, offset as u32 + 1, reference.len() as u32);
+                    let overlaps = nodes
+                        .iter()
+                        .any(|node| node.range.line == line_no && node.range.overlaps(&range));
+                    if let (false, Some(color)) = (overlaps, colors.get(name)) {
+                        let mut node = ColorNode::new(reference, color.clone(), ix + 1, offset + 1);
+                        node.locate_utf16(line);
+                        resolved.push(node);
+                    }
+                }
+                (_, Some(_)) => {
+                    let (name, offset, value) = declarations.next().unwrap();
+                    let node = nodes.iter().chain(&resolved).find(|node| {
+                        node.range.line == line_no
+                            && node.range.col as usize == offset + 1
+                            && node.matched == value
+                    });
+                    if let Some(node) = node {
+                        colors.insert(name, node.color.clone());
+                    }
+                }
+                (_, None) => break,
+            }
+        }
+    }
+
+    if !resolved.is_empty() {
+        nodes.extend(resolved);
+        nodes.sort_by_key(|node| (node.range.line, node.range.col));
+    }
+}
+
+/// Find the CSS Modules `@value name: value;` declaration in the line, not the imports like
+/// `@value primary from "./colors.css";`.
+///
+/// Returns the name, the byte offset of the value and the value.
+fn css_module_value_declaration(line: &str) -> Option<(&str, usize, &str)> {
+    let rest = line.trim_start().strip_prefix("@value")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (name, value) = rest.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+
+    let value = value[..value.find(';').unwrap_or(value.len())].trim();
+    let offset = value.as_ptr() as usize - line.as_ptr() as usize;
+    Some((name, offset, value))
+}
+
+/// Add the nodes of the usages of the CSS Modules `@value`s like `color: primary`, with the colors
+/// of the declarations like `@value primary: #ff5722;`.
+///
+/// A value can be another value like `@value link: primary;`. The usages are only resolved in
+/// the declaration values, not in the selectors like `.primary`.
+pub(crate) fn resolve_css_module_values(text: &str, nodes: &mut Vec<ColorNode>) {
+    if !text.contains("@value") {
+        return;
+    }
+
+    let mut colors: HashMap<&str, Color> = HashMap::new();
+    let mut resolved = vec![];
+    for (ix, line) in text.lines().enumerate() {
+        let Some((name, offset, value)) = css_module_value_declaration(line) else {
+            continue;
+        };
+        let node = nodes.iter().find(|node| {
+            node.range.line as usize == ix + 1
+                && node.range.col as usize == offset + 1
+                && node.matched == value
+        });
+        let color = match (node, colors.get(value)) {
+            (Some(node), _) => node.color.clone(),
+            (None, Some(color)) => {
+                let mut node = ColorNode::new(value, color.clone(), ix + 1, offset + 1);
+                node.locate_utf16(line);
+                resolved.push(node);
+                color.clone()
+            }
+            (None, None) => continue,
+        };
+        colors.insert(name, color);
+    }
+
+    for (ix, line) in text.lines().enumerate() {
+        if colors.is_empty() {
+            break;
+        }
+        if css_module_value_declaration(line).is_some() {
+            continue;
+        }
+        let line_no = ix as u32 + 1;
+        for word in line.split(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_')) {
+            let Some(color) = colors.get(word) else {
+                continue;
+            };
+            let offset = word.as_ptr() as usize - line.as_ptr() as usize;
+            let range = TextRange::new(line_no, offset as u32 + 1, word.len() as u32);
+            let overlaps = nodes
+                .iter()
+                .any(|node| node.range.line == line_no && node.range.overlaps(&range));
+            // Not in the declaration values, or in the file names like `url(primary.png)`
+            let before = &line[..offset];
+            let in_url = before.rfind("url(") > before.rfind(')');
+            if overlaps || in_url || css_property_name(line, offset).is_none() {
+                continue;
+            }
+            let mut node = ColorNode::new(word, color.clone(), ix + 1, offset + 1);
+            node.locate_utf16(line);
+            resolved.push(node);
+        }
+    }
+
+    if !resolved.is_empty() {
+        nodes.extend(resolved);
+        nodes.sort_by_key(|node| (node.range.line, node.range.col));
+    }
+}
+
+/// Parse the hex color of an Android resource, `#RGB`, `#ARGB`, `#RRGGBB` or `#AARRGGBB`.
+fn parse_android_resource_color(value: &str) -> Option<Color> {
+    let digits = value.strip_prefix('#')?;
+    match digits.len() {
+        3 | 6 => csscolorparser::parse(value).ok(),
+        4 => {
+            let expanded = digits.chars().flat_map(|c| [c, c]).collect::<String>();
+            try_parse_android_color(&format!("#{expanded}")).ok()
+        }
+        8 => try_parse_android_color(value).ok(),
+        _ => None,
+    }
+}
+
+/// Collect the Android color resources like `<color name="brand_red">#FFFF0000</color>` of
+/// `values/colors.xml` by the name.
+///
+/// A resource can be another resource like `<color name="primary">@color/brand_red</color>`.
+pub(crate) fn android_color_resources(text: &str) -> HashMap<String, Color> {
+    let mut colors = HashMap::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<color ") {
+        rest = &rest[start + "<color ".len()..];
+        let Some((attributes, after)) = rest.split_once('>') else {
+            break;
+        };
+        let Some((value, after)) = after.split_once("</color>") else {
+            break;
+        };
+        rest = after;
+
+        let name = attributes
+            .split_once("name=\"")
+            .and_then(|(_, name)| name.split_once('"'))
+            .map(|(name, _)| name);
+        let value = value.trim();
+        let color = match value.strip_prefix("@color/") {
+            Some(reference) => colors.get(reference).cloned(),
+            None => parse_android_resource_color(value),
+        };
+        if let (Some(name), Some(color)) = (name, color) {
+            colors.insert(name.to_string(), color);
+        }
+    }
+    colors
+}
+
+/// Add the nodes of the Android `@color/name` references, like in
+/// `<item name="colorPrimary">@color/brand_red</item>`, with the `colors` of the resources.
+pub(crate) fn resolve_android_color_references(
+    text: &str,
+    colors: &HashMap<String, Color>,
+    nodes: &mut Vec<ColorNode>,
+) {
+    if colors.is_empty() {
+        return;
+    }
+
+    let mut resolved = vec![];
+    for (ix, line) in text.lines().enumerate() {
+        let line_no = ix as u32 + 1;
+        for (offset, _) in line.match_indices("@color/") {
+            let name = &line[offset + "@color/".len()..];
+            let name_len = name
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(name.len());
+            let Some(color) = colors.get(&name[..name_len]) else {
+                continue;
+            };
+            let reference = &line[offset..offset + "@color/".len() + name_len];
+            let range = TextRange::new(line_no, offset as u32 + 1, reference.len() as u32);
+            let overlaps = nodes
+                .iter()
+                .any(|node| node.range.line == line_no && node.range.overlaps(&range));
+            if !overlaps {
+                let mut node = ColorNode::new(reference, color.clone(), ix + 1, offset + 1);
+                node.locate_utf16(line);
+                resolved.push(node);
+            }
+        }
+    }
+
+    if !resolved.is_empty() {
+        nodes.extend(resolved);
+        nodes.sort_by_key(|node| (node.range.line, node.range.col));
+    }
+}
+
+/// Parse the `part` as a color at the 0-based `line_ix` and byte `offset`.
+fn match_color(part: &str, line_ix: usize, offset: usize) -> Option<ColorNode> {
+    if let Ok(color) = try_parse_color(part) {
+        Some(ColorNode::new(part, color, line_ix + 1, offset + 1))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use csscolorparser::Color;
+    use tower_lsp::lsp_types;
+
+    use crate::config::Config;
+    use crate::parser::{
+        android_color_resources, byte_offset, ciede2000_distance, ciede2000_lab, css_property_name,
+        custom_property_colors, custom_property_names, is_valid_css_color, is_valid_hex_color,
+        match_color, nearest_named_colors, parse, parse_0x_color, parse_angle,
+        parse_css_property_value, parse_document, parse_file, parse_gradient, parse_str,
+        parse_with_context, parse_with_registry, resolve_android_color_references,
+        supported_formats, tailwind_theme_path, try_parse_android_color, try_parse_color,
+        try_parse_color_function, try_parse_color_mix, try_parse_flutter_color, try_parse_go_color,
+        try_parse_godot_color, try_parse_gpui_color, try_parse_haskell_color, try_parse_nix_color,
+        try_parse_swiftui_color, try_parse_tk_color, try_parse_uicolor, wcag_aa_passes, ColorNode,
+        ColorPattern, ColorPatternRegistry, ColorSpace, TextRange, DEFAULT_NEAR_THRESHOLD,
+        PATTERN_LANGUAGES,
+    };
+
+    #[test]
+    fn test_byte_offset() {
+        assert_eq!(byte_offset("a: #fff", 3), 3);
+        assert_eq!(byte_offset("颜色: #fff", 4), 8);
+        assert_eq!(byte_offset("🎨: #fff", 4), 6);
+        assert_eq!(byte_offset("a", 10), 1);
+    }
+
+    #[test]
+    fn test_color_information_round_trip() {
+        let text = "a {\n  颜色: rgb(255 136 0 / 50%);\n}";
+        let node = parse(text).remove(0);
+        let info = lsp_types::ColorInformation::from(&node);
+        assert_eq!(info.range, node.lsp_range());
+        assert_eq!(info.color, node.lsp_color());
+
+        let round_trip = ColorNode::try_from((&info, text)).unwrap();
+        assert_eq!(round_trip, node);
+        assert_eq!(round_trip.color.to_array(), node.color.to_array());
+        assert_eq!(round_trip.layer, node.layer);
+        assert_eq!(round_trip.original_space, node.original_space);
+        assert_eq!(round_trip.alpha_explicit, node.alpha_explicit);
+        assert_eq!(round_trip.utf16_cols, node.utf16_cols);
+
+        let mut info = info;
+        info.range.start.line = 5;
+        info.range.end.line = 5;
+        assert!(ColorNode::try_from((&info, text)).is_err());
+        info.range = lsp_types::Range::default();
+        assert!(ColorNode::try_from((&info, text)).is_err());
+    }
+
+    #[test]
+    fn test_match_color() {
+        let cases = vec![
+            "#A0F0F0",
+            "#2eC8f1",
+            "#AAF0F0aa",
+            "#AAF0F033",
+            "#0f0E",
+            "#F2c",
+            "rgb(80%,80%,20%)",
+            "rgb(255 100 0)",
+            "rgba(255, 0, 0, 0.5)",
+            "rgb(100, 200, 100)",
+            "hsl(225, 100%, 70%)",
+            "hsla(20, 100%, 50%, .5)",
+            "hsla(1., 0.5, 0.5, 1.)",
+        ];
+
+        for case in cases {
+            assert!(match_color(case, 1, 1).is_some());
+        }
+
+        assert_eq!(
+            match_color("#e7b911", 1, 10),
+            Some(ColorNode::must_parse("#e7b911", 2, 11))
+        );
+    }
+
+    #[test]
+    fn test_is_valid_color() {
+        for s in ["#fff", "#FFFA", "#ff8800", "#ff880080"] {
+            assert!(is_valid_hex_color(s), "{s}");
+            assert!(is_valid_css_color(s), "{s}");
+        }
+        for s in [
+            "fff",
+            "#ff",
+            "#fffff",
+            "#ggg",
+            "# fff",
+            "#ff8800 ",
+            "red",
+            "rgb(1, 2, 3)",
+        ] {
+            assert!(!is_valid_hex_color(s), "{s}");
+        }
+
+        assert!(is_valid_css_color("red"));
+        assert!(is_valid_css_color("rgb(255 0 0 / 50%)"));
+        assert!(is_valid_css_color("Color(0xFF112233)"));
+        assert!(!is_valid_css_color("#ggg"));
+        assert!(!is_valid_css_color("1px solid"));
+    }
+
+    #[test]
+    fn test_parse_angle() {
+        assert_eq!(parse_angle("120"), Some(120.0));
+        assert_eq!(parse_angle("120deg"), Some(120.0));
+        assert_eq!(parse_angle("0.5turn"), Some(180.0));
+        assert_eq!(parse_angle("200grad"), Some(180.0));
+        assert!((parse_angle("3.14159rad").unwrap() - 180.0).abs() < 0.001);
+        assert_eq!(parse_angle("1.5TURN"), Some(540.0));
+        assert_eq!(parse_angle("90px"), None);
+        assert_eq!(parse_angle("deg"), None);
+        assert_eq!(parse_angle("1ߘdeg"), None);
+
+        let close = |a: Color, b: &Color| {
+            (a.r - b.r).abs() < 0.001 && (a.g - b.g).abs() < 0.001 && (a.b - b.b).abs() < 0.001
+        };
+        let hsl = try_parse_color("hsl(240, 100%, 50%)").unwrap();
+        for s in [
+            "hsl(240deg, 100%, 50%)",
+            "hsl(4.18879rad, 100%, 50%)",
+            "hsl(266.6667grad, 100%, 50%)",
+            "hsl(0.6666667turn, 100%, 50%)",
+        ] {
+            assert!(close(try_parse_color(s).unwrap(), &hsl), "{s}");
+        }
+        let oklch = try_parse_color("oklch(0.6 0.2 240)").unwrap();
+        for s in ["oklch(0.6 0.2 240deg)", "oklch(0.6 0.2 0.6666667turn)"] {
+            assert!(close(try_parse_color(s).unwrap(), &oklch), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_parse_none_channels() {
+        assert_eq!(
+            try_parse_color("oklch(0.5 none 180)").unwrap().to_rgba8(),
+            try_parse_color("oklch(0.5 0 180)").unwrap().to_rgba8()
+        );
+        assert_eq!(
+            try_parse_color("oklab(0.5 none 0.1)").unwrap().to_rgba8(),
+            try_parse_color("oklab(0.5 0 0.1)").unwrap().to_rgba8()
+        );
+        assert_eq!(
+            try_parse_color("lch(50% 40 none / none)")
+                .unwrap()
+                .to_rgba8(),
+            try_parse_color("lch(50% 40 0 / 0)").unwrap().to_rgba8()
+        );
+        assert!(try_parse_color("hwb(none 0% 0%)").is_err());
+        assert!(try_parse_color("hsl(\u{1F600}0, 100%, 50%)").is_err());
+        assert!(parse("color: hsl(\u{1F600}0, 100%, 50%);").is_empty());
+
+        let nodes = parse("color: oklch(0.5 NONE 180);");
+        assert_eq!(nodes[0].matched, "oklch(0.5 NONE 180)");
+        assert!(nodes[0].is_css_notation());
+    }
+
+    #[test]
+    fn test_try_parse_gpui_color() {
+        assert_eq!(
+            try_parse_gpui_color("rgb(0., 1., 0.2)"),
+            Ok(Color {
+                r: 0.,
+                g: 1.,
+                b: 0.2,
+                a: 1.
+            })
+        );
+        assert_eq!(
+            try_parse_gpui_color("rgb(0., 1., 0., 0.45)"),
+            Ok(Color {
+                r: 0.,
+                g: 1.,
+                b: 0.,
+                a: 0.45
+            })
+        );
+        assert!(try_parse_gpui_color("rgb(255., 220.0, 0.)").is_err());
+        assert!(try_parse_gpui_color("rgba(255., 120., 20.0, 1.)").is_err());
+
+        assert_eq!(
+            try_parse_gpui_color("hsl(0.48, 1., 0.45)"),
+            Ok(Color::new(0., 0.9, 0.79200006, 1.))
+        );
+        assert_eq!(
+            try_parse_gpui_color("hsla(0.48, 1., 0.45, 0.3)"),
+            Ok(Color::new(0., 0.9, 0.79200006, 0.3))
+        );
+        assert_eq!(
+            try_parse_gpui_color("hsl(180deg, 1., 0.5)"),
+            try_parse_gpui_color("hsl(0.5, 1., 0.5)"),
+        );
+        assert!(try_parse_gpui_color("hsl(240., 0., 50.0)").is_err());
+        assert!(try_parse_gpui_color("hsla(240., 0., 50.0, 1.)").is_err());
+        // Found by the `parse_gpui_color` fuzzer, the hue overflows or splits a char
+        assert!(try_parse_gpui_color("hsl(1e39deg, 0.5, 0.5)").is_err());
+        assert!(try_parse_gpui_color("hsl(0ߘdeg, 0.5, 0.5)").is_err());
+    }
+
+    #[test]
+    fn test_color_space_from_notation() {
+        assert_eq!(
+            ColorSpace::from_notation("oklch(0.5 0.1 30)"),
+            Some(ColorSpace::Oklch)
+        );
+        assert_eq!(
+            ColorSpace::from_notation("OKLab(0.5 0 0)"),
+            Some(ColorSpace::Oklab)
+        );
+        assert_eq!(
+            ColorSpace::from_notation("[lch(50_30_120)]"),
+            Some(ColorSpace::Lch)
+        );
+        assert_eq!(
+            ColorSpace::from_notation("hsla(0, 100%, 50%, 1)"),
+            Some(ColorSpace::Hsl)
+        );
+        assert_eq!(ColorSpace::from_notation("#fff"), None);
+        assert_eq!(ColorSpace::from_notation("Color(0xFFFFFFFF)"), None);
+
+        let nodes = parse("a { color: lab(50 40 -20); background: #fff; }");
+        assert_eq!(nodes[0].original_space, Some(ColorSpace::Lab));
+        assert_eq!(nodes[1].original_space, None);
+    }
+
+    #[test]
+    fn test_to_java_color() {
+        let node = ColorNode::must_parse("#ff8800", 1, 1);
+        assert_eq!(node.to_java_color(), "new java.awt.Color(255, 136, 0, 255)");
+        assert_eq!(node.to_android_color_int(), "0xFFFF8800");
+
+        let node = ColorNode::must_parse("rgb(0 128 255 / 50%)", 1, 1);
+        assert_eq!(node.to_java_color(), "new java.awt.Color(0, 128, 255, 128)");
+        assert_eq!(node.to_android_color_int(), "0x800080FF");
+    }
+
+    #[test]
+    fn test_to_qcolor() {
+        let node = ColorNode::must_parse("#ff8800", 1, 1);
+        assert_eq!(node.to_qcolor(), "QColor(255, 136, 0, 255)");
+        assert_eq!(node.to_qcolor_hex(), "QColor(\"#ff8800\")");
+        assert_eq!(
+            node.to_qcolor_rgb_f(),
+            "QColor::fromRgbF(1.0, 0.533, 0.0, 1.0)"
+        );
+
+        let node = ColorNode::must_parse("rgb(0 128 255 / 50%)", 1, 1);
+        assert_eq!(node.to_qcolor(), "QColor(0, 128, 255, 128)");
+        assert_eq!(node.to_qcolor_hex(), "QColor(\"#800080ff\")");
+        assert_eq!(
+            node.to_qcolor_rgb_f(),
+            "QColor::fromRgbF(0.0, 0.502, 1.0, 0.5)"
+        );
+    }
+
+    #[test]
+    fn test_to_godot_color_string() {
+        let node = ColorNode::must_parse("#ff8800", 1, 1);
+        assert_eq!(
+            node.to_godot_color_string(),
+            "Color(1.0000, 0.5333, 0.0000, 1.0000)"
+        );
+        assert_eq!(node.to_godot_hex(), "Color.html(\"#ff8800\")");
+
+        let node = ColorNode::must_parse("rgb(0 128 255 / 50%)", 1, 1);
+        assert_eq!(
+            node.to_godot_color_string(),
+            "Color(0.0000, 0.5020, 1.0000, 0.5000)"
+        );
+        assert_eq!(node.to_godot_hex(), "Color.html(\"#0080ff80\")");
+    }
+
+    #[test]
+    fn test_to_hex_string() {
+        let hex = |s: &str| ColorNode::must_parse(s, 1, 1).to_hex_string();
+        assert_eq!(hex("#FF8A00"), "#ff8a00");
+        assert_eq!(hex("#FF8800"), "#f80");
+        assert_eq!(hex("#aabbcc"), "#abc");
+        assert_eq!(hex("rgb(255 0 0)"), "#f00");
+        assert_eq!(hex("#ff880080"), "#ff880080");
+        assert_eq!(hex("#aabbcc88"), "#abc8");
+        assert_eq!(hex("#abc"), "#abc");
+
+        let node = ColorNode::must_parse("#ff8800", 1, 1);
+        assert_eq!(node.to_rgb_string(), "rgb(255 136 0)");
+        assert_eq!(
+            node.to_css_custom_property_declaration("primary")
+                .as_deref(),
+            Some("--primary: #ff8800ff;")
+        );
+        assert_eq!(
+            node.to_css_custom_property_declaration("--brand_2")
+                .as_deref(),
+            Some("--brand_2: #ff8800ff;")
+        );
+        assert_eq!(node.to_css_custom_property_declaration("--"), None);
+        assert_eq!(node.to_css_custom_property_declaration("a b"), None);
+        assert_eq!(node.to_hsl_string(), "hsl(32 100% 50%)");
+        assert_eq!(node.to_oklch_string(), "oklch(0.744 0.181 56.46)");
+    }
+
+    #[test]
+    fn test_alpha_explicit() {
+        let explicit = |s: &str| ColorNode::must_parse(s, 1, 1).alpha_explicit;
+        for s in [
+            "#f00f",
+            "#ff0000ff",
+            "rgba(255, 0, 0, 1)",
+            "rgb(255 0 0 / 1)",
+            "hsl(0 100% 50% / 50%)",
+            "oklch(0.6 0.2 30 / 1)",
+            "color(srgb 1 0 0 / 1)",
+            "Color(0xFFFF0000)",
+            "Color.fromARGB(255, 255, 0, 0)",
+        ] {
+            assert!(explicit(s), "{s}");
+        }
+        for s in [
+            "#f00",
+            "#ff0000",
+            "red",
+            "rgb(255, 0, 0)",
+            "hsl(0 100% 50%)",
+            "oklch(0.6 0.2 30)",
+            "color(srgb 1 0 0)",
+        ] {
+            assert!(!explicit(s), "{s}");
+        }
+
+        let text = "c := color.NRGBA{R: 255, A: 255}\nd := color.RGBA{R: 255}";
+        let nodes = parse_document(text, "go", &Config::default());
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| node.alpha_explicit)
+                .collect::<Vec<_>>(),
+            [true, false]
+        );
+        let nodes = parse_document(
+            "{ r = 1.0; g = 0.5; b = 0.0; a = 1.0; }",
+            "nix",
+            &Config::default(),
+        );
+        assert!(nodes[0].alpha_explicit);
+    }
+
+    #[test]
+    fn test_ord_by_position() {
+        let text = "a { color: #f00; background: #0f0; }\nb { color: rgb(0 0 255); }\n#ff0";
+        let nodes = parse(text);
+        assert_eq!(nodes.len(), 4);
+
+        let mut shuffled = vec![
+            nodes[2].clone(),
+            nodes[0].clone(),
+            nodes[3].clone(),
+            nodes[1].clone(),
+        ];
+        shuffled.sort();
+        assert_eq!(shuffled, nodes);
+        assert!(nodes[0] < nodes[1] && nodes[1] < nodes[2]);
+
+        // Same position, ordered by length
+        let short = ColorNode::new("#f00", Color::new(1., 0., 0., 1.), 1, 1);
+        let long = ColorNode::new("#ff0000", Color::new(1., 0., 0., 1.), 1, 1);
+        assert!(short < long);
+        assert_eq!(short.cmp(&short.clone()), std::cmp::Ordering::Equal);
+
+        let search = |line, col| {
+            nodes
+                .binary_search_by(|node| node.cmp_position(line, col))
+                .map(|ix| nodes[ix].matched.as_str())
+        };
+        assert_eq!(search(1, 30), Ok("#0f0"));
+        assert_eq!(search(2, 12), Ok("rgb(0 0 255)"));
+        assert_eq!(search(3, 1), Ok("#ff0"));
+        assert_eq!(search(1, 31), Err(2));
+        assert_eq!(search(1, 1), Err(0));
+    }
+
+    #[test]
+    fn test_must_parse() {
+        assert_eq!(
+            ColorNode::must_parse("hsla(.2, 0.5, 0.5, 1.)", 10, 12),
+            ColorNode {
+                matched: "hsla(.2, 0.5, 0.5, 1.)".to_string(),
+                color: Color::from_hsla(0.2 * 360., 0.5, 0.5, 1.),
+                range: TextRange::new(10, 12, 22),
+                layer: None,
+                original_space: Some(ColorSpace::Hsl),
+                alpha_explicit: true,
+                utf16_cols: (11, 33),
+            }
+        );
+
+        assert_eq!(
+            ColorNode::must_parse("rgba(1., 0.5, 0.5, 1.)", 10, 12),
+            ColorNode {
+                matched: "rgba(1., 0.5, 0.5, 1.)".to_string(),
+                color: Color::new(1., 0.5, 0.5, 1.),
+                range: TextRange::new(10, 12, 22),
+                layer: None,
+                original_space: Some(ColorSpace::Srgb),
+                alpha_explicit: true,
+                utf16_cols: (11, 33),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let colors = parse(include_str!("../../tests/test.json"));
+
+        assert_eq!(colors.len(), 8);
+        assert_eq!(colors[0], ColorNode::must_parse("#999", 2, 15));
+        assert_eq!(colors[1], ColorNode::must_parse("#FFFFFF", 3, 18));
+        assert_eq!(colors[2], ColorNode::must_parse("#ff003c99", 4, 13));
+        assert_eq!(colors[3], ColorNode::must_parse("#3cBD00", 5, 15));
+        assert_eq!(
+            colors[4],
+            ColorNode::must_parse("rgba(255, 252, 0, 0.5)", 6, 12)
+        );
+        assert_eq!(
+            colors[5],
+            ColorNode::must_parse("rgb(100, 200, 100)", 7, 11)
+        );
+        assert_eq!(
+            colors[6],
+            ColorNode::must_parse("hsla(20, 100%, 50%, .5)", 8, 12)
+        );
+        assert_eq!(
+            colors[7],
+            ColorNode::must_parse("hsl(225, 100%, 70%)", 9, 11)
+        );
+
+        let colors = parse(include_str!("../../tests/test.rs"));
+        assert_eq!(colors.len(), 5);
+        assert_eq!(
+            colors[0],
+            ColorNode::must_parse("hsla(0.3, 1.0, 0.5, 1.0)", 1, 10)
+        );
+        assert_eq!(
+            colors[1],
+            ColorNode::must_parse("hsla(0.58, 1.0, 0.5, 1.0)", 2, 10)
+        );
+        assert_eq!(
+            colors[2],
+            ColorNode::must_parse("hsla(0.85, 0.9, 0.6, 1.0)", 3, 10)
+        );
+        assert_eq!(
+            colors[3],
+            ColorNode::must_parse("hsla(0.75, 0.9, 0.65, 1.0)", 4, 13)
+        );
+        assert_eq!(
+            colors[4],
+            ColorNode::must_parse("hsla(0.45, 0.7, 0.75, 1.0)", 5, 14)
+        );
+    }
+
+    #[test]
+    fn test_parse_0x_color() {
+        assert_eq!(
+            parse_0x_color("0xFF8800").unwrap(),
+            csscolorparser::parse("#FF8800").unwrap()
+        );
+        assert_eq!(
+            parse_0x_color("0XFF112233").unwrap(),
+            Color::from_rgba8(0x11, 0x22, 0x33, 0xFF)
+        );
+        assert_eq!(
+            parse_0x_color("0x80FF0000").unwrap(),
+            Color::from_rgba8(0xFF, 0, 0, 0x80)
+        );
+        assert!(parse_0x_color("0xFF88").is_err());
+        assert!(parse_0x_color("0xFF88001").is_err());
+        assert!(parse_0x_color("0xGG8800").is_err());
+
+        let colors = parse("const RED: u32 = 0xFF0000;\nlet c = [10x123456, 0xFF112233];");
+        assert_eq!(colors.len(), 2);
+        assert_eq!(
+            colors[0],
+            ColorNode::new("0xFF0000", Color::new(1., 0., 0., 1.), 1, 18)
+        );
+        assert_eq!(
+            colors[1],
+            ColorNode::new(
+                "0xFF112233",
+                Color::from_rgba8(0x11, 0x22, 0x33, 0xFF),
+                2,
+                21
+            )
+        );
+
+        // Too many digits for a color
+        assert!(parse("let n = 0xFF00000000;").is_empty());
+
+        let config = Config::default();
+        assert!(parse_document("width: 0xFF0000;", "css", &config).is_empty());
+        assert_eq!(parse_document("width: 0xFF0000;", "rust", &config).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_document_without_hex() {
+        let config = Config {
+            enable_hex: false,
+            ..Default::default()
+        };
+        let text = "a { color: #fff; background: linear-gradient(#000, red); }
+<div class=\"bg-[#fff] text-[red]\">
+b { color: color-mix(in srgb, #fff, red); border-color: color-mix(in srgb, blue, red); }";
+        let matched = parse_document(text, "css", &config)
+            .into_iter()
+            .map(|node| node.matched)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            matched,
+            vec!["red", "[red]", "color-mix(in srgb, blue, red)"]
+        );
+    }
+
+    #[test]
+    fn test_android_color_resources() {
+        let text = r#"<resources>
+    <color name="brand_red">#FFFF0000</color>
+    <color name="overlay">#80000000</color>
+    <color name="short">#0f0</color>
+    <color name="primary">@color/brand_red</color>
+    <color name="unknown">@color/missing</color>
+</resources>"#;
+        let colors = android_color_resources(text);
+        assert_eq!(colors.len(), 4);
+        assert_eq!(colors["brand_red"].to_rgba8(), [255, 0, 0, 255]);
+        assert_eq!(colors["overlay"].to_rgba8(), [0, 0, 0, 128]);
+        assert_eq!(colors["short"].to_rgba8(), [0, 255, 0, 255]);
+        assert_eq!(colors["primary"].to_rgba8(), [255, 0, 0, 255]);
+
+        let text = r#"<style name="AppTheme">
+    <item name="colorPrimary">@color/primary</item>
+    <item name="colorSecondary">@color/missing</item>
+    <item name="android:textColor">@android:color/white</item>
+</style>"#;
+        let mut nodes = parse(text);
+        resolve_android_color_references(text, &colors, &mut nodes);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].matched, "@color/primary");
+        assert_eq!(nodes[0].range, TextRange::new(2, 31, 14));
+        assert_eq!(nodes[0].color.to_rgba8(), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_parse_android_argb() {
+        assert_eq!(
+            try_parse_android_color("#80FF0000").unwrap().to_rgba8(),
+            [255, 0, 0, 128]
+        );
+        assert!(try_parse_android_color("#FF0000").is_err());
+        assert!(try_parse_android_color("#80FF00000").is_err());
+
+        let text = "<color name=\"overlay\">#80FF0000</color> <color name=\"red\">#F00</color>";
+        let nodes = parse_document(text, "xml", &Config::default());
+        assert_eq!(nodes[0].color.to_rgba8(), [0x80, 0xff, 0, 0]);
+
+        let config = Config {
+            enable_android_argb: true,
+            ..Default::default()
+        };
+        let nodes = parse_document(text, "xml", &config);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].matched, "#80FF0000");
+        assert_eq!(
+            nodes[0].color.to_rgba8(),
+            try_parse_color("rgba(255, 0, 0, 0.5)").unwrap().to_rgba8()
+        );
+        assert_eq!(nodes[1].color.to_rgba8(), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let text = "a {\n  color: #ff0000;\n  border-color: hsl(120, 100%, 50%);\n}";
+        let path = std::env::temp_dir().join(format!("color-lsp-parse-{}.css", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let nodes = parse_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let nodes = nodes.unwrap();
+        assert_eq!(nodes, parse_str(text));
+        assert_eq!(
+            nodes.iter().map(|node| node.range).collect::<Vec<_>>(),
+            vec![TextRange::new(2, 10, 7), TextRange::new(3, 17, 19)]
+        );
+        assert!(parse_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_parse_custom_property_declarations() {
+        let text = ":root { --primary: hsl(220, 90%, 56%); }\n:root {\n  --accent: red;\n  --shadow: 0 0 4px #fff;\n  --size: 4px;\n}";
+        let nodes = parse(text);
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| (node.matched.as_str(), node.range))
+                .collect::<Vec<_>>(),
+            vec![
+                ("hsl(220, 90%, 56%)", TextRange::new(1, 20, 18)),
+                ("red", TextRange::new(3, 13, 3)),
+                ("#fff", TextRange::new(4, 21, 4)),
+            ]
+        );
+
+        let colors = custom_property_colors(text, &nodes);
+        assert_eq!(colors.len(), 2);
+        assert_eq!(colors["--primary"], nodes[0]);
+        assert_eq!(colors["--accent"], nodes[1]);
+
+        let config = Config {
+            enable_hex: false,
+            ..Default::default()
+        };
+        assert!(parse_document("a { --accent: #fff; }", "css", &config).is_empty());
+        assert!(parse("a { background: var(--red); }").is_empty());
+
+        assert_eq!(
+            custom_property_names("a { --a-1: var(--b, var(--c_2)); width: calc(x--y); }"),
+            vec![(4, "--a-1"), (15, "--b"), (24, "--c_2")]
+        );
+    }
+
+    #[test]
+    fn test_parse_var_references() {
+        let text = "a {\n  --accent: #ff8800;\n  --link: var(--accent);\n}\nb { color: var(--accent); }\n\
+            c { color: var(--link); background: var(--unknown); border-color: var(--accent, #000); }";
+        let accent = Color::from_rgba8(0xff, 0x88, 0, 0xff);
+        assert_eq!(
+            parse(text),
+            vec![
+                ColorNode::new("#ff8800", accent.clone(), 2, 13),
+                ColorNode::new("var(--accent)", accent.clone(), 3, 11),
+                ColorNode::new("var(--accent)", accent.clone(), 5, 12),
+                ColorNode::new("var(--link)", accent, 6, 12),
+                ColorNode::must_parse("#000", 6, 81),
+            ]
+        );
+
+        // Only the declarations before the reference are resolved
+        assert!(parse("a { color: var(--accent); }\nb { --accent: #f80; }")
+            .iter()
+            .all(|node| node.matched != "var(--accent)"));
+        assert_eq!(
+            parse("a { --accent: #f80; color: var(--accent); }")[1],
+            ColorNode::new(
+                "var(--accent)",
+                Color::from_rgba8(0xff, 0x88, 0, 0xff),
+                1,
+                28
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_css_module_values() {
+        let text =
+            "@value primary: #ff5722;\n@value link: primary;\n@value small: (max-width: 599px);\n\
+            @value secondary from \"./colors.css\";\n\
+            .primary { color: primary; border: 1px solid link; }\n\
+            .link { background: url(primary.png); width: small; }";
+        let primary = Color::from_rgba8(0xff, 0x57, 0x22, 0xff);
+        assert_eq!(
+            parse_document(text, "css", &Config::default()),
+            vec![
+                ColorNode::must_parse("#ff5722", 1, 17),
+                ColorNode::new("primary", primary.clone(), 2, 14),
+                ColorNode::new("primary", primary.clone(), 5, 19),
+                ColorNode::new("link", primary, 5, 46),
+            ]
+        );
+        assert_eq!(
+            parse_document(text, "javascript", &Config::default()).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_parse_with_context() {
+        let context = [
+            ":root {",
+            "  --accent: #ff8800;",
+            "  --muted: rgb(100, 100, 100);",
+            "}",
+        ];
+
+        assert_eq!(
+            parse_with_context("  color: var(--accent);", 14, &context),
+            Some(ColorNode::new(
+                "var(--accent)",
+                Color::from_rgba8(0xff, 0x88, 0, 0xff),
+                5,
+                10
+            ))
+        );
+        assert_eq!(
+            parse_with_context("  border: 1px solid var(--muted, #000);", 22, &context),
+            Some(ColorNode::new(
+                "var(--muted, #000)",
+                Color::from_rgba8(100, 100, 100, 0xff),
+                5,
+                21
+            ))
+        );
+        assert_eq!(
+            parse_with_context("  color: #f00;", 12, &context),
+            Some(ColorNode::must_parse("#f00", 5, 10))
+        );
+        assert_eq!(parse_with_context("  color: #f00;", 4, &context), None);
+        assert_eq!(
+            parse_with_context("  color: var(--unknown);", 12, &context),
+            None
+        );
+    }
+
+    #[test]
+    fn test_try_parse_swiftui_color() {
+        assert_eq!(
+            try_parse_swiftui_color("Color(hue: 0.5, saturation: 0.8, brightness: 1.0)"),
+            Ok(Color::from_hsva(180., 0.8, 1., 1.))
+        );
+        assert_eq!(
+            try_parse_swiftui_color("Color(brightness: 1, opacity: 0.5, hue: 0, saturation: 1)"),
+            Ok(Color::new(1., 0., 0., 0.5))
+        );
+        assert_eq!(
+            try_parse_swiftui_color("Color(.systemBlue)"),
+            Ok(Color::from_rgba8(0, 0x7a, 0xff, 255))
+        );
+        assert!(try_parse_swiftui_color("Color(.unknownColor)").is_err());
+        assert!(try_parse_swiftui_color("Color(hue: 0.5, saturation: 0.8)").is_err());
+        assert!(
+            try_parse_swiftui_color("Color(hue: 180, saturation: 0.8, brightness: 1)").is_err()
+        );
+        assert!(try_parse_swiftui_color("Color(red: 1, saturation: 0.8, brightness: 1)").is_err());
+
+        let text = "let a = Color(hue: 0.5, saturation: 0.8, brightness: 1.0)\nlet b = SwiftUI.Color(.systemRed)";
+        let colors = parse_document(text, "swift", &Config::default());
+        assert_eq!(colors.len(), 2);
+        assert_eq!(
+            colors[0],
+            ColorNode::new(
+                "Color(hue: 0.5, saturation: 0.8, brightness: 1.0)",
+                Color::from_hsva(180., 0.8, 1., 1.),
+                1,
+                9
+            )
+        );
+        assert_eq!(
+            colors[1],
+            ColorNode::new(
+                "Color(.systemRed)",
+                Color::from_rgba8(0xff, 0x3b, 0x30, 255),
+                2,
+                17
+            )
+        );
+        assert!(parse_document(text, "rust", &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_try_parse_go_color() {
+        assert_eq!(
+            try_parse_go_color("color.RGBA{R: 255, G: 0, B: 0, A: 255}"),
+            Ok(Color::new(1., 0., 0., 1.))
+        );
+        assert_eq!(
+            try_parse_go_color("color.RGBA{A: 255, B: 0, G: 0, R: 255}"),
+            Ok(Color::new(1., 0., 0., 1.))
+        );
+        assert_eq!(
+            try_parse_go_color("color.NRGBA{R: 0xff, G: 0x00, B: 0x00, A: 0x80}")
+                .unwrap()
+                .to_rgba8(),
+            [255, 0, 0, 128]
+        );
+        // Premultiplied channels are divided by the alpha
+        assert_eq!(
+            try_parse_go_color("color.RGBA{R: 0x80, A: 0x80}")
+                .unwrap()
+                .to_rgba8(),
+            [255, 0, 0, 128]
+        );
+        assert_eq!(
+            try_parse_go_color("color.NRGBA{R: 255, G: 128}")
+                .unwrap()
+                .to_rgba8(),
+            [255, 128, 0, 0]
+        );
+        assert_eq!(
+            try_parse_go_color("color.RGBA{}"),
+            Ok(Color::new(0., 0., 0., 0.))
+        );
+        assert!(try_parse_go_color("color.RGBA{R: 256}").is_err());
+        assert!(try_parse_go_color("color.RGBA{X: 1}").is_err());
+        assert!(try_parse_go_color("color.RGBA{255, 0, 0, 255}").is_err());
+
+        let text = "c := color.NRGBA{R: 0xff, G: 0x88, A: 0xff}";
+        assert_eq!(
+            parse(text),
+            vec![ColorNode::new(
+                "color.NRGBA{R: 0xff, G: 0x88, A: 0xff}",
+                Color::from_rgba8(0xff, 0x88, 0, 0xff),
+                1,
+                6
+            )]
+        );
+        let config = Config {
+            enable_go_color: false,
+            ..Default::default()
+        };
+        assert!(parse_document(text, "go", &config).is_empty());
+    }
+
+    #[test]
+    fn test_formats_examples() {
+        let config = Config {
+            enable_android_argb: true,
+            ..Config::default()
+        };
+        let registries = std::iter::once(ColorPatternRegistry::builtin("", &config)).chain(
+            PATTERN_LANGUAGES
+                .iter()
+                .map(|language_id| ColorPatternRegistry::for_document(language_id, "", &config)),
+        );
+        for registry in registries {
+            for pattern in &registry.patterns {
+                for format in pattern.formats() {
+                    let node = pattern.try_match(format.example, 1, 1);
+                    assert!(
+                        node.is_some(),
+                        "{} example: {}",
+                        format.name,
+                        format.example
+                    );
                 }
             }
+        }
+    }
+
+    #[test]
+    fn test_supported_formats() {
+        let formats = supported_formats();
+        let languages = |name: &str| {
+            formats
+                .iter()
+                .find(|(format, _)| format.name == name)
+                .map(|(_, languages)| languages.clone())
+        };
+        assert_eq!(languages("hex6"), Some(vec![]));
+        assert_eq!(languages("argb8"), Some(vec![]));
+        assert_eq!(languages("godot"), Some(vec!["gdscript"]));
+        assert_eq!(languages("three"), Some(vec!["javascript", "typescript"]));
+        assert_eq!(languages("roblox"), Some(vec!["luau"]));
+        assert_eq!(languages("nope"), None);
+
+        let mut names = formats
+            .iter()
+            .map(|(format, _)| format.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), formats.len());
+    }
+
+    #[test]
+    fn test_parse_haskell_color() {
+        assert_eq!(
+            try_parse_haskell_color("sRGB24 255 128 0"),
+            Ok(Color::from_rgba8(255, 128, 0, 255))
+        );
+        assert_eq!(
+            try_parse_haskell_color("sRGB 0.5 0.2 0.8"),
+            Ok(Color::new(0.5, 0.2, 0.8, 1.))
+        );
+        assert_eq!(
+            try_parse_haskell_color("Colour.lightcoral")
+                .unwrap()
+                .to_css_hex(),
+            "#f08080"
+        );
+        assert!(try_parse_haskell_color("sRGB24 256 0 0").is_err());
+        assert!(try_parse_haskell_color("sRGB 1.5 0 0").is_err());
+        assert!(try_parse_haskell_color("sRGB 0.5 0.2").is_err());
+        assert!(try_parse_haskell_color("Colour.nope").is_err());
+
+        let text = "orange = sRGB24 255 128 0\n\
+            purple = sRGB 0.5 0.2 0.8 :: Colour Double\n\
+            coral = Colour.lightcoral `withOpacity` 0.5\n\
+            nope = sRGB 0.5,0.2,0.8";
+        assert_eq!(
+            parse_document(text, "haskell", &Config::default()),
+            vec![
+                ColorNode::new(
+                    "sRGB24 255 128 0",
+                    Color::from_rgba8(255, 128, 0, 255),
+                    1,
+                    10
+                ),
+                ColorNode::new("sRGB 0.5 0.2 0.8", Color::new(0.5, 0.2, 0.8, 1.), 2, 10),
+                ColorNode::new(
+                    "Colour.lightcoral",
+                    Color::from_rgba8(240, 128, 128, 255),
+                    3,
+                    9
+                ),
+            ]
+        );
+        assert!(parse_document("sRGB24 255 128 0", "rust", &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_godot_color() {
+        assert_eq!(
+            try_parse_godot_color("Color(0.5, 0.2, 0.8, 0.5)"),
+            Ok(Color::new(0.5, 0.2, 0.8, 0.5))
+        );
+        assert_eq!(
+            try_parse_godot_color("Color(1, 0, 0)"),
+            Ok(Color::new(1., 0., 0., 1.))
+        );
+        assert_eq!(
+            try_parse_godot_color("Color(\"#ff5722\")")
+                .unwrap()
+                .to_css_hex(),
+            "#ff5722"
+        );
+        assert_eq!(
+            try_parse_godot_color("Color('ff572280')")
+                .unwrap()
+                .to_css_hex(),
+            "#ff572280"
+        );
+        assert_eq!(
+            try_parse_godot_color("Color(\"dark slate blue\")")
+                .unwrap()
+                .to_css_hex(),
+            "#483d8b"
+        );
+        assert_eq!(
+            try_parse_godot_color("Color.DARK_SLATE_BLUE")
+                .unwrap()
+                .to_css_hex(),
+            "#483d8b"
+        );
+        assert_eq!(
+            try_parse_godot_color("Color.GREEN").unwrap().to_css_hex(),
+            "#00ff00"
+        );
+        assert_eq!(
+            try_parse_godot_color("Color.WEB_GREEN")
+                .unwrap()
+                .to_css_hex(),
+            "#008000"
+        );
+        assert_eq!(
+            try_parse_godot_color("Color.TRANSPARENT"),
+            Ok(Color::new(1., 1., 1., 0.))
+        );
+        assert!(try_parse_godot_color("Color.red").is_err());
+        assert!(try_parse_godot_color("Color.NOPE").is_err());
+        assert!(try_parse_godot_color("Color(2.0, 0, 0)").is_err());
+        assert!(try_parse_godot_color("Color(0.5, 0.5)").is_err());
+
+        let text = "var a = Color(0.5, 0.2, 0.8, 1.0)\nvar b := Color.RED.lightened(0.2)\n\
+            var c = Color(\"#ff5722\")\nvar d = Color.from_hsv(0.5, 1, 1)";
+        assert_eq!(
+            parse_document(text, "gdscript", &Config::default()),
+            vec![
+                ColorNode::new(
+                    "Color(0.5, 0.2, 0.8, 1.0)",
+                    Color::new(0.5, 0.2, 0.8, 1.),
+                    1,
+                    9
+                ),
+                ColorNode::new("Color.RED", Color::new(1., 0., 0., 1.), 2, 10),
+                ColorNode::new(
+                    "Color(\"#ff5722\")",
+                    Color::from_rgba8(255, 87, 34, 255),
+                    3,
+                    9
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_nix_color() {
+        assert_eq!(
+            try_parse_nix_color("{ r = 1.0; g = 0.5; b = 0.0; a = 1.0; }"),
+            Ok(Color::new(1., 0.5, 0., 1.))
+        );
+        assert_eq!(
+            try_parse_nix_color("{b=0;g=1;r=0.2;}"),
+            Ok(Color::new(0.2, 1., 0., 1.))
+        );
+        assert!(try_parse_nix_color("{ r = 1.0; g = 0.5; }").is_err());
+        assert!(try_parse_nix_color("{ r = 255; g = 0; b = 0; }").is_err());
+        assert!(try_parse_nix_color("{ r = 1.0; g = 0.5; b = 0.0; x = 1; }").is_err());
+
+        let node = ColorNode::must_parse("rgb(255 128 0 / 50%)", 1, 1);
+        assert_eq!(
+            node.to_nix_attrset(),
+            "{ r = 1.0; g = 0.502; b = 0.0; a = 0.5; }"
+        );
+        assert_eq!(
+            try_parse_nix_color(&node.to_nix_attrset()).map(|color| color.to_rgba8()),
+            Ok(node.color.to_rgba8())
+        );
+
+        let text =
+            "{\n  accent = { r = 1.0; g = 0.5; b = 0.0; a = 1.0; };\n  attrs = { r = 1; };\n}";
+        assert_eq!(
+            parse_document(text, "nix", &Config::default()),
+            vec![ColorNode::new(
+                "{ r = 1.0; g = 0.5; b = 0.0; a = 1.0; }",
+                Color::new(1., 0.5, 0., 1.),
+                2,
+                12
+            )]
+        );
+        assert!(parse_document(text, "plaintext", &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_tkinter() {
+        assert_eq!(try_parse_tk_color("red"), Ok(Color::new(1., 0., 0., 1.)));
+        assert_eq!(
+            try_parse_tk_color("LightBlue"),
+            try_parse_tk_color("light blue")
+        );
+        assert_eq!(
+            try_parse_tk_color("gray75").unwrap().to_css_hex(),
+            "#bfbfbf"
+        );
+        assert_eq!(
+            try_parse_tk_color("grey100").unwrap().to_css_hex(),
+            "#ffffff"
+        );
+        assert_eq!(try_parse_tk_color("gray").unwrap().to_css_hex(), "#808080");
+        assert!(try_parse_tk_color("gray101").is_err());
+        assert!(try_parse_tk_color("rgb(1, 2, 3)").is_err());
+
+        let text = "label = tk.Label(root, text=\"red\", bg=\"gray75\", fg='#ff0000')\n\
+            canvas.create_rectangle(0, 0, 10, 10, fill=\"light blue\", outline=\"nope\")";
+        assert_eq!(
+            parse_document(text, "python", &Config::default()),
+            vec![
+                ColorNode::new("gray75", Color::from_rgba8(191, 191, 191, 255), 1, 40),
+                ColorNode::must_parse("#ff0000", 1, 53),
+                ColorNode::new("light blue", Color::from_rgba8(173, 216, 230, 255), 2, 45),
+            ]
+        );
+        assert!(parse(r#"Label(root, bg="gray75")"#).is_empty());
+    }
+
+    #[test]
+    fn test_try_parse_uicolor() {
+        assert_eq!(
+            try_parse_uicolor("UIColor(red: 1.0, green: 0.5, blue: 0.0, alpha: 0.5)"),
+            Ok(Color::new(1., 0.5, 0., 0.5))
+        );
+        assert_eq!(
+            try_parse_uicolor("UIColor(alpha: 1, blue: 0.1, red: 0.5, green: 0.3)"),
+            Ok(Color::new(0.5, 0.3, 0.1, 1.))
+        );
+        assert_eq!(
+            try_parse_uicolor("UIColor(white: 0.5, alpha: 1.0)"),
+            Ok(Color::new(0.5, 0.5, 0.5, 1.))
+        );
+        assert!(try_parse_uicolor("UIColor(red: 255, green: 0, blue: 0, alpha: 1)").is_err());
+        assert!(try_parse_uicolor("UIColor(red: 1, green: 0, alpha: 1)").is_err());
+        assert!(try_parse_uicolor("UIColor(red: 1, green: 0, blue: 0, white: 1)").is_err());
+
+        let text = "view.backgroundColor = UIColor(white: 0.5, alpha: 1.0)";
+        let colors = parse(text);
+        assert_eq!(
+            colors,
+            vec![ColorNode::new(
+                "UIColor(white: 0.5, alpha: 1.0)",
+                Color::new(0.5, 0.5, 0.5, 1.),
+                1,
+                24
+            )]
+        );
+        let config = Config {
+            enable_uicolor: false,
+            ..Default::default()
+        };
+        assert!(parse_document(text, "swift", &config).is_empty());
+    }
+
+    #[test]
+    fn test_parse_zig_hex_literals() {
+        let text = "const accent = 0xFF5722;\nconst overlay: u32 = 0x80_FF_00_FF;\nconst orange = 0xFF_88_00;\nconst big = 0xFF_FF_FF_FF_FF;";
+        let colors = parse_document(text, "zig", &Config::default());
+        assert_eq!(
+            colors,
+            vec![
+                ColorNode::new("0xFF5722", Color::from_rgba8(0xff, 0x57, 0x22, 0xff), 1, 16),
+                ColorNode::new(
+                    "0x80_FF_00_FF",
+                    Color::from_rgba8(0xff, 0, 0xff, 0x80),
+                    2,
+                    22
+                ),
+                ColorNode::new("0xFF_88_00", Color::from_rgba8(0xff, 0x88, 0, 0xff), 3, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_0x_color_with_digit_separators() {
+        let text = "const A: u32 = 0xFF_00_FF;\nconst B: u32 = 0xFF_00_00_80u32;\nconst C: u32 = 0xFF_GG_00;";
+        let colors = parse_document(text, "rust", &Config::default());
+        assert_eq!(colors.len(), 2);
+        assert_eq!(
+            colors[0],
+            ColorNode::new("0xFF_00_FF", Color::from_rgba8(0xff, 0, 0xff, 0xff), 1, 16)
+        );
+        assert_eq!(
+            colors[1],
+            ColorNode::new("0xFF_00_00_80", Color::from_rgba8(0, 0, 0x80, 0xff), 2, 16)
+        );
+
+        // Digit separators are only allowed in Rust
+        assert!(parse_document(text, "javascript", &Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_rgb_255_tuple() {
+        let node = ColorNode::from_rgb_255_tuple(255, 128, 0, 128);
+        assert_eq!(node.matched, "#ff800080");
+        assert_eq!(node.to_rgb_255_tuple(), (255, 128, 0, 128));
+        assert_eq!(
+            ColorNode::from_rgb_255_tuple(1, 2, 3, 255).matched,
+            "#010203"
+        );
+
+        // Channels out of gamut are clamped, others are rounded
+        let node = ColorNode::new("", Color::new(1.2, -0.1, 0.5, 0.999), 1, 1);
+        assert_eq!(node.to_rgb_255_tuple(), (255, 0, 128, 255));
+    }
+
+    #[test]
+    fn test_css_filter_string() {
+        assert_eq!(
+            ColorNode::must_parse("#ff8800", 1, 1).css_filter_string(),
+            "rgba(255, 136, 0, 1)"
+        );
+        assert_eq!(
+            ColorNode::must_parse("hsla(0, 100%, 50%, 0.25)", 1, 1).css_filter_string(),
+            "rgba(255, 0, 0, 0.25)"
+        );
+    }
+
+    #[test]
+    fn test_css_property_name() {
+        let line = "  filter: drop-shadow(0 0 10px #000); color: #fff";
+        assert_eq!(css_property_name(line, 31), Some("filter"));
+        assert_eq!(css_property_name(line, 46), Some("color"));
+        assert_eq!(css_property_name("a { color: red }", 11), Some("color"));
+        assert_eq!(css_property_name("let a = #fff;", 8), None);
+        assert_eq!(css_property_name("  \"a b\": \"#fff\"", 9), None);
+    }
+
+    #[test]
+    fn test_parse_with_registry() {
+        struct BrandPattern;
+
+        impl ColorPattern for BrandPattern {
+            fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+                text.starts_with("$brand")
+                    .then(|| ColorNode::new("$brand", Color::new(1., 0., 0., 1.), line, col))
+            }
+        }
+
+        let text = "a { color: $brand; background: #fff; }\nb { color: $brandy; }";
+        let mut registry = ColorPatternRegistry::new();
+        registry.register(BrandPattern);
+        assert_eq!(
+            parse_with_registry(text, &registry),
+            vec![
+                ColorNode::new("$brand", Color::new(1., 0., 0., 1.), 1, 12),
+                ColorNode::new("$brand", Color::new(1., 0., 0., 1.), 2, 12),
+            ]
+        );
+
+        registry.register(ColorPatternRegistry::builtin("css", &Config::default()));
+        let colors = parse_with_registry(text, &registry);
+        assert_eq!(colors.len(), 3);
+        assert_eq!(colors[1], ColorNode::must_parse("#fff", 1, 32));
+
+        assert!(parse_with_registry(text, &ColorPatternRegistry::new()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_multibyte() {
+        let colors = parse("// 颜色 #ff0000 和 rgb(0, 0, 255)\nabc#fff");
+        assert_eq!(colors.len(), 2);
+        assert_eq!(colors[0], ColorNode::must_parse("#ff0000", 1, 11));
+        assert_eq!(colors[1], ColorNode::must_parse("rgb(0, 0, 255)", 1, 23));
+
+        // The LSP positions are in UTF-16 code units
+        let range = |node: &ColorNode| {
+            let range = node.lsp_range();
+            (range.start.line, range.start.character, range.end.character)
+        };
+        assert_eq!(range(&colors[0]), (0, 6, 13));
+        assert_eq!(range(&colors[1]), (0, 16, 30));
+        assert_eq!(range(&parse("a { color: /* 🎨 */ #fff; }")[0]), (0, 20, 24));
+    }
+
+    #[test]
+    fn test_parse_tailwind() {
+        let text =
+            r#"<div class="bg-[#ff0000] hover:text-[rgb(255,0,0)] border-[hsl(240,100%,50%)]">"#;
+        let colors = parse(text);
+        assert_eq!(
+            colors,
+            vec![
+                ColorNode::new("[#ff0000]", Color::new(1., 0., 0., 1.), 1, 16),
+                ColorNode::new("[rgb(255,0,0)]", Color::new(1., 0., 0., 1.), 1, 37),
+                ColorNode::new("[hsl(240,100%,50%)]", Color::new(0., 0., 1., 1.), 1, 59),
+            ]
+        );
+
+        assert_eq!(
+            parse("bg-[rgb(255_0_0_/_50%)]"),
+            vec![ColorNode::new(
+                "[rgb(255_0_0_/_50%)]",
+                Color::new(1., 0., 0., 0.5),
+                1,
+                4
+            )]
+        );
+
+        assert!(parse("text-[14px] bg-[url(/a.png)] bg-[var(--brand)] w-[rgb(1,2)]").is_empty());
+        assert_eq!(parse("text-red-500 'bg-[#fff]'")[0].range.col, 18);
+    }
+
+    #[test]
+    fn test_parse_tailwind_theme() {
+        let text =
+            r#"a { background: theme('colors.blue.500'); color: theme("colors.white / 50%"); }"#;
+        assert_eq!(
+            parse(text),
+            vec![
+                ColorNode::new(
+                    "theme('colors.blue.500')",
+                    Color::from_rgba8(0x3b, 0x82, 0xf6, 255),
+                    1,
+                    17
+                ),
+                ColorNode::new(
+                    r#"theme("colors.white / 50%")"#,
+                    Color::new(1., 1., 1., 0.5),
+                    1,
+                    50
+                ),
+            ]
+        );
+        assert_eq!(
+            parse("theme(colors.slate.950)")[0].color.to_css_hex(),
+            "#020617"
+        );
+        assert!(
+            parse("theme('spacing.4') theme('colors.blue.550') theme('colors.red.500 / 2')")
+                .is_empty()
+        );
 
-            offset += 1;
-        }
+        assert_eq!(
+            tailwind_theme_path(&Color::from_rgba8(0xef, 0x44, 0x44, 255)).as_deref(),
+            Some("colors.red.500")
+        );
+        assert_eq!(tailwind_theme_path(&Color::new(0.1, 0.2, 0.3, 1.)), None);
     }
 
-    nodes
-}
+    #[test]
+    fn test_parse_glsl_uniform() {
+        let text = "uniform vec4 uColor = vec4(0.5, 0.2, 0.8, 1.0);\n\
+            layout(location = 0) uniform highp vec3 uTint = vec3(1.0, 0.5, 0.0);\n\
+            uniform vec4 uSize = vec4(10.0, 20.0, 0.0, 1.0);\n\
+            uniform vec3 uMix = vec4(1.0, 0.5, 0.0, 1.0);\n\
+            vec4 color = vec4(0.5, 0.2, 0.8, 1.0);";
+        let colors = parse_document(text, "glsl", &Config::default());
+        assert_eq!(
+            colors,
+            vec![
+                ColorNode::new(
+                    "vec4(0.5, 0.2, 0.8, 1.0)",
+                    Color::new(0.5, 0.2, 0.8, 1.0),
+                    1,
+                    23
+                ),
+                ColorNode::new("vec3(1.0, 0.5, 0.0)", Color::new(1.0, 0.5, 0.0, 1.0), 2, 49),
+            ]
+        );
 
-fn match_color(part: &str, line_ix: usize, offset: usize) -> Option<ColorNode> {
-    if let Ok(color) = try_parse_color(part) {
-        Some(ColorNode::new(part, color, line_ix + 1, offset + 1))
-    } else {
-        None
+        assert!(parse_document(text, "c", &Config::default()).is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use csscolorparser::Color;
+    #[test]
+    fn test_is_near() {
+        let red = ColorNode::must_parse("#ff0000", 1, 1);
+        assert!(red.is_near(
+            &ColorNode::must_parse("#fe0000", 1, 1),
+            DEFAULT_NEAR_THRESHOLD
+        ));
+        assert!(red.is_near(&ColorNode::must_parse("rgb(255, 0, 0)", 1, 1), 0.001));
+        assert!(!red.is_near(
+            &ColorNode::must_parse("#0000ff", 1, 1),
+            DEFAULT_NEAR_THRESHOLD
+        ));
+        assert!(!red.is_near(
+            &ColorNode::must_parse("#ff4000", 1, 1),
+            DEFAULT_NEAR_THRESHOLD
+        ));
+    }
+
+    #[test]
+    fn test_try_parse_flutter_color() {
+        assert_eq!(
+            try_parse_flutter_color("Color(0xFF0000FF)"),
+            Ok(Color::new(0., 0., 1., 1.))
+        );
+        assert_eq!(
+            try_parse_flutter_color("Color(0x80112233)"),
+            Ok(Color::from_rgba8(0x11, 0x22, 0x33, 0x80))
+        );
+        assert_eq!(
+            try_parse_flutter_color("Color.fromARGB(128, 255, 0, 0)"),
+            Ok(Color::from_rgba8(255, 0, 0, 128))
+        );
+        assert_eq!(
+            try_parse_flutter_color("Color.fromRGBO(255, 0, 0, 0.5)"),
+            Ok(Color::new(1., 0., 0., 0.5))
+        );
+        assert!(try_parse_flutter_color("Color(0xFF00FF)").is_err());
+        assert!(try_parse_flutter_color("Color.fromARGB(255, 256, 0, 0)").is_err());
+        assert!(try_parse_flutter_color("Color.fromRGBO(255, 0, 0, 2)").is_err());
+        assert!(try_parse_flutter_color("Colors.fromARGB(255, 0, 0, 0)").is_err());
 
-    use crate::parser::{match_color, parse, try_parse_gpui_color, ColorNode};
+        let text = "const a = Color(0xFF112233);\nfinal b = Color.fromARGB(255, 17, 34, 51);\nfinal c = Color.fromRGBO(17, 34, 51, 1.0);";
+        assert!(parse_document(text, "rust", &Config::default())
+            .iter()
+            .all(|node| !node.matched.starts_with("Color")));
+        let colors = parse_document(text, "dart", &Config::default());
+        assert_eq!(colors.len(), 3);
+        assert_eq!(
+            colors[0],
+            ColorNode::new(
+                "Color(0xFF112233)",
+                Color::from_rgba8(17, 34, 51, 255),
+                1,
+                11
+            )
+        );
+        assert_eq!(
+            colors[1],
+            ColorNode::new(
+                "Color.fromARGB(255, 17, 34, 51)",
+                Color::from_rgba8(17, 34, 51, 255),
+                2,
+                11
+            )
+        );
+        assert_eq!(
+            colors[2],
+            ColorNode::new(
+                "Color.fromRGBO(17, 34, 51, 1.0)",
+                Color::from_rgba8(17, 34, 51, 255),
+                3,
+                11
+            )
+        );
+    }
 
     #[test]
-    fn test_match_color() {
-        let cases = vec![
-            "#A0F0F0",
-            "#2eC8f1",
-            "#AAF0F0aa",
-            "#AAF0F033",
-            "#0f0E",
-            "#F2c",
-            "rgb(80%,80%,20%)",
-            "rgb(255 100 0)",
-            "rgba(255, 0, 0, 0.5)",
-            "rgb(100, 200, 100)",
-            "hsl(225, 100%, 70%)",
-            "hsla(20, 100%, 50%, .5)",
-            "hsla(1., 0.5, 0.5, 1.)",
-        ];
+    fn test_parse_three_color() {
+        let text = "const a = new THREE.Color(0xff5722);\nconst b = new THREE.Color(1.0, 0.5, 0.0);\nconst c = new THREE.Color(255, 0, 0);";
+        let colors = parse_document(text, "typescript", &Config::default());
+        assert_eq!(colors.len(), 2);
+        assert_eq!(
+            colors[0],
+            ColorNode::new(
+                "THREE.Color(0xff5722)",
+                Color::from_rgba8(0xff, 0x57, 0x22, 255),
+                1,
+                15
+            )
+        );
+        assert_eq!(
+            colors[1],
+            ColorNode::new(
+                "THREE.Color(1.0, 0.5, 0.0)",
+                Color::new(1., 0.5, 0., 1.),
+                2,
+                15
+            )
+        );
 
-        for case in cases {
-            assert!(match_color(case, 1, 1).is_some());
-        }
+        let colors = parse_document(text, "rust", &Config::default());
+        assert_eq!(colors.len(), 1);
+        assert_eq!(colors[0].matched, "0xff5722");
+    }
 
+    #[test]
+    fn test_hue() {
+        let hue = |s| ColorNode::must_parse(s, 1, 1).hue();
+        assert_eq!(hue("#ff0000"), 0.);
+        assert_eq!(hue("#00ff00"), 120.);
+        assert_eq!(hue("#0000ff"), 240.);
+        assert!((hue("hsl(359.5, 100%, 50%)") - 359.5).abs() < 0.5);
+        assert_eq!(hue("#808080"), 0.);
+        assert_eq!(hue("hsl(-90, 100%, 50%)"), 270.);
+    }
+
+    #[test]
+    fn test_tetradic() {
+        let tetradic = |s| {
+            ColorNode::must_parse(s, 1, 1)
+                .tetradic()
+                .map(|color| color.to_css_hex())
+        };
+        assert_eq!(tetradic("#ff0000"), ["#80ff00", "#00ffff", "#8000ff"]);
         assert_eq!(
-            match_color("#e7b911", 1, 10),
-            Some(ColorNode::must_parse("#e7b911", 2, 11))
+            tetradic("hsl(300 50% 40% / 50%)"),
+            ["#99663380", "#33993380", "#33669980"]
         );
+        assert_eq!(tetradic("#808080"), ["#808080", "#808080", "#808080"]);
     }
 
     #[test]
-    fn test_try_parse_gpui_color() {
+    fn test_to_p3_values() {
+        let node = |color| ColorNode::new("", color, 1, 1);
+        let close = |(r, g, b): (f32, f32, f32), [r2, g2, b2]: [f32; 3]| {
+            (r - r2).abs() < 1e-3 && (g - g2).abs() < 1e-3 && (b - b2).abs() < 1e-3
+        };
+
+        let red = ColorNode::must_parse("#ff0000", 1, 1)
+            .to_p3_values()
+            .unwrap();
+        assert!(close(red, [0.9175, 0.2003, 0.1386]), "{red:?}");
+        let white = node(Color::new(1., 1., 1., 1.)).to_p3_values().unwrap();
+        assert!(close(white, [1., 1., 1.]), "{white:?}");
+        assert_eq!(node(Color::new(1.5, 0., 0., 1.)).to_p3_values(), None);
+    }
+
+    #[test]
+    fn test_to_p3_gamut_warning() {
+        let node = |color: Color| ColorNode::new("", color, 1, 1);
+
         assert_eq!(
-            try_parse_gpui_color("rgb(0., 1., 0.2)"),
-            Ok(Color {
-                r: 0.,
-                g: 1.,
-                b: 0.2,
-                a: 1.
-            })
+            ColorNode::must_parse("#ff0000", 1, 1).to_p3_gamut_warning(),
+            None
         );
+        assert_eq!(node(Color::new(0., 1., 1., 1.)).to_p3_gamut_warning(), None);
         assert_eq!(
-            try_parse_gpui_color("rgb(0., 1., 0., 0.45)"),
-            Ok(Color {
-                r: 0.,
-                g: 1.,
-                b: 0.,
-                a: 0.45
-            })
+            node(Color::new(1.05, 0., 0., 1.)).to_p3_gamut_warning(),
+            Some("Extended sRGB, within P3 gamut".to_string())
         );
-        assert!(try_parse_gpui_color("rgb(255., 220.0, 0.)").is_err());
-        assert!(try_parse_gpui_color("rgba(255., 120., 20.0, 1.)").is_err());
+        assert_eq!(
+            node(Color::new(-0.1, 1., -0.05, 1.)).to_p3_gamut_warning(),
+            Some("Extended sRGB, within P3 gamut".to_string())
+        );
+        assert_eq!(
+            node(Color::new(1.5, 0., 0., 1.)).to_p3_gamut_warning(),
+            Some("Out of P3 gamut".to_string())
+        );
+        assert_eq!(
+            node(Color::new(-0.8, 1., 0., 1.)).to_p3_gamut_warning(),
+            Some("Out of P3 gamut".to_string())
+        );
+    }
+
+    #[test]
+    fn test_try_parse_color_mix() {
+        let mix = |s: &str| try_parse_color_mix(s).unwrap().to_array();
+        let approx_eq =
+            |a: [f32; 4], b: [f32; 4]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-4);
+
+        assert!(approx_eq(
+            mix("color-mix(in srgb, #ff0000 50%, #0000ff 50%)"),
+            [0.5, 0., 0.5, 1.]
+        ));
+        assert!(approx_eq(
+            mix("color-mix(in srgb, #ff0000 30%, #0000ff 70%)"),
+            [0.3, 0., 0.7, 1.]
+        ));
+        assert!(approx_eq(
+            mix("color-mix(in srgb, #ff0000 30%, rgb(0, 0, 255))"),
+            [0.3, 0., 0.7, 1.]
+        ));
+        assert!(approx_eq(
+            mix("color-mix(in srgb, 30% #ff0000, #0000ff)"),
+            [0.3, 0., 0.7, 1.]
+        ));
+        assert!(approx_eq(
+            mix("color-mix(in srgb, #ff0000, #0000ff)"),
+            [0.5, 0., 0.5, 1.]
+        ));
+        // Normalized to 100%, the sum less than 100% makes it transparent.
+        assert!(approx_eq(
+            mix("color-mix(in srgb, #ff0000 60%, #0000ff 60%)"),
+            [0.5, 0., 0.5, 1.]
+        ));
+        assert!(approx_eq(
+            mix("color-mix(in srgb, #ff0000 20%, #0000ff 20%)"),
+            [0.5, 0., 0.5, 0.4]
+        ));
 
+        let red = Color::new(1., 0., 0., 1.);
+        let blue = Color::new(0., 0., 1., 1.);
+        assert!(approx_eq(
+            mix("color-mix(in oklab, red 30%, blue 70%)"),
+            red.interpolate_oklab(&blue, 0.7).to_array()
+        ));
+        assert!(approx_eq(
+            mix("color-mix(in hsl, hsl(350 100% 50%), hsl(10 100% 50%))"),
+            Color::from_hsla(0., 1., 0.5, 1.).to_array()
+        ));
+
+        assert!(try_parse_color_mix("color-mix(in foo, red, blue)").is_err());
+        assert!(try_parse_color_mix("color-mix(srgb, red, blue)").is_err());
+        assert!(try_parse_color_mix("color-mix(in srgb, red)").is_err());
+
+        let text = "a { color: color-mix(in srgb, rgb(255, 0, 0) 30%, #0000ff); }";
+        let colors = parse(text);
+        assert_eq!(colors.len(), 1);
         assert_eq!(
-            try_parse_gpui_color("hsl(0.48, 1., 0.45)"),
-            Ok(Color::new(0., 0.9, 0.79200006, 1.))
+            colors[0].matched,
+            "color-mix(in srgb, rgb(255, 0, 0) 30%, #0000ff)"
         );
+        assert_eq!(colors[0].range, TextRange::new(1, 12, 47));
+    }
+
+    #[test]
+    fn test_contrast_ratio() {
+        let white = ColorNode::must_parse("#ffffff", 1, 1);
+        let black = ColorNode::must_parse("#000000", 1, 1);
+        let gray = ColorNode::must_parse("#777777", 1, 1);
+
+        assert_eq!(white.relative_luminance(), 1.0);
+        assert_eq!(black.relative_luminance(), 0.0);
+        assert_eq!(white.contrast_ratio_with(&black), 21.0);
+        assert_eq!(black.contrast_ratio_with(&white), 21.0);
+        assert_eq!(gray.contrast_ratio_with(&gray), 1.0);
+        assert!((gray.contrast_ratio_with(&white) - 4.48).abs() < 0.01);
+        let light_gray = ColorNode::must_parse("#d3d3d3", 1, 1);
+        assert!((light_gray.contrast_ratio_with(&white) - 1.5).abs() < 0.01);
+
+        assert!(wcag_aa_passes(&black, &white, false));
+        assert!(!wcag_aa_passes(&gray, &white, false));
+        assert!(wcag_aa_passes(&gray, &white, true));
+        assert!(!wcag_aa_passes(&light_gray, &white, true));
+    }
+
+    #[test]
+    fn test_parse_css_layers() {
+        let text = r#"@layer reset, base;
+a { color: #000; }
+@layer base {
+  a { color: #111; }
+  @layer theme {
+    b { color: #222; }
+  }
+  c { color: #333; }
+}
+@layer { d { color: #444; } }
+e { color: #555; }
+@layer utilities
+{
+  f { color: #666; }
+}"#;
+        let layers = parse_document(text, "css", &Config::default())
+            .into_iter()
+            .map(|node| node.layer)
+            .collect::<Vec<_>>();
         assert_eq!(
-            try_parse_gpui_color("hsla(0.48, 1., 0.45, 0.3)"),
-            Ok(Color::new(0., 0.9, 0.79200006, 0.3))
+            layers,
+            vec![
+                None,
+                Some("base".to_string()),
+                Some("base.theme".to_string()),
+                Some("base".to_string()),
+                None,
+                None,
+                Some("utilities".to_string()),
+            ]
         );
-        assert!(try_parse_gpui_color("hsl(240., 0., 50.0)").is_err());
-        assert!(try_parse_gpui_color("hsla(240., 0., 50.0, 1.)").is_err());
+        assert!(parse_document(text, "html", &Config::default())
+            .iter()
+            .all(|node| node.layer.is_none()));
     }
 
     #[test]
-    fn test_must_parse() {
+    fn test_format_like() {
+        let format_like = |matched: &str, color: &str| {
+            let value = matched.trim_matches(['[', ']']).replace('_', " ");
+            let original = try_parse_color(&value)
+                .or_else(|_| parse_0x_color(matched))
+                .unwrap();
+            ColorNode::new(matched, original, 1, 1)
+                .format_like(&csscolorparser::parse(color).unwrap())
+                .unwrap_or_default()
+        };
+
+        assert_eq!(format_like("#fff", "#ff8800"), "#ff8800");
+        assert_eq!(format_like("#FFF", "#ff8800"), "#FF8800");
+        assert_eq!(format_like("#fff", "#ff880080"), "#ff880080");
+        assert_eq!(format_like("red", "#ff8800"), "#ff8800");
+        assert_eq!(format_like("rgb(0, 0, 0)", "#ff8800"), "rgb(255, 136, 0)");
+        assert_eq!(format_like("rgb(0 0 0)", "#ff8800"), "rgb(255 136 0)");
         assert_eq!(
-            ColorNode::must_parse("hsla(.2, 0.5, 0.5, 1.)", 10, 12),
-            ColorNode {
-                matched: "hsla(.2, 0.5, 0.5, 1.)".to_string(),
-                color: Color::from_hsla(0.2 * 360., 0.5, 0.5, 1.),
-                loc: (10, 12)
-            }
+            format_like("rgb(0, 0, 0)", "#ff000080"),
+            "rgba(255, 0, 0, 0.5)"
+        );
+        assert_eq!(
+            format_like("rgba(0, 0, 0, 0.5)", "#ff0000"),
+            "rgba(255, 0, 0, 1)"
+        );
+        assert_eq!(format_like("rgb(0 0 0)", "#ff000080"), "rgb(255 0 0 / 0.5)");
+        assert_eq!(
+            format_like("hsl(0, 0%, 0%)", "#ff8800"),
+            "hsl(32, 100%, 50%)"
+        );
+        assert_eq!(format_like("hsl(0 0% 0%)", "#ff0000"), "hsl(0 100% 50%)");
+        assert_eq!(
+            format_like("hsla(0, 0%, 0%, 1)", "#ff000080"),
+            "hsla(0, 100%, 50%, 0.5)"
+        );
+        assert_eq!(
+            format_like("oklch(0.5 0.1 20)", "#ff0000"),
+            "oklch(0.628 0.258 29.23)"
         );
 
+        // gpui colors keep the 0..1 values
+        assert_eq!(format_like("rgb(0.5, 0.5, 0.5)", "#ff0000"), "rgb(1, 0, 0)");
         assert_eq!(
-            ColorNode::must_parse("rgba(1., 0.5, 0.5, 1.)", 10, 12),
-            ColorNode {
-                matched: "rgba(1., 0.5, 0.5, 1.)".to_string(),
-                color: Color::new(1., 0.5, 0.5, 1.),
-                loc: (10, 12)
-            }
+            format_like("rgba(0.5, 0.5, 0.5, 1.)", "#ff000080"),
+            "rgba(1, 0, 0, 0.5)"
+        );
+        assert_eq!(
+            format_like("hsla(0.5, 1., 0.5, 1.)", "#ff0000"),
+            "hsla(0, 1, 0.5, 1)"
+        );
+        // `0x` hex keeps the prefix, the case and the ARGB order
+        assert_eq!(format_like("0xFF0000", "#ff8800"), "0xFF8800");
+        assert_eq!(format_like("0x80ff0000", "#ff880080"), "0x80ff8800");
+        assert_eq!(format_like("0xff0000", "#ff880080"), "");
+        assert_eq!(
+            format_like("Color(0xFF112233)", "#ff8800"),
+            "Color(0xFFFF8800)"
+        );
+        // Tailwind arbitrary values keep the brackets and `_` for spaces
+        assert_eq!(format_like("[#fff]", "#ff8800"), "[#ff8800]");
+        assert_eq!(format_like("[rgb(0_0_0)]", "#ff8800"), "[rgb(255_136_0)]");
+
+        // Can't be written back
+        assert_eq!(
+            format_like("Color.fromARGB(255, 17, 34, 51)", "#ff8800"),
+            ""
         );
     }
 
     #[test]
-    fn test_parse() {
-        let colors = parse(include_str!("../../tests/test.json"));
+    fn test_css4_syntax() {
+        let css4_syntax = |matched: &str| ColorNode::must_parse(matched, 1, 1).css4_syntax();
 
-        assert_eq!(colors.len(), 8);
-        assert_eq!(colors[0], ColorNode::must_parse("#999", 2, 15));
-        assert_eq!(colors[1], ColorNode::must_parse("#FFFFFF", 3, 18));
-        assert_eq!(colors[2], ColorNode::must_parse("#ff003c99", 4, 13));
-        assert_eq!(colors[3], ColorNode::must_parse("#3cBD00", 5, 15));
         assert_eq!(
-            colors[4],
-            ColorNode::must_parse("rgba(255, 252, 0, 0.5)", 6, 12)
+            css4_syntax("rgba(255, 0, 0, 0.5)"),
+            Some("rgb(255 0 0 / 50%)".to_string())
         );
         assert_eq!(
-            colors[5],
-            ColorNode::must_parse("rgb(100, 200, 100)", 7, 11)
+            css4_syntax("hsla(120, 100%, 50%, 0.8)"),
+            Some("hsl(120 100% 50% / 80%)".to_string())
         );
         assert_eq!(
-            colors[6],
-            ColorNode::must_parse("hsla(20, 100%, 50%, .5)", 8, 12)
+            css4_syntax("rgb(255,0,0)"),
+            Some("rgb(255 0 0)".to_string())
         );
         assert_eq!(
-            colors[7],
-            ColorNode::must_parse("hsl(225, 100%, 70%)", 9, 11)
+            css4_syntax("rgba(255, 0, 0, 25%)"),
+            Some("rgb(255 0 0 / 25%)".to_string())
         );
+        assert_eq!(css4_syntax("rgb(255 0 0 / 50%)"), None);
+        assert_eq!(css4_syntax("#ff0000"), None);
+        assert_eq!(css4_syntax("hwb(0 0% 0%)"), None);
+    }
 
-        let colors = parse(include_str!("../../tests/test.rs"));
-        assert_eq!(colors.len(), 5);
+    #[test]
+    fn test_to_xcassets_json() {
+        let components = |node: &ColorNode| {
+            let json = node.to_xcassets_json();
+            let color = &json["colors"][0]["color"];
+            (
+                color["color-space"].as_str().unwrap().to_string(),
+                color["components"].clone(),
+            )
+        };
+
+        let node = ColorNode::must_parse("#ff8000", 1, 1);
         assert_eq!(
-            colors[0],
-            ColorNode::must_parse("hsla(0.3, 1.0, 0.5, 1.0)", 1, 10)
+            node.to_xcassets_json(),
+            serde_json::json!({
+                "colors": [{
+                    "color": {
+                        "color-space": "srgb",
+                        "components": {
+                            "alpha": "1.000",
+                            "blue": "0.000",
+                            "green": "0.502",
+                            "red": "1.000",
+                        },
+                    },
+                    "idiom": "universal",
+                }],
+                "info": { "author": "xcode", "version": 1 },
+            })
         );
+
+        let node = ColorNode::must_parse("rgba(0, 0, 255, 0.5)", 1, 1);
         assert_eq!(
-            colors[1],
-            ColorNode::must_parse("hsla(0.58, 1.0, 0.5, 1.0)", 2, 10)
+            components(&node),
+            (
+                "srgb".to_string(),
+                serde_json::json!({ "alpha": "0.500", "blue": "1.000", "green": "0.000", "red": "0.000" })
+            )
+        );
+
+        let node = ColorNode::new("", Color::new(1.05, 0., 0., 1.), 1, 1);
+        let (color_space, components) = components(&node);
+        assert_eq!(color_space, "display-p3");
+        assert_eq!(components["red"], "0.964");
+        assert_eq!(components["alpha"], "1.000");
+    }
+
+    #[test]
+    fn test_ciede2000() {
+        // The test data of Sharma, Wu, Dalal, "The CIEDE2000 Color-Difference Formula", 2005
+        #[rustfmt::skip]
+        let pairs = [
+            ([50.0000, 2.6772, -79.7751], [50.0000, 0.0000, -82.7485], 2.0425),
+            ([50.0000, 3.1571, -77.2803], [50.0000, 0.0000, -82.7485], 2.8615),
+            ([50.0000, 2.8361, -74.0200], [50.0000, 0.0000, -82.7485], 3.4412),
+            ([50.0000, -1.3802, -84.2814], [50.0000, 0.0000, -82.7485], 1.0000),
+            ([50.0000, -1.1848, -84.8006], [50.0000, 0.0000, -82.7485], 1.0000),
+            ([50.0000, -0.9009, -85.5211], [50.0000, 0.0000, -82.7485], 1.0000),
+            ([50.0000, 0.0000, 0.0000], [50.0000, -1.0000, 2.0000], 2.3669),
+            ([50.0000, -1.0000, 2.0000], [50.0000, 0.0000, 0.0000], 2.3669),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0009], 7.1792),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0010], 7.1792),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0011], 7.2195),
+            ([50.0000, 2.4900, -0.0010], [50.0000, -2.4900, 0.0012], 7.2195),
+            ([50.0000, -0.0010, 2.4900], [50.0000, 0.0009, -2.4900], 4.8045),
+            ([50.0000, -0.0010, 2.4900], [50.0000, 0.0010, -2.4900], 4.8045),
+            ([50.0000, -0.0010, 2.4900], [50.0000, 0.0011, -2.4900], 4.7461),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 0.0000, -2.5000], 4.3065),
+            ([50.0000, 2.5000, 0.0000], [73.0000, 25.0000, -18.0000], 27.1492),
+            ([50.0000, 2.5000, 0.0000], [61.0000, -5.0000, 29.0000], 22.8977),
+            ([50.0000, 2.5000, 0.0000], [56.0000, -27.0000, -3.0000], 31.9030),
+            ([50.0000, 2.5000, 0.0000], [58.0000, 24.0000, 15.0000], 19.4535),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 3.1736, 0.5854], 1.0000),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 3.2972, 0.0000], 1.0000),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 1.8634, 0.5757], 1.0000),
+            ([50.0000, 2.5000, 0.0000], [50.0000, 3.2592, 0.3350], 1.0000),
+            ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644),
+            ([63.0109, -31.0961, -5.8663], [62.8187, -29.7946, -4.0864], 1.2630),
+            ([61.2901, 3.7196, -5.3901], [61.4292, 2.2480, -4.9620], 1.8731),
+            ([35.0831, -44.1164, 3.7933], [35.0232, -40.0716, 1.5901], 1.8645),
+            ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373),
+            ([36.4612, 47.8580, 18.3852], [36.2715, 50.5065, 21.2231], 1.4146),
+            ([90.8027, -2.0831, 1.4410], [91.1528, -1.6435, 0.0447], 1.4441),
+            ([90.9257, -0.5406, -0.9208], [88.6381, -0.8985, -0.7239], 1.5381),
+            ([6.7747, -0.2908, -2.4247], [5.8714, -0.0985, -2.2286], 0.6377),
+            ([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514], 0.9082),
+        ];
+        for (ix, (lab1, lab2, expected)) in pairs.into_iter().enumerate() {
+            let delta_e = ciede2000_lab(lab1, lab2);
+            assert!(
+                (delta_e - expected).abs() < 0.0001,
+                "pair {}: {delta_e} != {expected}",
+                ix + 1
+            );
+            assert_eq!(delta_e, ciede2000_lab(lab2, lab1));
+        }
+
+        let red = ColorNode::must_parse("#ff0000", 1, 1);
+        let blue = ColorNode::must_parse("blue", 1, 1);
+        assert_eq!(ciede2000_distance(&red, &red), 0.0);
+        assert_eq!(ciede2000_distance(&red, &blue), red.delta_e(&blue));
+        assert!(ciede2000_distance(&red, &blue) > 50.0);
+    }
+
+    #[test]
+    fn test_parse_color_function() {
+        let rgba = |s: &str| {
+            let color = try_parse_color_function(s).unwrap();
+            [color.r, color.g, color.b, color.a].map(|v| (v * 1000.).round() / 1000.)
+        };
+
+        assert_eq!(rgba("color(srgb 1 0.5 0)"), [1., 0.5, 0., 1.]);
+        assert_eq!(rgba("color(srgb 100% 50% 0% / 0.5)"), [1., 0.5, 0., 0.5]);
+        assert_eq!(rgba("color(srgb-linear 1 none 0 / 50%)"), [1., 0., 0., 0.5]);
+        // The wide gamut colors are out of the sRGB range
+        assert_eq!(rgba("color(display-p3 1 0 0)"), [1.093, -0.227, -0.15, 1.]);
+        assert_eq!(
+            try_parse_color_function("color(display-p3 1 0 0)")
+                .unwrap()
+                .to_css_hex(),
+            "#ff0000"
         );
+        assert_eq!(rgba("color(display-p3 0.5 0.5 0.5)"), [0.5, 0.5, 0.5, 1.]);
+        for white in [
+            "color(a98-rgb 1 1 1)",
+            "color(prophoto-rgb 1 1 1)",
+            "color(rec2020 1 1 1)",
+            "color(xyz-d50 0.9642 1 0.8251)",
+            "color(xyz-d65 0.9505 1 1.089)",
+            "color(xyz 0.9505 1 1.089)",
+        ] {
+            assert_eq!(rgba(white), [1., 1., 1., 1.], "{white}");
+        }
+        assert_eq!(rgba("color(rec2020 0 0 0)"), [0., 0., 0., 1.]);
+
+        assert!(try_parse_color_function("color(cmyk 1 0 0)").is_err());
+        assert!(try_parse_color_function("color(srgb 1 0)").is_err());
+        assert!(try_parse_color_function("color(srgb 1 0 x)").is_err());
+        assert!(try_parse_color_function("rgb(1 0 0)").is_err());
+
+        let node = ColorNode::must_parse("color(display-p3 1 0 0)", 1, 1);
         assert_eq!(
-            colors[2],
-            ColorNode::must_parse("hsla(0.85, 0.9, 0.6, 1.0)", 3, 10)
+            node.to_p3_gamut_warning().as_deref(),
+            Some("Extended sRGB, within P3 gamut")
         );
         assert_eq!(
-            colors[3],
-            ColorNode::must_parse("hsla(0.75, 0.9, 0.65, 1.0)", 4, 13)
+            parse("a { color: color(display-p3 0.5 0.5 0.5 / 0.5); }")[0].matched,
+            "color(display-p3 0.5 0.5 0.5 / 0.5)"
         );
+    }
+
+    #[test]
+    fn test_parse_css_property_value() {
+        let matched = |property, value| {
+            parse_css_property_value(property, value)
+                .into_iter()
+                .map(|node| (node.matched, node.range.col))
+                .collect::<Vec<_>>()
+        };
+
         assert_eq!(
-            colors[4],
-            ColorNode::must_parse("hsla(0.45, 0.7, 0.75, 1.0)", 5, 14)
+            parse_css_property_value("border", "1px solid #ff0000"),
+            vec![ColorNode::new("#ff0000", Color::new(1., 0., 0., 1.), 1, 11)]
+        );
+        assert_eq!(
+            matched(
+                "box-shadow",
+                "0 0 4px rgba(0, 0, 0, 0.5), inset 0 1px color-mix(in srgb, red, blue)"
+            ),
+            vec![
+                ("rgba(0, 0, 0, 0.5)".into(), 9),
+                ("color-mix(in srgb, red, blue)".into(), 41),
+            ]
+        );
+        assert_eq!(
+            matched(
+                "background",
+                "url(red.png) no-repeat, linear-gradient(red, #00f 50%) teal"
+            ),
+            vec![("red".into(), 41), ("#00f".into(), 46), ("teal".into(), 56)]
+        );
+        assert_eq!(
+            matched("border-image", "linear-gradient(red, blue) 30"),
+            vec![("red".into(), 17), ("blue".into(), 22)]
+        );
+        assert!(matched("list-style", "linear-gradient(red, blue)").is_empty());
+        assert!(matched("font-family", "red-hat, sans-serif").is_empty());
+        assert!(matched("transition", "color 0.2s ease").is_empty());
+    }
+
+    #[test]
+    fn test_parse_gradient() {
+        let registry = ColorPatternRegistry::builtin("css", &Config::default());
+        let gradient = "linear-gradient(90deg, #ff0000 0%, rgb(0, 0, 255) 50%, blue 100%)";
+        assert_eq!(
+            parse_gradient(gradient, 2, 5, &registry),
+            vec![
+                ColorNode::new("#ff0000", Color::new(1., 0., 0., 1.), 2, 28),
+                ColorNode::new("rgb(0, 0, 255)", Color::new(0., 0., 1., 1.), 2, 40),
+                ColorNode::new("blue", Color::new(0., 0., 1., 1.), 2, 60),
+            ]
+        );
+        assert_eq!(
+            parse_gradient(
+                "radial-gradient(circle at center, red, transparent 10px)",
+                1,
+                1,
+                &registry
+            )
+            .into_iter()
+            .map(|node| node.matched)
+            .collect::<Vec<_>>(),
+            vec!["red", "transparent"]
+        );
+
+        let text =
+            "a { background: conic-gradient(from 0.25turn, red 10deg, #0f0 20deg); color: #00f; }";
+        assert_eq!(
+            parse(text),
+            vec![
+                ColorNode::new("red", Color::new(1., 0., 0., 1.), 1, 47),
+                ColorNode::new("#0f0", Color::new(0., 1., 0., 1.), 1, 58),
+                ColorNode::new("#00f", Color::new(0., 0., 1., 1.), 1, 78),
+            ]
         );
+
+        // The stops are matched by the registry, with the custom patterns and the config
+        struct BrandPattern;
+
+        impl ColorPattern for BrandPattern {
+            fn try_match(&self, text: &str, line: usize, col: usize) -> Option<ColorNode> {
+                text.starts_with("$brand")
+                    .then(|| ColorNode::new("$brand", Color::new(1., 0., 0., 1.), line, col))
+            }
+        }
+
+        let config = Config {
+            enable_hex: false,
+            ..Default::default()
+        };
+        let mut registry = ColorPatternRegistry::new();
+        registry
+            .register(BrandPattern)
+            .register(ColorPatternRegistry::builtin("css", &config));
+        assert_eq!(
+            parse_gradient("linear-gradient($brand, #fff, red)", 1, 1, &registry)
+                .into_iter()
+                .map(|node| node.matched)
+                .collect::<Vec<_>>(),
+            vec!["$brand", "red"]
+        );
+    }
+
+    #[test]
+    fn test_nearest_named_colors() {
+        let nearest = |s: &str| nearest_named_colors(&csscolorparser::parse(s).unwrap())[0];
+
+        assert_eq!(nearest("#e9967a"), ("darksalmon", 0.));
+        assert_eq!(nearest("#ff0001").0, "red");
+        assert_eq!(nearest("#808081").0, "gray");
+        assert_eq!(csscolorparser::NAMED_COLORS.len(), 148);
+    }
+
+    #[test]
+    fn test_parse_roblox_color3() {
+        let text = "local part = script.Parent\npart.Color = Color3.fromRGB(255, 128, 0)\npart.Color = Color3.new(0, 0.5, 1)\nlocal bad = Color3.fromRGB(256, 0, 0)";
+        let colors = parse_document(text, "lua", &Config::default());
+        assert_eq!(
+            colors,
+            vec![
+                ColorNode::new(
+                    "Color3.fromRGB(255, 128, 0)",
+                    Color::from_rgba8(255, 128, 0, 255),
+                    2,
+                    14
+                ),
+                ColorNode::new("Color3.new(0, 0.5, 1)", Color::new(0., 0.5, 1., 1.), 3, 14),
+            ]
+        );
+
+        let text = "local c = Color3.new(0, 0.5, 1)";
+        assert!(parse_document(text, "lua", &Config::default()).is_empty());
+        assert_eq!(parse_document(text, "luau", &Config::default()).len(), 1);
+    }
+
+    /// Separators that can't be part of a color, or extend the color before them.
+    const SEPARATOR: &str = "[ :;{}=,\n]{1,8}";
+
+    proptest::proptest! {
+        #[test]
+        fn proptest_parse_hex(
+            parts in proptest::collection::vec(
+                (SEPARATOR, "#([0-9a-f]{3}|[0-9a-f]{4}|[0-9A-F]{6}|[0-9a-f]{8})"),
+                1..8,
+            ),
+            tail in SEPARATOR,
+        ) {
+            let mut text = String::new();
+            let mut expected = vec![];
+            for (separator, hex) in &parts {
+                text.push_str(separator);
+                let line = text.matches('\n').count() + 1;
+                let col = text.len() - text.rfind('\n').map_or(0, |ix| ix + 1) + 1;
+                expected.push((hex.clone(), TextRange::new(line as u32, col as u32, hex.len() as u32)));
+                text.push_str(hex);
+            }
+            text.push_str(&tail);
+
+            let nodes = parse(&text)
+                .into_iter()
+                .map(|node| (node.matched, node.range))
+                .collect::<Vec<_>>();
+            proptest::prop_assert_eq!(nodes, expected);
+        }
+
+        #[test]
+        fn proptest_parse_functions(
+            hsl in proptest::bool::ANY,
+            values in (0u16..=360, 0u8..=100, 0u8..=100),
+            spaces in proptest::collection::vec("[ \t]{0,3}", 6),
+            prefix in SEPARATOR,
+        ) {
+            let (name, expected) = if hsl {
+                let (h, s, l) = values;
+                ("hsl", Color::from_hsla(h.into(), f32::from(s) / 100., f32::from(l) / 100., 1.))
+            } else {
+                let (r, g, b) = (values.0.min(255) as u8, values.1, values.2);
+                ("rgb", Color::from_rgba8(r, g, b, 255))
+            };
+            let args = if hsl {
+                [values.0.to_string(), format!("{}%", values.1), format!("{}%", values.2)]
+            } else {
+                [values.0.min(255).to_string(), values.1.to_string(), values.2.to_string()]
+            };
+            let function = format!(
+                "{name}({}{}{},{}{}{},{}{}{})",
+                spaces[0], args[0], spaces[1], spaces[2], args[1], spaces[3], spaces[4], args[2],
+                spaces[5],
+            );
+            let text = format!("{prefix}{function};");
+
+            let nodes = parse(&text);
+            proptest::prop_assert_eq!(nodes.len(), 1);
+            proptest::prop_assert_eq!(&nodes[0].matched, &function);
+            proptest::prop_assert_eq!(nodes[0].color.to_rgba8(), expected.to_rgba8());
+        }
+
+        #[test]
+        fn proptest_parse_nested_brackets(text in "[()\\[\\]{}#a-f0-9rgbhsl,%. \n-]{0,64}") {
+            let lines = text.lines().collect::<Vec<_>>();
+            for node in parse(&text) {
+                let TextRange { line, col, len } = node.range;
+                proptest::prop_assert!(line >= 1 && col >= 1);
+                let line = lines[line as usize - 1];
+                let start = col as usize - 1;
+                proptest::prop_assert_eq!(line.get(start..start + len as usize), Some(node.matched.as_str()));
+            }
+        }
     }
 }
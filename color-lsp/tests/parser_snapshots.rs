@@ -0,0 +1,38 @@
+//! Snapshots of the parser output for the fixtures in the `tests` directory of the workspace,
+//! review the changes with `cargo insta review`.
+use std::path::Path;
+
+use color_lsp::parser::{parse_str, ColorNode};
+use tower_lsp::lsp_types::ColorInformation;
+
+fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../tests")
+        .join(name);
+    std::fs::read_to_string(path).unwrap()
+}
+
+fn lsp_colors(nodes: &[ColorNode]) -> Vec<ColorInformation> {
+    nodes.iter().map(ColorInformation::from).collect()
+}
+
+#[test]
+fn test_json() {
+    let nodes = parse_str(&fixture("test.json"));
+    insta::assert_debug_snapshot!("test_json", nodes);
+    insta::assert_debug_snapshot!("test_json_lsp_colors", lsp_colors(&nodes));
+}
+
+#[test]
+fn test_gradient_css() {
+    let nodes = parse_str(&fixture("gradient.css"));
+    insta::assert_debug_snapshot!("gradient_css", nodes);
+    insta::assert_debug_snapshot!("gradient_css_lsp_colors", lsp_colors(&nodes));
+}
+
+#[test]
+fn test_inline_style_html() {
+    let nodes = parse_str(&fixture("inline-style.html"));
+    insta::assert_debug_snapshot!("inline_style_html", nodes);
+    insta::assert_debug_snapshot!("inline_style_html_lsp_colors", lsp_colors(&nodes));
+}
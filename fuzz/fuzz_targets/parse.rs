@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let lines = text.lines().collect::<Vec<_>>();
+    for node in color_lsp::parser::parse_str(text) {
+        let range = node.range;
+        assert!(range.line >= 1 && range.col >= 1, "{range:?}");
+
+        let line = lines[range.line as usize - 1];
+        let start = range.col as usize - 1;
+        assert!(start + range.len as usize <= line.len(), "{range:?} in {line:?}");
+    }
+});
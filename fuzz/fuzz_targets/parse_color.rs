@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    // Any result is fine, as long as it doesn't panic
+    let _ = color_lsp::parser::try_parse_color(text);
+
+    // A valid hex color is always a valid color
+    if color_lsp::parser::is_valid_hex_color(text) {
+        assert!(color_lsp::parser::is_valid_css_color(text));
+    }
+});
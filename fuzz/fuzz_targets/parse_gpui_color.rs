@@ -0,0 +1,88 @@
+#![no_main]
+
+use csscolorparser::ParseColorError;
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+/// A gpui color function call like `rgb(a, b, c)`, with the edge cases of the params.
+#[derive(Arbitrary, Debug)]
+struct Input<'a> {
+    name: Name<'a>,
+    params: Vec<Param<'a>>,
+    /// Join the params with `,` or only with spaces.
+    comma: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Name<'a> {
+    Rgb,
+    Rgba,
+    Hsl,
+    Hsla,
+    Other(&'a str),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Param<'a> {
+    value: Value<'a>,
+    leading_spaces: u8,
+    trailing_spaces: u8,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Value<'a> {
+    Float(f32),
+    Unit(f32, &'a str),
+    Text(&'a str),
+}
+
+impl Input<'_> {
+    fn to_source(&self) -> String {
+        let name = match self.name {
+            Name::Rgb => "rgb",
+            Name::Rgba => "rgba",
+            Name::Hsl => "hsl",
+            Name::Hsla => "hsla",
+            Name::Other(name) => name,
+        };
+        let params = self
+            .params
+            .iter()
+            .map(|param| {
+                let value = match param.value {
+                    Value::Float(v) => format!("{v:?}"),
+                    Value::Unit(v, unit) => format!("{v:?}{unit}"),
+                    Value::Text(text) => text.to_string(),
+                };
+                let spaces = |n: u8| " ".repeat(n as usize % 4);
+                format!(
+                    "{}{value}{}",
+                    spaces(param.leading_spaces),
+                    spaces(param.trailing_spaces)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(if self.comma { "," } else { " " });
+        format!("{name}({params})")
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let source = input.to_source();
+    match color_lsp::parser::try_parse_gpui_color(&source) {
+        Ok(color) => {
+            for v in color.to_array() {
+                assert!((0.0..=1.0).contains(&v), "{v} of {source:?}");
+            }
+        }
+        Err(err) => {
+            assert!(
+                matches!(
+                    err,
+                    ParseColorError::InvalidFunction | ParseColorError::InvalidUnknown
+                ),
+                "{err:?} of {source:?}"
+            );
+        }
+    }
+});
@@ -48,14 +48,14 @@ impl ColorHighlightExtension {
         }
 
         if let Some(path) = &self.cached_binary_path {
-            if fs::metadata(path).map_or(false, |stat| stat.is_file()) {
+            if fs::metadata(path).is_ok_and(|stat| stat.is_file()) {
                 update_status(id, Status::None);
                 return Ok(path.clone());
             }
         }
         let (platform, arch) = zed::current_platform();
 
-        if fs::metadata(BIN_NAME).map_or(false, |stat| stat.is_file()) {
+        if fs::metadata(BIN_NAME).is_ok_and(|stat| stat.is_file()) {
             update_status(id, Status::None);
             return Ok(BIN_NAME.to_string());
         }
@@ -102,7 +102,7 @@ impl ColorHighlightExtension {
         let version_dir = format!("color-lsp-{}", release.version);
         let binary_path = format!("{version_dir}/{BIN_NAME}");
 
-        if !fs::metadata(&binary_path).map_or(false, |stat| stat.is_file()) {
+        if !fs::metadata(&binary_path).is_ok_and(|stat| stat.is_file()) {
             update_status(id, Status::Downloading);
             zed::download_file(&asset.download_url, &version_dir, file_type)
                 .map_err(|e| format!("failed to download file: {e}"))?;